@@ -1,7 +1,7 @@
 
 
-use guff::{GaloisField, new_gf4, F4, new_gf8, F8,
-	   new_gf16, F16, new_gf32, F32 };
+use guff::{GaloisField, new_gf4, F4, new_gf8,
+	   new_gf16, new_gf32 };
 use guff::good::{new_gf4_0x13, new_gf8_0x11b,
 		 new_gf16_0x1002b};
 // , new_gf32_0x10000008d
@@ -0,0 +1,484 @@
+//! # Binary BCH codes
+//!
+//! A binary primitive BCH code of length n = 2<sup>m</sup> - 1 and
+//! designed distance d has as its generator g(x) the least common
+//! multiple of the minimal polynomials of α, α<sup>2</sup>, ...,
+//! α<sup>d-1</sup>, where α is a primitive element of
+//! GF(2<sup>m</sup>). It corrects any t = ⌊(d-1)/2⌋ bit errors in a
+//! codeword of n bits carrying k = n - deg g(x) message bits.
+//!
+//! Codewords and messages are [BinaryPoly] values. Encoding is
+//! systematic: the message m(x) is shifted up to the top k bits, and
+//! the low n - k bits hold the remainder of x<sup>n-k</sup>m(x)
+//! modulo g(x). Decoding computes the syndromes
+//! S<sub>j</sub> = r(α<sup>j</sup>) in GF(2<sup>m</sup>), finds the
+//! error locator with Berlekamp–Massey and its roots by Chien search.
+//! Since the code is binary, no error values are needed: each root
+//! marks a bit to flip.
+//!
+//! ## Field support
+//!
+//! The request for this module assumed runtime-order field support,
+//! which guff doesn't have: [GaloisField::ORDER] is an associated
+//! constant, so every field type fixes its size at compile time.
+//! [Bch] is therefore generic over [GaloisField], with m taken from
+//! the field type, and the usual [crate::poly], [crate::bm] and
+//! [crate::roots] routines do the arithmetic. Flash controllers want
+//! codes for every m from 8 to 16, not just the natural sizes, so
+//! [F2m] covers any 2 ≤ m ≤ 16; [default_field] builds one with the
+//! polynomial from [default_poly].
+//!
+//! ```rust
+//! use guff::bch::{Bch, default_field};
+//! use guff::binpoly::BinaryPoly;
+//!
+//! // the (1023, 923) code corrects 10 errors
+//! let f = default_field::<10>();
+//! let code = Bch::new(&f, 21).unwrap();
+//! assert_eq!((code.n(), code.k(), code.t()), (1023, 923, 10));
+//!
+//! let msg = BinaryPoly::from_bytes_be(b"some flash page data");
+//! let mut word = code.encode(&msg).unwrap();
+//! for bit in [3, 100, 555, 1000].iter() { word.flip_bit(*bit) }
+//!
+//! let fixed = code.decode(&f, &mut word).unwrap();
+//! assert_eq!(fixed, vec![3, 100, 555, 1000]);
+//! assert_eq!(code.message(&word), msg);
+//! ```
+//!
+//! When m is only known at run time (eg, read from a controller's
+//! configuration), [DynBch] picks the matching `Bch<F2m<m>>` and
+//! dispatches to it:
+//!
+//! ```rust
+//! use guff::bch::DynBch;
+//! use guff::binpoly::BinaryPoly;
+//!
+//! let m = 13;
+//! let code = DynBch::new(m, 9).unwrap();
+//! assert_eq!((code.n(), code.k()), (8191, 8191 - 52));
+//! let mut word = code.encode(&BinaryPoly::from_u64(0x1234)).unwrap();
+//! word.flip_bit(4000);
+//! assert_eq!(code.decode(&mut word).unwrap(), vec![4000]);
+//! ```
+
+use crate::{GaloisField, F2m, new_gf2m};
+use crate::binpoly::BinaryPoly;
+use crate::poly::{self, Poly};
+use crate::bm::berlekamp_massey;
+use crate::roots::{chien_search, find_generator, CHIEN_MAX_ORDER};
+
+use num::{One,Zero,FromPrimitive};
+use std::fmt;
+
+/// Errors from BCH operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BchError {
+    /// Field too big for Chien search, or designed distance out of
+    /// range or leaving no room for a message
+    BadParameters,
+    /// Message has k or more bits, or word has n or more
+    TooLong,
+    /// More errors than the code can correct
+    Uncorrectable,
+}
+
+impl fmt::Display for BchError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    BchError::BadParameters => write!(f, "bad BCH parameters"),
+	    BchError::TooLong       => write!(f, "message or codeword too long"),
+	    BchError::Uncorrectable => write!(f, "too many errors to correct"),
+	}
+    }
+}
+
+impl std::error::Error for BchError {}
+
+/// Default primitive polynomial for GF(2<sup>m</sup>), 2 ≤ m ≤ 16
+///
+/// These are the usual low-weight choices (eg, 0x11d for m = 8, as
+/// in most BCH and RS hardware).
+pub fn default_poly(m : usize) -> Option<u32> {
+    const POLYS : [u32; 15] = [
+	0x7, 0xb, 0x13, 0x25, 0x43, 0x89, 0x11d, 0x211,
+	0x409, 0x805, 0x1053, 0x201b, 0x4443, 0x8003, 0x1100b,
+    ];
+    if (2..=16).contains(&m) { Some(POLYS[m - 2]) } else { None }
+}
+
+/// GF(2<sup>m</sup>) with the [default_poly] for m
+///
+/// Panics unless 2 ≤ m ≤ 16.
+pub fn default_field<const M : u16>() -> F2m<M> {
+    let full = default_poly(M as usize).expect("field size must be from 2 to 16 bits");
+    new_gf2m(full, (full ^ 1 << M) as u16)
+}
+
+/// A binary primitive BCH code
+///
+/// m is the size of the field type G, so is fixed at compile time;
+/// see [DynBch] for choosing it at run time.
+pub struct Bch<G> where G : GaloisField {
+    n         : usize,
+    d         : usize,
+    k         : usize,
+    alpha     : G::E,
+    generator : BinaryPoly,
+}
+
+impl<G> Bch<G> where G : GaloisField {
+
+    /// Code of length n = 2<sup>m</sup> - 1 and designed distance d,
+    /// where m is the size of the field f (at most
+    /// [CHIEN_MAX_ORDER])
+    ///
+    /// α is the field's smallest generator, which is `2` for a
+    /// primitive field polynomial.
+    pub fn new(f : &G, d : usize) -> Result<Bch<G>, BchError> {
+	if G::ORDER > CHIEN_MAX_ORDER || d < 2 { return Err(BchError::BadParameters) }
+	let n = (1usize << G::ORDER) - 1;
+	if d > n { return Err(BchError::BadParameters) }
+	let one = G::E::one();
+	let alpha = find_generator(f);
+
+	// product of the minimal polynomials of α^1 ... α^(d-1), taking
+	// each cyclotomic coset once
+	let mut done = vec![false; n];
+	let mut generator = BinaryPoly::one();
+	for i in 1..d {
+	    if done[i] { continue }
+	    // minimal polynomial: product of (x + α^c) over the coset
+	    let mut min : Poly<G> = vec![one];
+	    let mut c = i;
+	    while !done[c] {
+		done[c] = true;
+		min = poly::mul(f, &min, &[Self::alpha_pow(f, alpha, c), one]);
+		c = (2 * c) % n;
+	    }
+	    let exps : Vec<usize> = min.iter().enumerate().filter(|(_, c)| **c != G::E::zero())
+		.map(|(j, c)| { debug_assert!(*c == one); j }).collect();
+	    generator = generator.mul(&BinaryPoly::from_exponents(&exps));
+	}
+	let deg = generator.degree().unwrap();
+	if deg >= n { return Err(BchError::BadParameters) }
+	Ok(Bch { n, d, k : n - deg, alpha, generator })
+    }
+
+    fn alpha_pow(f : &G, alpha : G::E, i : usize) -> G::E {
+	f.pow(alpha, G::EE::from_usize(i).unwrap())
+    }
+
+    /// Field size exponent m
+    pub fn m(&self) -> usize { G::ORDER as usize }
+
+    /// Code length 2<sup>m</sup> - 1
+    pub fn n(&self) -> usize { self.n }
+
+    /// Message length
+    pub fn k(&self) -> usize { self.k }
+
+    /// Designed distance
+    pub fn designed_distance(&self) -> usize { self.d }
+
+    /// Number of bit errors guaranteed to be correctable
+    pub fn t(&self) -> usize { (self.d - 1) / 2 }
+
+    /// Generator polynomial, of degree n - k
+    pub fn generator_poly(&self) -> &BinaryPoly { &self.generator }
+
+    /// Systematic encoding of a message of fewer than k bits
+    pub fn encode(&self, msg : &BinaryPoly) -> Result<BinaryPoly, BchError> {
+	if msg.degree().map_or(false, |d| d >= self.k) { return Err(BchError::TooLong) }
+	let shifted = msg.shl(self.n - self.k);
+	Ok(shifted.add(&shifted.rem(&self.generator)))
+    }
+
+    /// The message part of a (corrected) codeword
+    pub fn message(&self, word : &BinaryPoly) -> BinaryPoly {
+	word.divrem(&BinaryPoly::x_pow(self.n - self.k)).0
+    }
+
+    /// Syndromes S<sub>j</sub> = r(α<sup>j</sup>) for j = 1 ... d-1
+    pub fn syndromes(&self, f : &G, word : &BinaryPoly) -> Vec<G::E> {
+	let (zero, one) = (G::E::zero(), G::E::one());
+	let r : Poly<G> = (0..word.degree().map_or(0, |d| d + 1))
+	    .map(|i| if word.bit(i) { one } else { zero }).collect();
+	let mut s = Vec::with_capacity(self.d - 1);
+	let mut x = self.alpha;
+	for j in 1..self.d {
+	    // r has binary coefficients, so S_2j = S_j^2
+	    let sj = if j % 2 == 0 {
+		f.mul(s[j / 2 - 1], s[j / 2 - 1])
+	    } else {
+		poly::eval(f, &r, x)
+	    };
+	    s.push(sj);
+	    x = f.mul(x, self.alpha);
+	}
+	s
+    }
+
+    /// Correct bit errors in place
+    ///
+    /// Returns the positions that were flipped, in ascending order.
+    /// On failure, `word` is left untouched.
+    pub fn decode(&self, f : &G, word : &mut BinaryPoly) -> Result<Vec<usize>, BchError> {
+	let n = self.n;
+	if word.degree().map_or(false, |d| d >= n) { return Err(BchError::TooLong) }
+	let s = self.syndromes(f, word);
+	if s.iter().all(|x| *x == G::E::zero()) { return Ok(Vec::new()) }
+	let (lambda, l) = berlekamp_massey(f, &s);
+	if l > self.t() { return Err(BchError::Uncorrectable) }
+
+	// roots of Λ are α^-p for the positions p in error
+	let roots = chien_search(f, &lambda);
+	if roots.len() != l { return Err(BchError::Uncorrectable) }
+	let alpha_inv = f.inv(self.alpha);
+	let mut x = G::E::one();
+	let mut errors = Vec::with_capacity(l);
+	for p in 0..n {
+	    if roots.binary_search(&x).is_ok() { errors.push(p) }
+	    x = f.mul(x, alpha_inv);
+	}
+	for p in errors.iter() { word.flip_bit(*p) }
+	Ok(errors)
+    }
+}
+
+// one variant per supported m, holding the field alongside the code
+enum DynInner {
+    M2(F2m<2>, Bch<F2m<2>>),
+    M3(F2m<3>, Bch<F2m<3>>),
+    M4(F2m<4>, Bch<F2m<4>>),
+    M5(F2m<5>, Bch<F2m<5>>),
+    M6(F2m<6>, Bch<F2m<6>>),
+    M7(F2m<7>, Bch<F2m<7>>),
+    M8(F2m<8>, Bch<F2m<8>>),
+    M9(F2m<9>, Bch<F2m<9>>),
+    M10(F2m<10>, Bch<F2m<10>>),
+    M11(F2m<11>, Bch<F2m<11>>),
+    M12(F2m<12>, Bch<F2m<12>>),
+    M13(F2m<13>, Bch<F2m<13>>),
+    M14(F2m<14>, Bch<F2m<14>>),
+    M15(F2m<15>, Bch<F2m<15>>),
+    M16(F2m<16>, Bch<F2m<16>>),
+}
+
+// run $body with $f and $c bound to the field and code of any variant
+macro_rules! dispatch {
+    ($inner:expr, $f:tt, $c:tt => $body:expr) => {
+	match $inner {
+	    DynInner::M2($f, $c)  => $body,
+	    DynInner::M3($f, $c)  => $body,
+	    DynInner::M4($f, $c)  => $body,
+	    DynInner::M5($f, $c)  => $body,
+	    DynInner::M6($f, $c)  => $body,
+	    DynInner::M7($f, $c)  => $body,
+	    DynInner::M8($f, $c)  => $body,
+	    DynInner::M9($f, $c)  => $body,
+	    DynInner::M10($f, $c) => $body,
+	    DynInner::M11($f, $c) => $body,
+	    DynInner::M12($f, $c) => $body,
+	    DynInner::M13($f, $c) => $body,
+	    DynInner::M14($f, $c) => $body,
+	    DynInner::M15($f, $c) => $body,
+	    DynInner::M16($f, $c) => $body,
+	}
+    }
+}
+
+fn build<const M : u16>(d : usize, wrap : fn(F2m<M>, Bch<F2m<M>>) -> DynInner)
+			-> Result<DynInner, BchError> {
+    let f = default_field::<M>();
+    let code = Bch::new(&f, d)?;
+    Ok(wrap(f, code))
+}
+
+/// A [Bch] code with m chosen at run time
+///
+/// Holds the `Bch<F2m<m>>` for the requested m, along with its
+/// [default_field], and forwards to it. Field elements (syndromes)
+/// are u16 for every m.
+pub struct DynBch {
+    inner : DynInner,
+}
+
+impl DynBch {
+    /// Code of length 2<sup>m</sup> - 1 and designed distance d, for
+    /// 2 ≤ m ≤ 16, over the field with [default_poly] for m
+    pub fn new(m : usize, d : usize) -> Result<DynBch, BchError> {
+	let inner = match m {
+	    2  => build(d, DynInner::M2),
+	    3  => build(d, DynInner::M3),
+	    4  => build(d, DynInner::M4),
+	    5  => build(d, DynInner::M5),
+	    6  => build(d, DynInner::M6),
+	    7  => build(d, DynInner::M7),
+	    8  => build(d, DynInner::M8),
+	    9  => build(d, DynInner::M9),
+	    10 => build(d, DynInner::M10),
+	    11 => build(d, DynInner::M11),
+	    12 => build(d, DynInner::M12),
+	    13 => build(d, DynInner::M13),
+	    14 => build(d, DynInner::M14),
+	    15 => build(d, DynInner::M15),
+	    16 => build(d, DynInner::M16),
+	    _  => Err(BchError::BadParameters),
+	}?;
+	Ok(DynBch { inner })
+    }
+
+    /// Field size exponent m
+    pub fn m(&self) -> usize { dispatch!(&self.inner, _, c => c.m()) }
+
+    /// Code length 2<sup>m</sup> - 1
+    pub fn n(&self) -> usize { dispatch!(&self.inner, _, c => c.n()) }
+
+    /// Message length
+    pub fn k(&self) -> usize { dispatch!(&self.inner, _, c => c.k()) }
+
+    /// Designed distance
+    pub fn designed_distance(&self) -> usize {
+	dispatch!(&self.inner, _, c => c.designed_distance())
+    }
+
+    /// Number of bit errors guaranteed to be correctable
+    pub fn t(&self) -> usize { dispatch!(&self.inner, _, c => c.t()) }
+
+    /// Generator polynomial, of degree n - k
+    pub fn generator_poly(&self) -> &BinaryPoly {
+	dispatch!(&self.inner, _, c => c.generator_poly())
+    }
+
+    /// As [Bch::encode]
+    pub fn encode(&self, msg : &BinaryPoly) -> Result<BinaryPoly, BchError> {
+	dispatch!(&self.inner, _, c => c.encode(msg))
+    }
+
+    /// As [Bch::message]
+    pub fn message(&self, word : &BinaryPoly) -> BinaryPoly {
+	dispatch!(&self.inner, _, c => c.message(word))
+    }
+
+    /// As [Bch::syndromes]
+    pub fn syndromes(&self, word : &BinaryPoly) -> Vec<u16> {
+	dispatch!(&self.inner, f, c => c.syndromes(f, word))
+    }
+
+    /// As [Bch::decode]
+    pub fn decode(&self, word : &mut BinaryPoly) -> Result<Vec<usize>, BchError> {
+	dispatch!(&self.inner, f, c => c.decode(f, word))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::good::new_gf8_0x11b;
+
+    #[test]
+    fn default_polys_are_primitive() {
+	for m in 2..=16 {
+	    let p = BinaryPoly::from_u64(default_poly(m).unwrap() as u64);
+	    assert_eq!(p.degree(), Some(m));
+	    assert!(p.is_primitive(), "m = {}", m);
+	}
+	assert_eq!(default_poly(17), None);
+	assert_eq!(find_generator(&default_field::<13>()), 2);
+    }
+
+    #[test]
+    fn known_code_parameters() {
+	// classic (15, 7) and (15, 5) codes
+	let f = default_field::<4>();
+	let c = Bch::new(&f, 5).unwrap();
+	assert_eq!((c.n(), c.k()), (15, 7));
+	assert_eq!(c.generator_poly(), &BinaryPoly::from_u64(0x1d1));
+	assert_eq!(Bch::new(&f, 7).unwrap().k(), 5);
+	assert_eq!(Bch::new(&f, 16).err(), Some(BchError::BadParameters));
+	// (255, 231) with t = 3, and the m = 13 code used for 512-byte
+	// flash sectors with t = 4
+	assert_eq!(Bch::new(&default_field::<8>(), 7).unwrap().k(), 231);
+	assert_eq!(Bch::new(&default_field::<13>(), 9).unwrap().k(), 8191 - 52);
+	// too big for Chien search
+	assert_eq!(Bch::new(&crate::new_gf32(0x1_0000_008d, 0x8d), 5).err(),
+		   Some(BchError::BadParameters));
+    }
+
+    fn corrects_up_to_t<G : GaloisField>(f : &G) {
+	let code = Bch::new(f, 9).unwrap();
+	let (m, n) = (code.m(), code.n());
+	let msg = BinaryPoly::from_words(vec![0x0123_4567_89ab_cdef, 0xdead_beef]);
+	let clean = code.encode(&msg).unwrap();
+	assert!(clean.rem(code.generator_poly()).is_zero());
+	assert!(code.syndromes(f, &clean).iter().all(|s| *s == G::E::zero()));
+	for errs in 1..=4 {
+	    let mut word = clean.clone();
+	    let mut pos : Vec<usize> = (0..errs).map(|i| (i * 7919 + m * 31) % n).collect();
+	    for p in pos.iter() { word.flip_bit(*p) }
+	    pos.sort();
+	    assert_eq!(code.decode(f, &mut word).unwrap(), pos);
+	    assert_eq!(word, clean);
+	}
+	assert_eq!(code.message(&clean), msg);
+    }
+
+    #[test]
+    fn corrects_up_to_t_for_all_m() {
+	corrects_up_to_t(&default_field::<8>());
+	corrects_up_to_t(&default_field::<9>());
+	corrects_up_to_t(&default_field::<10>());
+	corrects_up_to_t(&default_field::<11>());
+	corrects_up_to_t(&default_field::<12>());
+	corrects_up_to_t(&default_field::<13>());
+	corrects_up_to_t(&default_field::<14>());
+	corrects_up_to_t(&default_field::<15>());
+	corrects_up_to_t(&default_field::<16>());
+	// natural-size fields work too, primitive polynomial or not
+	corrects_up_to_t(&new_gf8_0x11b());
+    }
+
+    #[test]
+    fn runtime_m_matches_static_codes() {
+	let dynamic = DynBch::new(13, 9).unwrap();
+	let fixed = Bch::new(&default_field::<13>(), 9).unwrap();
+	assert_eq!(dynamic.m(), 13);
+	assert_eq!(dynamic.generator_poly(), fixed.generator_poly());
+	for m in 8..=16 {
+	    let code = DynBch::new(m, 7).unwrap();
+	    assert_eq!((code.m(), code.n(), code.t()), (m, (1 << m) - 1, 3));
+	    let msg = BinaryPoly::from_u64(0xfeed_f00d);
+	    let clean = code.encode(&msg).unwrap();
+	    let mut word = clean.clone();
+	    for p in [1, 77, code.n() - 1].iter() { word.flip_bit(*p) }
+	    assert_eq!(code.syndromes(&word).len(), 6);
+	    assert_eq!(code.decode(&mut word).unwrap(), vec![1, 77, code.n() - 1]);
+	    assert_eq!(code.message(&word), msg);
+	}
+	assert_eq!(DynBch::new(17, 5).err(), Some(BchError::BadParameters));
+	assert_eq!(DynBch::new(1, 5).err(), Some(BchError::BadParameters));
+	assert_eq!(DynBch::new(4, 16).err(), Some(BchError::BadParameters));
+    }
+
+    #[test]
+    fn reports_uncorrectable() {
+	let f = default_field::<8>();
+	let code = Bch::new(&f, 7).unwrap();
+	let clean = code.encode(&BinaryPoly::from_u64(0x5555)).unwrap();
+	let mut failed = 0;
+	for trial in 0..20 {
+	    let mut word = clean.clone();
+	    for i in 0..5 { word.flip_bit((i * 41 + trial * 13) % 255) }
+	    let before = word.clone();
+	    match code.decode(&f, &mut word) {
+		Err(e) => { assert_eq!(e, BchError::Uncorrectable); assert_eq!(word, before); failed += 1 }
+		Ok(_)  => assert_ne!(word, clean),
+	    }
+	}
+	assert!(failed > 10);
+	assert_eq!(code.encode(&BinaryPoly::x_pow(231)).err(), Some(BchError::TooLong));
+	assert_eq!(code.decode(&f, &mut BinaryPoly::x_pow(255)).err(), Some(BchError::TooLong));
+    }
+}
@@ -0,0 +1,523 @@
+//! # Arbitrary-length polynomials over GF(2)
+//!
+//! Field polynomials elsewhere in this crate are stored as plain
+//! integers, and [GaloisField::mull](crate::GaloisField::mull) and
+//! [GaloisField::mod_reduce](crate::GaloisField::mod_reduce) only work
+//! on products that fit in `EE`. [BinaryPoly] lifts that restriction:
+//! it stores a polynomial in GF(2)\[x\] as a vector of `u64` words,
+//! with bit i of the whole vector being the coefficient of
+//! x<sup>i</sup>.
+//!
+//! Multiplication of words reuses the long multiplication table in
+//! [crate::tables::mull], with Karatsuba multiplication taking over
+//! for operands above [KARATSUBA_THRESHOLD] words.
+//!
+//! Typical uses are CRC design, testing large polynomials for
+//! irreducibility and Rabin fingerprinting:
+//!
+//! ```rust
+//! use guff::binpoly::BinaryPoly;
+//!
+//! // x^127 + x + 1 is irreducible; x^8 + 1 = (x + 1)^8 is not
+//! let p = BinaryPoly::from_exponents(&[127, 1, 0]);
+//! assert!(p.is_irreducible());
+//! assert!(!BinaryPoly::from_u64(0x101).is_irreducible());
+//!
+//! // Rabin fingerprint of a message: message mod p
+//! let msg = BinaryPoly::from_bytes_be(b"the quick brown fox");
+//! let fp = msg.rem(&p);
+//! assert!(fp.degree().unwrap() < 127);
+//! ```
+
+use crate::tables::mull::rmull;
+
+/// Operands of at least this many words are multiplied using
+/// Karatsuba's method
+pub const KARATSUBA_THRESHOLD : usize = 16;
+
+/// A polynomial over GF(2) of any degree
+///
+/// Always kept normalised (no high zero words), so the zero
+/// polynomial has no words at all.
+#[derive(Debug,Clone,PartialEq,Eq,Default)]
+pub struct BinaryPoly {
+    words : Vec<u64>,
+}
+
+/// Carry-less multiplication of two 64-bit words
+///
+/// Uses the 8-bit by 4-bit [MULL](crate::tables::mull::MULL) table,
+/// so it takes (at most) 128 lookups.
+pub fn clmul64(a : u64, b : u64) -> u128 {
+    let mut result : u128 = 0;
+    for i in 0..8 {
+	let byte = (a >> (8 * i)) as u8;
+	if byte == 0 { continue }
+	for j in 0..16 {
+	    let nibble = ((b >> (4 * j)) & 0x0f) as u8;
+	    result ^= (rmull(byte, nibble) as u128) << (8 * i + 4 * j);
+	}
+    }
+    result
+}
+
+// dest ^= src, with dest long enough
+fn xor_into(dest : &mut [u64], src : &[u64]) {
+    for (d, s) in dest.iter_mut().zip(src.iter()) {
+	*d ^= *s
+    }
+}
+
+fn schoolbook(a : &[u64], b : &[u64], out : &mut [u64]) {
+    for (i, ai) in a.iter().enumerate() {
+	if *ai == 0 { continue }
+	for (j, bj) in b.iter().enumerate() {
+	    let p = clmul64(*ai, *bj);
+	    out[i + j]     ^= p as u64;
+	    out[i + j + 1] ^= (p >> 64) as u64;
+	}
+    }
+}
+
+// out ^= a * b, where out has at least a.len() + b.len() words
+fn mul_words(a : &[u64], b : &[u64], out : &mut [u64]) {
+    let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    if b.is_empty() { return }
+    if b.len() < KARATSUBA_THRESHOLD {
+	return schoolbook(a, b, out)
+    }
+    if b.len() <= a.len() / 2 {
+	// very unbalanced: multiply b by b-sized chunks of a
+	for (i, chunk) in a.chunks(b.len()).enumerate() {
+	    let off = i * b.len();
+	    mul_words(chunk, b, &mut out[off..]);
+	}
+	return
+    }
+    // Karatsuba: a = a0 + a1·X, b = b0 + b1·X with X = x^(64m)
+    //   a·b = z0 + (z1 - z0 - z2)·X + z2·X²
+    // where z1 = (a0 + a1)(b0 + b1)
+    let m = a.len() / 2;
+    let (a0, a1) = a.split_at(m);
+    let (b0, b1) = b.split_at(m);
+
+    let mut z0 = vec![0u64; 2 * m];
+    mul_words(a0, b0, &mut z0);
+    let mut z2 = vec![0u64; a1.len() + b1.len()];
+    mul_words(a1, b1, &mut z2);
+
+    let mut sa = a1.to_vec();
+    xor_into(&mut sa, a0);
+    let mut sb = vec![0u64; m.max(b1.len())];
+    sb[..b1.len()].copy_from_slice(b1);
+    xor_into(&mut sb, b0);
+    let mut z1 = vec![0u64; sa.len() + sb.len()];
+    mul_words(&sa, &sb, &mut z1);
+    xor_into(&mut z1, &z0);
+    xor_into(&mut z1, &z2);
+
+    xor_into(out, &z0);
+    xor_into(&mut out[m..], &z1);
+    xor_into(&mut out[2 * m..], &z2);
+}
+
+// Distinct prime factors of n (for the irreducibility test)
+fn prime_factors(mut n : usize) -> Vec<usize> {
+    let mut factors = Vec::new();
+    let mut p = 2;
+    while p * p <= n {
+	if n % p == 0 {
+	    factors.push(p);
+	    while n % p == 0 { n /= p }
+	}
+	p += 1;
+    }
+    if n > 1 { factors.push(n) }
+    factors
+}
+
+impl BinaryPoly {
+
+    /// The zero polynomial
+    pub fn new() -> BinaryPoly {
+	BinaryPoly { words : Vec::new() }
+    }
+
+    /// The constant polynomial 1
+    pub fn one() -> BinaryPoly {
+	BinaryPoly { words : vec![1] }
+    }
+
+    /// Build from a vector of words, least significant word first
+    pub fn from_words(words : Vec<u64>) -> BinaryPoly {
+	let mut p = BinaryPoly { words };
+	p.normalise();
+	p
+    }
+
+    /// Build from a single word, eg a field polynomial such as 0x11b
+    pub fn from_u64(w : u64) -> BinaryPoly {
+	BinaryPoly::from_words(vec![w])
+    }
+
+    /// Build from a list of exponents with non-zero coefficients
+    ///
+    /// Repeated exponents cancel each other out.
+    pub fn from_exponents(exps : &[usize]) -> BinaryPoly {
+	let mut p = BinaryPoly::new();
+	for e in exps { p.flip_bit(*e) }
+	p
+    }
+
+    /// Build from a byte string, taking the first bit of the first
+    /// byte as the highest power of x (the usual convention for CRCs
+    /// and fingerprints)
+    pub fn from_bytes_be(bytes : &[u8]) -> BinaryPoly {
+	let mut words = vec![0u64; (bytes.len() + 7) / 8];
+	for (i, b) in bytes.iter().rev().enumerate() {
+	    words[i / 8] |= (*b as u64) << (8 * (i % 8));
+	}
+	BinaryPoly::from_words(words)
+    }
+
+    /// The monomial x<sup>n</sup>
+    pub fn x_pow(n : usize) -> BinaryPoly {
+	BinaryPoly::from_exponents(&[n])
+    }
+
+    fn normalise(&mut self) {
+	while let Some(0) = self.words.last() { self.words.pop(); }
+    }
+
+    /// Underlying words, least significant first
+    pub fn words(&self) -> &[u64] {
+	&self.words
+    }
+
+    /// Degree of the polynomial, or `None` for the zero polynomial
+    pub fn degree(&self) -> Option<usize> {
+	self.words.last().map(|w| {
+	    64 * (self.words.len() - 1) + 63 - w.leading_zeros() as usize
+	})
+    }
+
+    /// Test whether this is the zero polynomial
+    pub fn is_zero(&self) -> bool {
+	self.words.is_empty()
+    }
+
+    /// Test whether this is the constant polynomial 1
+    pub fn is_one(&self) -> bool {
+	self.words.len() == 1 && self.words[0] == 1
+    }
+
+    /// Coefficient of x<sup>i</sup>
+    pub fn bit(&self, i : usize) -> bool {
+	match self.words.get(i / 64) {
+	    Some(w) => (w >> (i % 64)) & 1 == 1,
+	    None => false
+	}
+    }
+
+    /// Flip the coefficient of x<sup>i</sup>
+    pub fn flip_bit(&mut self, i : usize) {
+	if self.words.len() <= i / 64 {
+	    self.words.resize(i / 64 + 1, 0)
+	}
+	self.words[i / 64] ^= 1 << (i % 64);
+	self.normalise();
+    }
+
+    /// Iterate over the exponents with non-zero coefficients, in
+    /// ascending order
+    pub fn bits(&self) -> impl Iterator<Item = usize> + '_ {
+	self.words.iter().enumerate().flat_map(|(i, w)| {
+	    let mut w = *w;
+	    std::iter::from_fn(move || {
+		if w == 0 { return None }
+		let b = w.trailing_zeros() as usize;
+		w &= w - 1;
+		Some(64 * i + b)
+	    })
+	})
+    }
+
+    /// Sum (or difference) of two polynomials
+    pub fn add(&self, other : &BinaryPoly) -> BinaryPoly {
+	let (long, short) = if self.words.len() >= other.words.len() {
+	    (self, other)
+	} else {
+	    (other, self)
+	};
+	let mut words = long.words.clone();
+	xor_into(&mut words, &short.words);
+	BinaryPoly::from_words(words)
+    }
+
+    /// Multiply by x<sup>n</sup>
+    pub fn shl(&self, n : usize) -> BinaryPoly {
+	if self.is_zero() { return BinaryPoly::new() }
+	let (wshift, bshift) = (n / 64, n % 64);
+	let mut words = vec![0u64; self.words.len() + wshift + 1];
+	for (i, w) in self.words.iter().enumerate() {
+	    words[i + wshift] |= w << bshift;
+	    if bshift > 0 {
+		words[i + wshift + 1] |= w >> (64 - bshift);
+	    }
+	}
+	BinaryPoly::from_words(words)
+    }
+
+    /// Product of two polynomials
+    pub fn mul(&self, other : &BinaryPoly) -> BinaryPoly {
+	if self.is_zero() || other.is_zero() { return BinaryPoly::new() }
+	let mut words = vec![0u64; self.words.len() + other.words.len()];
+	mul_words(&self.words, &other.words, &mut words);
+	BinaryPoly::from_words(words)
+    }
+
+    /// Square of the polynomial
+    ///
+    /// Squaring is linear over GF(2), so this just spreads the bits
+    /// out, with no need for any multiplication.
+    pub fn square(&self) -> BinaryPoly {
+	fn spread(mut x : u64) -> u64 {
+	    // x has 32 significant bits
+	    x = (x | (x << 16)) & 0x0000_ffff_0000_ffff;
+	    x = (x | (x <<  8)) & 0x00ff_00ff_00ff_00ff;
+	    x = (x | (x <<  4)) & 0x0f0f_0f0f_0f0f_0f0f;
+	    x = (x | (x <<  2)) & 0x3333_3333_3333_3333;
+	    x = (x | (x <<  1)) & 0x5555_5555_5555_5555;
+	    x
+	}
+	let mut words = Vec::with_capacity(self.words.len() * 2);
+	for w in self.words.iter() {
+	    words.push(spread(w & 0xffff_ffff));
+	    words.push(spread(w >> 32));
+	}
+	BinaryPoly::from_words(words)
+    }
+
+    /// Polynomial long division, returning (quotient, remainder)
+    ///
+    /// Panics if the divisor is zero.
+    pub fn divrem(&self, divisor : &BinaryPoly) -> (BinaryPoly, BinaryPoly) {
+	let db = divisor.degree().expect("polynomial division by zero");
+	let mut r = self.clone();
+	let mut q = BinaryPoly::new();
+	while let Some(dr) = r.degree() {
+	    if dr < db { break }
+	    let shift = dr - db;
+	    q.flip_bit(shift);
+	    // r -= divisor << shift, without allocating a new poly
+	    let (wshift, bshift) = (shift / 64, shift % 64);
+	    for (i, w) in divisor.words.iter().enumerate() {
+		r.words[i + wshift] ^= w << bshift;
+		if bshift > 0 && i + wshift + 1 < r.words.len() {
+		    r.words[i + wshift + 1] ^= w >> (64 - bshift);
+		}
+	    }
+	    r.normalise();
+	}
+	(q, r)
+    }
+
+    /// Remainder modulo another polynomial
+    pub fn rem(&self, m : &BinaryPoly) -> BinaryPoly {
+	self.divrem(m).1
+    }
+
+    /// Greatest common divisor
+    pub fn gcd(&self, other : &BinaryPoly) -> BinaryPoly {
+	let mut a = self.clone();
+	let mut b = other.clone();
+	while !b.is_zero() {
+	    let r = a.rem(&b);
+	    a = b;
+	    b = r;
+	}
+	a
+    }
+
+    /// (self · other) mod m
+    pub fn mulmod(&self, other : &BinaryPoly, m : &BinaryPoly) -> BinaryPoly {
+	self.mul(other).rem(m)
+    }
+
+    /// self<sup>e</sup> mod m, by square and multiply
+    pub fn powmod(&self, mut e : u64, m : &BinaryPoly) -> BinaryPoly {
+	let mut result = BinaryPoly::one().rem(m);
+	let mut base = self.rem(m);
+	while e > 0 {
+	    if e & 1 == 1 { result = result.mulmod(&base, m) }
+	    e >>= 1;
+	    if e > 0 { base = base.square().rem(m) }
+	}
+	result
+    }
+
+    /// self<sup>2<sup>k</sup></sup> mod m, by k squarings
+    pub fn pow_2k_mod(&self, k : usize, m : &BinaryPoly) -> BinaryPoly {
+	let mut r = self.rem(m);
+	for _ in 0..k { r = r.square().rem(m) }
+	r
+    }
+
+    /// Test for irreducibility over GF(2) (Rabin's test)
+    ///
+    /// A polynomial p of degree n is irreducible if and only if
+    /// x<sup>2<sup>n</sup></sup> = x mod p, and
+    /// gcd(x<sup>2<sup>n/r</sup></sup> - x, p) = 1 for every prime
+    /// r dividing n.
+    pub fn is_irreducible(&self) -> bool {
+	let n = match self.degree() {
+	    None | Some(0) => return false,
+	    Some(n) => n
+	};
+	let x = BinaryPoly::x_pow(1);
+	for r in prime_factors(n) {
+	    let h = x.pow_2k_mod(n / r, self).add(&x);
+	    if !h.gcd(self).is_one() { return false }
+	}
+	x.pow_2k_mod(n, self) == x.rem(self)
+    }
+
+    /// Test whether the polynomial is primitive
+    ///
+    /// A primitive polynomial of degree n is irreducible, and x has
+    /// order 2<sup>n</sup> - 1 modulo it. Such polynomials give
+    /// maximal-length LFSRs (see [crate::lfsr]). Supports degrees up
+    /// to 64, though factoring 2<sup>n</sup> - 1 (by trial division)
+    /// can be slow for some degrees above 32.
+    pub fn is_primitive(&self) -> bool {
+	let n = match self.degree() {
+	    None | Some(0) => return false,
+	    Some(n) => n
+	};
+	assert!(n <= 64, "degree too large for primitivity test");
+	if !self.is_irreducible() { return false }
+	let group = u64::MAX >> (64 - n);
+	let x = BinaryPoly::x_pow(1);
+	let one = BinaryPoly::one().rem(self);
+	crate::roots::group_order_factors(n as u16).iter().all(|r| {
+	    x.powmod(group / r, self) != one
+	})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{GaloisField, F32};
+
+    // xorshift for test data
+    fn words(n : usize, seed : u64) -> Vec<u64> {
+	let mut x = seed;
+	(0..n).map(|_| { x ^= x << 13; x ^= x >> 7; x ^= x << 17; x })
+	    .collect()
+    }
+
+    // bit-at-a-time reference multiply
+    fn slow_mul(a : &BinaryPoly, b : &BinaryPoly) -> BinaryPoly {
+	let mut r = BinaryPoly::new();
+	for i in a.bits() { r = r.add(&b.shl(i)) }
+	r
+    }
+
+    #[test]
+    fn clmul64_vs_reference_mull() {
+	for (a, b) in [(0x1234_5678u32, 0x9abc_def0u32),
+		       (0xffff_ffff, 0xffff_ffff), (1, 0x8000_0001)].iter() {
+	    assert_eq!(clmul64(*a as u64, *b as u64),
+		       F32::mull(*a, *b) as u128);
+	}
+    }
+
+    #[test]
+    fn degree_and_bits() {
+	let p = BinaryPoly::from_exponents(&[200, 64, 3, 0]);
+	assert_eq!(p.degree(), Some(200));
+	assert_eq!(p.bits().collect::<Vec<_>>(), vec![0, 3, 64, 200]);
+	assert!(p.bit(64) && !p.bit(65));
+	assert_eq!(BinaryPoly::new().degree(), None);
+	assert_eq!(BinaryPoly::from_bytes_be(&[0x01, 0x1b]),
+		   BinaryPoly::from_u64(0x11b));
+    }
+
+    #[test]
+    fn mul_and_square() {
+	let a = BinaryPoly::from_words(words(3, 1));
+	let b = BinaryPoly::from_words(words(5, 2));
+	assert_eq!(a.mul(&b), slow_mul(&a, &b));
+	assert_eq!(a.square(), a.mul(&a));
+    }
+
+    #[test]
+    fn karatsuba_vs_schoolbook() {
+	for (la, lb) in [(40, 40), (57, 33), (100, 17), (130, 64)].iter() {
+	    let a = words(*la, 3);
+	    let b = words(*lb, 4);
+	    let mut want = vec![0u64; la + lb];
+	    schoolbook(&a, &b, &mut want);
+	    let got = BinaryPoly::from_words(a).mul(&BinaryPoly::from_words(b));
+	    assert_eq!(got, BinaryPoly::from_words(want));
+	}
+    }
+
+    #[test]
+    fn divrem_and_gcd() {
+	let a = BinaryPoly::from_words(words(7, 5));
+	let b = BinaryPoly::from_words(words(2, 6));
+	let (q, r) = a.divrem(&b);
+	assert!(r.degree() < b.degree());
+	assert_eq!(q.mul(&b).add(&r), a);
+
+	let common = BinaryPoly::from_exponents(&[127, 1, 0]);
+	let g = common.mul(&a).gcd(&common.mul(&b));
+	assert_eq!(g.rem(&common), BinaryPoly::new());
+    }
+
+    #[test]
+    fn crc32_remainder() {
+	// non-reflected CRC with zero init is just the remainder of
+	// msg · x^32 modulo the generator
+	let gen = 0x1_04c1_1db7u64;
+	let msg = b"123456789";
+	let mut crc : u32 = 0;
+	for byte in msg.iter() {
+	    crc ^= (*byte as u32) << 24;
+	    for _ in 0..8 {
+		crc = if crc & 0x8000_0000 != 0 {
+		    (crc << 1) ^ (gen as u32)
+		} else { crc << 1 };
+	    }
+	}
+	let r = BinaryPoly::from_bytes_be(msg).shl(32)
+	    .rem(&BinaryPoly::from_u64(gen));
+	assert_eq!(r.words(), &[crc as u64]);
+    }
+
+    #[test]
+    fn irreducibility() {
+	// field polys used elsewhere in the crate
+	for p in [0x13u64, 0x11b, 0x11d, 0x1002b, 0x1_0000_008d].iter() {
+	    assert!(BinaryPoly::from_u64(*p).is_irreducible(), "{:x}", p);
+	}
+	// GF(2^64) poly x^64 + x^4 + x^3 + x + 1
+	assert!(BinaryPoly::from_exponents(&[64, 4, 3, 1, 0]).is_irreducible());
+	let a = BinaryPoly::from_exponents(&[64, 4, 3, 1, 0]);
+	assert!(!a.mul(&BinaryPoly::from_u64(0x13)).is_irreducible());
+	assert!(!BinaryPoly::from_u64(0x1fe).is_irreducible()); // x·(...)
+    }
+
+    #[test]
+    fn primitivity() {
+	// 0x11b is irreducible but not primitive (generator is 3, not 2)
+	assert!(BinaryPoly::from_u64(0x11d).is_primitive());
+	assert!(!BinaryPoly::from_u64(0x11b).is_primitive());
+	assert!(BinaryPoly::from_u64(0x13).is_primitive());
+	assert!(!BinaryPoly::from_u64(0x1f).is_primitive());
+	assert!(BinaryPoly::from_exponents(&[31, 3, 0]).is_primitive());
+    }
+}
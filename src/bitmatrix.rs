@@ -0,0 +1,483 @@
+//! # Binary matrices and XOR schedules
+//!
+//! Multiplying by a constant e in GF(2<sup>w</sup>) is a linear map
+//! on the w bits of an element, so it can be written as a w×w matrix
+//! over GF(2). Expanding every element of a coding matrix this way
+//! (as Cauchy Reed-Solomon codes do) turns encoding into nothing but
+//! XORs of whole packets, with no field multiplications at all:
+//!
+//! * each k-symbol data block is split into k·w packets, packet
+//!   j·w + c holding bit c of each of device j's elements
+//!   ("bit-slicing")
+//!
+//! * output packet i·w + r is the XOR of the input packets whose bits
+//!   are set in row i·w + r of the expanded matrix
+//!
+//! The packets themselves are just byte slices, so each XOR works on
+//! many elements at once.
+//!
+//! A [Schedule] is a list of packet copy/XOR operations that computes
+//! all the outputs. [Schedule::naive] does one XOR per set bit (less
+//! one per row), while [Schedule::with_cse] looks for pairs of inputs
+//! that are used together in several rows and computes them once into
+//! a temporary packet (Paar's greedy common-subexpression
+//! elimination).
+//!
+//! ```rust
+//! use guff::{GaloisField, new_gf8};
+//! use guff::bitmatrix::{BitMatrix, Schedule};
+//!
+//! let f = new_gf8(0x11d, 0x1d);
+//!
+//! // multiplication by 3 is x·a + a, as a matrix over GF(2)
+//! let m3 = BitMatrix::from_element(&f, 3);
+//! assert_eq!(m3.mul_bits(0x81), f.mul(3, 0x81) as u64);
+//!
+//! // bit-sliced: eight packets in, eight packets out
+//! let input : Vec<Vec<u8>> = (0..8).map(|i| vec![i as u8; 16]).collect();
+//! let inputs : Vec<&[u8]> = input.iter().map(|v| &v[..]).collect();
+//! let mut output = vec![vec![0u8; 16]; 8];
+//! let mut outputs : Vec<&mut [u8]> = output.iter_mut().map(|v| &mut v[..]).collect();
+//!
+//! let sched = Schedule::with_cse(&m3);
+//! assert!(sched.xor_count() <= Schedule::naive(&m3).xor_count());
+//! sched.execute(&inputs, &mut outputs);
+//! ```
+
+use crate::GaloisField;
+use crate::matrix::Matrix;
+
+use num::{One,ToPrimitive};
+use std::collections::BTreeMap;
+
+/// Dense matrix over GF(2), with rows packed into 64-bit words
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitMatrix {
+    rows  : usize,
+    cols  : usize,
+    words : usize,
+    data  : Vec<u64>,
+}
+
+impl BitMatrix {
+
+    /// All-zero matrix
+    pub fn new(rows : usize, cols : usize) -> BitMatrix {
+	let words = (cols + 63) / 64;
+	BitMatrix { rows, cols, words, data : vec![0; rows * words] }
+    }
+
+    /// n×n identity matrix
+    pub fn identity(n : usize) -> BitMatrix {
+	let mut m = BitMatrix::new(n, n);
+	for i in 0..n { m.set(i, i, true) }
+	m
+    }
+
+    /// The w×w matrix of multiplication by `e` in field `f`
+    ///
+    /// Column j holds the bits of e·x<sup>j</sup>, with bit i in row
+    /// i, so that the matrix times the bit vector of a gives the bits
+    /// of e·a.
+    pub fn from_element<G>(f : &G, e : G::E) -> BitMatrix
+    where G : GaloisField
+    {
+	let w = G::ORDER as usize;
+	let mut m = BitMatrix::new(w, w);
+	m.set_block(f, 0, 0, e);
+	m
+    }
+
+    /// Expand every element of a field matrix into its w×w
+    /// multiplication matrix
+    pub fn from_matrix<G>(f : &G, fm : &Matrix<G>) -> BitMatrix
+    where G : GaloisField
+    {
+	let w = G::ORDER as usize;
+	let mut m = BitMatrix::new(fm.rows() * w, fm.cols() * w);
+	for r in 0..fm.rows() {
+	    for c in 0..fm.cols() {
+		m.set_block(f, r * w, c * w, fm[(r, c)]);
+	    }
+	}
+	m
+    }
+
+    fn set_block<G>(&mut self, f : &G, row : usize, col : usize, e : G::E)
+    where G : GaloisField
+    {
+	let two = G::E::one() << 1;
+	let mut p = e;
+	for j in 0..G::ORDER as usize {
+	    let bits = p.to_u64().unwrap();
+	    for i in 0..G::ORDER as usize {
+		if (bits >> i) & 1 == 1 { self.set(row + i, col + j, true) }
+	    }
+	    p = f.mul(p, two);
+	}
+    }
+
+    /// Number of rows
+    pub fn rows(&self) -> usize { self.rows }
+
+    /// Number of columns
+    pub fn cols(&self) -> usize { self.cols }
+
+    /// Read one bit
+    pub fn get(&self, r : usize, c : usize) -> bool {
+	assert!(r < self.rows && c < self.cols);
+	(self.data[r * self.words + c / 64] >> (c % 64)) & 1 == 1
+    }
+
+    /// Write one bit
+    pub fn set(&mut self, r : usize, c : usize, bit : bool) {
+	assert!(r < self.rows && c < self.cols);
+	let w = &mut self.data[r * self.words + c / 64];
+	if bit { *w |= 1 << (c % 64) } else { *w &= !(1 << (c % 64)) }
+    }
+
+    /// Columns with a set bit in row r, in ascending order
+    pub fn row_ones(&self, r : usize) -> impl Iterator<Item = usize> + '_ {
+	(0..self.cols).filter(move |c| self.get(r, *c))
+    }
+
+    /// Total number of set bits
+    pub fn count_ones(&self) -> usize {
+	self.data.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Product over GF(2)
+    pub fn mul(&self, other : &BitMatrix) -> BitMatrix {
+	assert_eq!(self.cols, other.rows, "matrix dimensions don't match");
+	let mut prod = BitMatrix::new(self.rows, other.cols);
+	for r in 0..self.rows {
+	    for k in self.row_ones(r) {
+		let src = &other.data[k * other.words .. (k + 1) * other.words];
+		let dest = &mut prod.data[r * prod.words .. (r + 1) * prod.words];
+		for (d, s) in dest.iter_mut().zip(src) { *d ^= *s }
+	    }
+	}
+	prod
+    }
+
+    /// Multiply a bit vector of up to 64 bits (bit i = element i)
+    pub fn mul_bits(&self, v : u64) -> u64 {
+	assert!(self.cols <= 64 && self.rows <= 64);
+	let mut out = 0;
+	for r in 0..self.rows {
+	    let parity = (self.data[r * self.words] & v).count_ones() & 1;
+	    out |= (parity as u64) << r;
+	}
+	out
+    }
+}
+
+/// A packet operand in a [Schedule]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Packet {
+    /// Input packet (column of the bit matrix)
+    Input(usize),
+    /// Output packet (row of the bit matrix)
+    Output(usize),
+    /// Intermediate packet allocated by the executor
+    Temp(usize),
+}
+
+/// A single schedule step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XorOp {
+    /// dest = 0
+    Zero(Packet),
+    /// dest = src (dest, src)
+    Copy(Packet, Packet),
+    /// dest ^= src (dest, src)
+    Xor(Packet, Packet),
+}
+
+/// Sequence of packet operations computing bit matrix · inputs
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    inputs  : usize,
+    outputs : usize,
+    temps   : usize,
+    ops     : Vec<XorOp>,
+}
+
+impl Schedule {
+
+    // emit ops to compute one output from a list of signals, where
+    // signal ids >= inputs refer to temporaries
+    fn emit_row(ops : &mut Vec<XorOp>, inputs : usize, r : usize, signals : &[usize]) {
+	let packet = |s : usize| if s < inputs { Packet::Input(s) } else { Packet::Temp(s - inputs) };
+	let dest = Packet::Output(r);
+	match signals.split_first() {
+	    None => ops.push(XorOp::Zero(dest)),
+	    Some((first, rest)) => {
+		ops.push(XorOp::Copy(dest, packet(*first)));
+		for s in rest { ops.push(XorOp::Xor(dest, packet(*s))) }
+	    }
+	}
+    }
+
+    /// One copy plus one XOR per additional set bit in each row
+    pub fn naive(m : &BitMatrix) -> Schedule {
+	let mut ops = Vec::new();
+	for r in 0..m.rows {
+	    let signals : Vec<usize> = m.row_ones(r).collect();
+	    Schedule::emit_row(&mut ops, m.cols, r, &signals);
+	}
+	Schedule { inputs : m.cols, outputs : m.rows, temps : 0, ops }
+    }
+
+    /// Schedule with greedy common-subexpression elimination
+    ///
+    /// Repeatedly finds the pair of signals appearing together in
+    /// the most rows, computes their XOR once into a temporary, and
+    /// substitutes it, until no pair is shared by two or more rows.
+    pub fn with_cse(m : &BitMatrix) -> Schedule {
+	let inputs = m.cols;
+	let mut rows : Vec<Vec<usize>> = (0..m.rows).map(|r| m.row_ones(r).collect()).collect();
+	let mut ops = Vec::new();
+	let mut temps = 0;
+	loop {
+	    // BTreeMap so that ties are broken the same way every time
+	    let mut counts : BTreeMap<(usize, usize), usize> = BTreeMap::new();
+	    for row in rows.iter() {
+		for (i, a) in row.iter().enumerate() {
+		    for b in row[i + 1..].iter() {
+			*counts.entry((*a, *b)).or_insert(0) += 1;
+		    }
+		}
+	    }
+	    let mut best = None;
+	    for (pair, count) in counts.iter() {
+		if *count >= 2 && best.map_or(true, |(_, c)| *count > c) {
+		    best = Some((*pair, *count));
+		}
+	    }
+	    let (a, b) = match best { Some((pair, _)) => pair, None => break };
+
+	    let t = inputs + temps;
+	    let packet = |s : usize| if s < inputs { Packet::Input(s) } else { Packet::Temp(s - inputs) };
+	    ops.push(XorOp::Copy(Packet::Temp(temps), packet(a)));
+	    ops.push(XorOp::Xor(Packet::Temp(temps), packet(b)));
+	    temps += 1;
+	    for row in rows.iter_mut() {
+		if row.contains(&a) && row.contains(&b) {
+		    row.retain(|s| *s != a && *s != b);
+		    // new signal has the highest id, so row stays sorted
+		    row.push(t);
+		}
+	    }
+	}
+	for (r, signals) in rows.iter().enumerate() {
+	    Schedule::emit_row(&mut ops, inputs, r, signals);
+	}
+	Schedule { inputs, outputs : m.rows, temps, ops }
+    }
+
+    /// The operations making up the schedule
+    pub fn ops(&self) -> &[XorOp] { &self.ops }
+
+    /// Number of temporary packets needed
+    pub fn temps(&self) -> usize { self.temps }
+
+    /// Number of XOR operations (the main cost of running it)
+    pub fn xor_count(&self) -> usize {
+	self.ops.iter().filter(|op| matches!(op, XorOp::Xor(_, _))).count()
+    }
+
+    /// Run the schedule over byte packets, which must all be the same
+    /// length
+    pub fn execute(&self, inputs : &[&[u8]], outputs : &mut [&mut [u8]]) {
+	assert_eq!(inputs.len(), self.inputs, "wrong number of input packets");
+	assert_eq!(outputs.len(), self.outputs, "wrong number of output packets");
+	let len = inputs.first().map_or(0, |p| p.len());
+	assert!(inputs.iter().all(|p| p.len() == len), "packet lengths differ");
+	assert!(outputs.iter().all(|p| p.len() == len), "packet lengths differ");
+	let mut temps = vec![vec![0u8; len]; self.temps];
+
+	for op in self.ops.iter() {
+	    let (dest, src, xor) = match *op {
+		XorOp::Zero(dest) => {
+		    clear_packet(dest, outputs, &mut temps);
+		    continue
+		},
+		XorOp::Copy(dest, src) => (dest, src, false),
+		XorOp::Xor(dest, src)  => (dest, src, true),
+	    };
+	    if dest == src {
+		// x ^= x clears; x = x does nothing
+		if xor { clear_packet(dest, outputs, &mut temps) }
+		continue
+	    }
+	    match (dest, src) {
+		(Packet::Input(_), _) => panic!("can't write to an input packet"),
+		(Packet::Output(d), Packet::Input(s)) =>
+		    apply_op(outputs[d], inputs[s], xor),
+		(Packet::Temp(d), Packet::Input(s)) =>
+		    apply_op(&mut temps[d], inputs[s], xor),
+		(Packet::Output(d), Packet::Temp(s)) =>
+		    apply_op(outputs[d], &temps[s], xor),
+		(Packet::Temp(d), Packet::Output(s)) =>
+		    apply_op(&mut temps[d], outputs[s], xor),
+		(Packet::Output(d), Packet::Output(s)) => {
+		    let (dp, sp) = pair_mut(outputs, d, s);
+		    apply_op(dp, sp, xor)
+		},
+		(Packet::Temp(d), Packet::Temp(s)) => {
+		    let (dp, sp) = pair_mut(&mut temps, d, s);
+		    apply_op(dp, sp, xor)
+		},
+	    }
+	}
+    }
+}
+
+fn clear_packet(p : Packet, outputs : &mut [&mut [u8]], temps : &mut [Vec<u8>]) {
+    match p {
+	Packet::Output(i) => outputs[i].fill(0),
+	Packet::Temp(i) => temps[i].fill(0),
+	Packet::Input(_) => panic!("can't write to an input packet"),
+    }
+}
+
+fn apply_op(d : &mut [u8], s : &[u8], xor : bool) {
+    if xor {
+	for (db, sb) in d.iter_mut().zip(s) { *db ^= *sb }
+    } else {
+	d.copy_from_slice(s);
+    }
+}
+
+// Borrow element d mutably and s (!= d) immutably from the same slice
+fn pair_mut<T, P>(v : &mut [T], d : usize, s : usize) -> (&mut P, &P)
+where T : AsMut<P> + AsRef<P>, P : ?Sized
+{
+    if d < s {
+	let (lo, hi) = v.split_at_mut(s);
+	(lo[d].as_mut(), hi[0].as_ref())
+    } else {
+	let (lo, hi) = v.split_at_mut(d);
+	(hi[0].as_mut(), lo[s].as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{new_gf4, new_gf16};
+    use crate::good::new_gf8_0x11b;
+
+    // bit-slice k devices of w-bit elements into k*w packets
+    fn slice(devices : &[Vec<u8>], w : usize) -> Vec<Vec<u8>> {
+	let n = devices[0].len();
+	let mut packets = vec![vec![0u8; (n + 7) / 8]; devices.len() * w];
+	for (j, dev) in devices.iter().enumerate() {
+	    for (t, e) in dev.iter().enumerate() {
+		for c in 0..w {
+		    if (e >> c) & 1 == 1 { packets[j * w + c][t / 8] |= 1 << (t % 8) }
+		}
+	    }
+	}
+	packets
+    }
+
+    #[test]
+    fn element_matrices_multiply() {
+	let f = new_gf8_0x11b();
+	for a in [1u8, 2, 3, 0x53, 0xca, 0xff].iter() {
+	    let ma = BitMatrix::from_element(&f, *a);
+	    for b in [0u8, 1, 7, 0x80, 0x9c].iter() {
+		assert_eq!(ma.mul_bits(*b as u64), f.mul(*a, *b) as u64);
+		let mb = BitMatrix::from_element(&f, *b);
+		assert_eq!(ma.mul(&mb), BitMatrix::from_element(&f, f.mul(*a, *b)));
+	    }
+	}
+	assert_eq!(BitMatrix::from_element(&f, 1), BitMatrix::identity(8));
+    }
+
+    #[test]
+    fn gf16_element_matrix() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let m = BitMatrix::from_element(&f, 0x1234);
+	assert_eq!(m.mul_bits(0xbeef), f.mul(0x1234, 0xbeef) as u64);
+    }
+
+    #[test]
+    fn cse_schedule_matches_naive() {
+	let f = new_gf8_0x11b();
+	let fm = Matrix::cauchy(&f, &[1, 2, 3], &[4, 5, 6, 7]).unwrap();
+	let bm = BitMatrix::from_matrix(&f, &fm);
+	assert_eq!((bm.rows(), bm.cols()), (24, 32));
+	let naive = Schedule::naive(&bm);
+	let cse = Schedule::with_cse(&bm);
+	assert_eq!(naive.xor_count(), bm.count_ones() - 24);
+	assert!(cse.xor_count() < naive.xor_count());
+	assert!(cse.temps() > 0);
+
+	let input : Vec<Vec<u8>> = (0..32).map(|i| (0..40).map(|j| (i * 13 + j * 3) as u8).collect()).collect();
+	let inputs : Vec<&[u8]> = input.iter().map(|v| &v[..]).collect();
+	let mut out_a = vec![vec![0u8; 40]; 24];
+	let mut out_b = vec![vec![0xffu8; 40]; 24];
+	naive.execute(&inputs, &mut out_a.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>());
+	cse.execute(&inputs, &mut out_b.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>());
+	assert_eq!(out_a, out_b);
+    }
+
+    #[test]
+    fn bit_sliced_encoding_matches_field_maths() {
+	let f = new_gf4(19, 3);
+	let w = 4;
+	let fm = Matrix::cauchy(&f, &[1, 2], &[3, 4, 5]).unwrap();
+	let data : Vec<Vec<u8>> = (0..3).map(|j| (0..32).map(|t| ((t * 5 + j * 3) % 16) as u8).collect()).collect();
+	// expected coding devices, by field arithmetic
+	let coding : Vec<Vec<u8>> = (0..2).map(|i| (0..32).map(|t| {
+	    (0..3).fold(0, |acc, j| acc ^ f.mul(fm[(i, j)], data[j][t]))
+	}).collect()).collect();
+
+	let sched = Schedule::with_cse(&BitMatrix::from_matrix(&f, &fm));
+	let input = slice(&data, w);
+	let inputs : Vec<&[u8]> = input.iter().map(|v| &v[..]).collect();
+	let mut output = vec![vec![0u8; 4]; 2 * w];
+	let mut outputs : Vec<&mut [u8]> = output.iter_mut().map(|v| &mut v[..]).collect();
+	sched.execute(&inputs, &mut outputs);
+	assert_eq!(output, slice(&coding, w));
+    }
+
+    #[test]
+    fn empty_rows_are_zeroed() {
+	let mut m = BitMatrix::new(2, 2);
+	m.set(1, 0, true);
+	m.set(1, 1, true);
+	let s = Schedule::with_cse(&m);
+	assert_eq!(s.ops()[0], XorOp::Zero(Packet::Output(0)));
+	let mut out = vec![vec![9u8; 2]; 2];
+	s.execute(&[&[1, 2], &[4, 8]],
+		  &mut out.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>());
+	assert_eq!(out, vec![vec![0, 0], vec![5, 10]]);
+    }
+
+    #[test]
+    fn execute_between_outputs_and_temps() {
+	use Packet::*;
+	let s = Schedule {
+	    inputs : 1, outputs : 2, temps : 2,
+	    ops : vec![XorOp::Copy(Output(0), Input(0)),
+		       XorOp::Copy(Temp(1), Output(0)),
+		       XorOp::Xor(Temp(1), Input(0)),
+		       XorOp::Xor(Temp(0), Output(0)),
+		       XorOp::Xor(Temp(1), Temp(0)),
+		       XorOp::Copy(Output(1), Temp(1)),
+		       XorOp::Xor(Output(1), Output(0)),
+		       XorOp::Copy(Output(0), Output(0)),
+		       XorOp::Xor(Temp(0), Temp(0)),
+		       XorOp::Xor(Output(0), Temp(0))],
+	};
+	let mut out = vec![vec![9u8; 2]; 2];
+	s.execute(&[&[3, 5]],
+		  &mut out.iter_mut().map(|v| &mut v[..]).collect::<Vec<_>>());
+	assert_eq!(out, vec![vec![3, 5], vec![0, 0]]);
+    }
+}
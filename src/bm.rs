@@ -0,0 +1,250 @@
+//! # Berlekamp–Massey LFSR synthesis
+//!
+//! Given a sequence s<sub>0</sub>, s<sub>1</sub>, ... of field
+//! elements, the Berlekamp–Massey algorithm finds the shortest linear
+//! feedback shift register that generates it. The result is described
+//! by its *linear complexity* L (the length of the register) and
+//! *connection polynomial*
+//!
+//! C(x) = 1 + c<sub>1</sub>x + ... + c<sub>L</sub>x<sup>L</sup>
+//!
+//! such that, for every n ≥ L:
+//!
+//! s<sub>n</sub> + c<sub>1</sub>s<sub>n-1</sub> + ... +
+//! c<sub>L</sub>s<sub>n-L</sub> = 0
+//!
+//! Note that c<sub>L</sub> may be zero, so the degree of C(x) can be
+//! less than L. The connection polynomial is in the format described
+//! in [crate::poly].
+//!
+//! Use [berlekamp_massey] to process a whole sequence at once, or a
+//! [BerlekampMassey] object to feed in one element at a time:
+//!
+//! ```rust
+//! use guff::{GaloisField, new_gf8};
+//! use guff::bm::{berlekamp_massey, BerlekampMassey};
+//!
+//! let f = new_gf8(0x11d, 0x1d);
+//!
+//! // s[n] = 3·s[n-1] + 7·s[n-2]
+//! let mut s = vec![1u8, 2];
+//! for n in 2..10 {
+//!     s.push(f.mul(3, s[n-1]) ^ f.mul(7, s[n-2]))
+//! }
+//! let (conn, l) = berlekamp_massey(&f, &s);
+//! assert_eq!(l, 2);
+//! assert_eq!(conn, vec![1, 3, 7]);
+//!
+//! let mut bm = BerlekampMassey::new();
+//! for e in s.iter() { bm.push(&f, *e); }
+//! assert_eq!(bm.connection(), &conn[..]);
+//! ```
+
+use crate::GaloisField;
+use crate::poly::{self, Poly};
+
+use num::{One,Zero};
+
+/// Incremental Berlekamp–Massey state
+///
+/// Feed sequence elements in with [push](BerlekampMassey::push); the
+/// current shortest LFSR is always available.
+pub struct BerlekampMassey<G> where G : GaloisField {
+    // current connection polynomial C(x), and the one from before the
+    // last length change, B(x)
+    conn : Poly<G>,
+    prev : Poly<G>,
+    // linear complexity
+    l    : usize,
+    // steps since last length change
+    m    : usize,
+    // discrepancy at last length change
+    b    : G::E,
+    seq  : Vec<G::E>,
+}
+
+impl<G> Default for BerlekampMassey<G> where G : GaloisField {
+    fn default() -> Self { Self::new() }
+}
+
+impl<G> BerlekampMassey<G> where G : GaloisField {
+
+    /// Start with an empty sequence
+    pub fn new() -> BerlekampMassey<G> {
+	BerlekampMassey {
+	    conn : vec![G::E::one()],
+	    prev : vec![G::E::one()],
+	    l : 0, m : 1,
+	    b : G::E::one(),
+	    seq : Vec::new(),
+	}
+    }
+
+    // s[n] + c[1]s[n-1] + ... + c[L]s[n-L] for the next element s[n]
+    fn feedback(&self, f : &G) -> G::E {
+	let n = self.seq.len();
+	let mut sum = G::E::zero();
+	for (i, c) in self.conn.iter().enumerate().skip(1) {
+	    if i > n { break }
+	    sum = sum ^ f.mul(*c, self.seq[n - i]);
+	}
+	sum
+    }
+
+    /// The element the current LFSR would generate next
+    pub fn predict(&self, f : &G) -> G::E {
+	self.feedback(f)
+    }
+
+    /// Add the next element of the sequence, updating the LFSR
+    ///
+    /// Returns the discrepancy, ie the difference between the new
+    /// element and what the previous LFSR predicted (zero means that
+    /// the LFSR didn't need to change).
+    pub fn push(&mut self, f : &G, s : G::E) -> G::E {
+	let zero = G::E::zero();
+	let d = s ^ self.feedback(f);
+	let n = self.seq.len();
+	self.seq.push(s);
+	if d == zero {
+	    self.m += 1;
+	    return d
+	}
+	// C(x) -= (d/b)·x^m·B(x)
+	let coef = f.div(d, self.b);
+	let mut shifted = vec![zero; self.m];
+	shifted.extend(poly::scale(f, &self.prev, coef));
+	let new_conn = poly::add(f, &self.conn, &shifted);
+	if 2 * self.l <= n {
+	    self.l = n + 1 - self.l;
+	    self.prev = std::mem::replace(&mut self.conn, new_conn);
+	    self.b = d;
+	    self.m = 1;
+	} else {
+	    self.conn = new_conn;
+	    self.m += 1;
+	}
+	d
+    }
+
+    /// Current connection polynomial C(x)
+    pub fn connection(&self) -> &[G::E] {
+	&self.conn
+    }
+
+    /// Current linear complexity L
+    pub fn linear_complexity(&self) -> usize {
+	self.l
+    }
+
+    /// Number of sequence elements processed so far
+    pub fn len(&self) -> usize {
+	self.seq.len()
+    }
+
+    /// Test whether no elements have been processed yet
+    pub fn is_empty(&self) -> bool {
+	self.seq.is_empty()
+    }
+}
+
+/// Find the shortest LFSR generating `seq`
+///
+/// Returns the connection polynomial and linear complexity.
+pub fn berlekamp_massey<G>(f : &G, seq : &[G::E]) -> (Poly<G>, usize)
+where G : GaloisField
+{
+    let mut bm = BerlekampMassey::new();
+    for s in seq { bm.push(f, *s); }
+    (bm.conn, bm.l)
+}
+
+/// Linear complexity of each prefix of `seq`
+///
+/// Element i of the result is the linear complexity of
+/// `seq[..=i]`. For a random sequence this should track i/2 closely.
+pub fn linear_complexity_profile<G>(f : &G, seq : &[G::E]) -> Vec<usize>
+where G : GaloisField
+{
+    let mut bm = BerlekampMassey::new();
+    seq.iter().map(|s| { bm.push(f, *s); bm.l }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{new_gf4, new_gf16};
+    use crate::good::new_gf8_0x11b;
+
+    // run an LFSR with given connection poly and initial state
+    fn generate<G>(f : &G, conn : &[G::E], init : &[G::E], n : usize) -> Vec<G::E>
+    where G : GaloisField
+    {
+	let mut s = init.to_vec();
+	while s.len() < n {
+	    let k = s.len();
+	    let mut next = G::E::zero();
+	    for i in 1..conn.len() { next = next ^ f.mul(conn[i], s[k - i]) }
+	    s.push(next);
+	}
+	s
+    }
+
+    #[test]
+    fn recovers_known_lfsr() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let conn = vec![1u16, 0x1234, 0, 0xbeef, 7];
+	let s = generate(&f, &conn, &[1, 2, 3, 4], 20);
+	assert_eq!(berlekamp_massey(&f, &s), (conn, 4));
+    }
+
+    #[test]
+    fn trivial_sequences() {
+	let f = new_gf4(19, 3);
+	assert_eq!(berlekamp_massey(&f, &[0, 0, 0]), (vec![1], 0));
+	// impulse at position 2 needs a register of length 3
+	assert_eq!(berlekamp_massey(&f, &[0, 0, 5]).1, 3);
+	// constant sequence
+	assert_eq!(berlekamp_massey(&f, &[9, 9, 9, 9]), (vec![1, 1], 1));
+    }
+
+    #[test]
+    fn incremental_matches_batch() {
+	let f = new_gf8_0x11b();
+	let s : Vec<u8> = (0..30u32).map(|i| (i * i * 7 + 3) as u8).collect();
+	let mut bm = BerlekampMassey::new();
+	for (i, e) in s.iter().enumerate() {
+	    bm.push(&f, *e);
+	    assert_eq!((bm.connection().to_vec(), bm.linear_complexity()),
+		       berlekamp_massey(&f, &s[..=i]));
+	}
+	assert_eq!(bm.len(), 30);
+    }
+
+    #[test]
+    fn prediction_and_discrepancy() {
+	let f = new_gf8_0x11b();
+	let conn = vec![1u8, 0x53, 0xca];
+	let s = generate(&f, &conn, &[5, 6], 12);
+	let mut bm = BerlekampMassey::new();
+	for e in s[..4].iter() { bm.push(&f, *e); }
+	for e in s[4..].iter() {
+	    assert_eq!(bm.predict(&f), *e);
+	    assert_eq!(bm.push(&f, *e), 0);
+	}
+	// corrupt the next element: discrepancy is the error
+	let next = bm.predict(&f);
+	assert_eq!(bm.push(&f, next ^ 0x10), 0x10);
+    }
+
+    #[test]
+    fn profile_of_binary_sequence() {
+	// GF(2) embedded in GF(16): x^4 + x + 1 m-sequence
+	let f = new_gf4(19, 3);
+	let s = generate(&f, &[1, 1, 0, 0, 1], &[1, 0, 0, 0], 30);
+	let profile = linear_complexity_profile(&f, &s);
+	assert_eq!(*profile.last().unwrap(), 4);
+	assert!(profile.windows(2).all(|w| w[0] <= w[1]));
+    }
+}
@@ -0,0 +1,190 @@
+//! # Byte buffers and endianness
+//!
+//! Data usually comes as bytes, but the wider fields work on `u16`
+//! or `u32` elements. Reinterpreting a byte buffer in place would
+//! silently depend on the machine's byte order (and alignment), so
+//! that a file encoded on one machine couldn't be decoded on
+//! another. Instead, this module converts explicitly, with the byte
+//! order given by an [Endian] value.
+//!
+//! [matrix_apply_bytes] wraps [GaloisField::matrix_apply] for byte
+//! buffers, converting a block at a time:
+//!
+//! ```rust
+//! use guff::{GaloisField, new_gf16};
+//! use guff::bytes::{Endian, matrix_apply_bytes, elements_from_bytes};
+//!
+//! let f = new_gf16(0x1002b, 0x2b);
+//! let a = [0x12u8, 0x34, 0x56, 0x78];
+//! let b = [0xffu8, 0xee, 0xdd, 0xcc];
+//! let mut out = [0u8; 4];
+//!
+//! // out = 2·a + 3·b, taking elements as big-endian u16s
+//! matrix_apply_bytes(&f, &[2, 3], &[&a, &b], &mut [&mut out], Endian::Big);
+//!
+//! let x : Vec<u16> = elements_from_bytes(&out, Endian::Big);
+//! assert_eq!(x[0], f.mul(2, 0x1234) ^ f.mul(3, 0xffee));
+//! ```
+//!
+//! For GF(2<sup>8</sup>) no conversion is needed and `matrix_apply`
+//! can be called on the bytes directly. GF(2<sup>4</sup>) elements
+//! are stored one per byte, so aren't supported here.
+
+use crate::{GaloisField, ElementStore};
+
+use num::Zero;
+
+/// Byte order of multi-byte field elements in a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    /// Least significant byte first
+    Little,
+    /// Most significant byte first
+    Big,
+}
+
+/// Element types that can be read from and written to bytes
+pub trait ElementBytes : ElementStore {
+    /// Size of the element in bytes
+    const BYTES : usize;
+    /// Read one element from the first `BYTES` bytes
+    fn read(bytes : &[u8], endian : Endian) -> Self;
+    /// Write one element to the first `BYTES` bytes
+    fn write(self, bytes : &mut [u8], endian : Endian);
+}
+
+impl ElementBytes for u8 {
+    const BYTES : usize = 1;
+    fn read(bytes : &[u8], _endian : Endian) -> u8 { bytes[0] }
+    fn write(self, bytes : &mut [u8], _endian : Endian) { bytes[0] = self }
+}
+
+impl ElementBytes for u16 {
+    const BYTES : usize = 2;
+    fn read(bytes : &[u8], endian : Endian) -> u16 {
+	let b = [bytes[0], bytes[1]];
+	match endian {
+	    Endian::Little => u16::from_le_bytes(b),
+	    Endian::Big    => u16::from_be_bytes(b),
+	}
+    }
+    fn write(self, bytes : &mut [u8], endian : Endian) {
+	bytes[..2].copy_from_slice(&match endian {
+	    Endian::Little => self.to_le_bytes(),
+	    Endian::Big    => self.to_be_bytes(),
+	})
+    }
+}
+
+impl ElementBytes for u32 {
+    const BYTES : usize = 4;
+    fn read(bytes : &[u8], endian : Endian) -> u32 {
+	let b = [bytes[0], bytes[1], bytes[2], bytes[3]];
+	match endian {
+	    Endian::Little => u32::from_le_bytes(b),
+	    Endian::Big    => u32::from_be_bytes(b),
+	}
+    }
+    fn write(self, bytes : &mut [u8], endian : Endian) {
+	bytes[..4].copy_from_slice(&match endian {
+	    Endian::Little => self.to_le_bytes(),
+	    Endian::Big    => self.to_be_bytes(),
+	})
+    }
+}
+
+/// Read a buffer as a vector of elements
+///
+/// Panics if the buffer length isn't a multiple of the element size.
+pub fn elements_from_bytes<E>(bytes : &[u8], endian : Endian) -> Vec<E>
+where E : ElementBytes
+{
+    assert!(bytes.len() % E::BYTES == 0, "buffer is not a whole number of elements");
+    bytes.chunks_exact(E::BYTES).map(|c| E::read(c, endian)).collect()
+}
+
+/// Write elements into a buffer of exactly the right size
+pub fn elements_to_bytes<E>(elements : &[E], bytes : &mut [u8], endian : Endian)
+where E : ElementBytes
+{
+    assert_eq!(bytes.len(), elements.len() * E::BYTES, "buffer size doesn't match");
+    for (e, c) in elements.iter().zip(bytes.chunks_exact_mut(E::BYTES)) {
+	e.write(c, endian)
+    }
+}
+
+// bytes per block converted in matrix_apply_bytes
+const BYTES_BLOCK : usize = 16384;
+
+/// [GaloisField::matrix_apply] on byte buffers
+///
+/// Each buffer is treated as a sequence of field elements in the
+/// given byte order. All buffers must be the same length, a multiple
+/// of the element size.
+pub fn matrix_apply_bytes<G>(f : &G,
+			     coeffs  : &[G::E],
+			     inputs  : &[&[u8]],
+			     outputs : &mut [&mut [u8]],
+			     endian  : Endian)
+where G : GaloisField, G::E : ElementBytes
+{
+    assert_eq!(G::ORDER as usize, 8 * G::E::BYTES, "field elements don't fill their storage");
+    let len = outputs.first().map_or(0, |o| o.len());
+    assert!(len % G::E::BYTES == 0, "buffer is not a whole number of elements");
+    assert!(inputs.iter().all(|s| s.len() == len), "buffer lengths differ");
+    assert!(outputs.iter().all(|s| s.len() == len), "buffer lengths differ");
+
+    let block = BYTES_BLOCK - BYTES_BLOCK % G::E::BYTES;
+    let mut in_elems  = vec![Vec::new(); inputs.len()];
+    let mut out_elems = vec![vec![G::E::zero(); block / G::E::BYTES]; outputs.len()];
+    let mut start = 0;
+    while start < len {
+	let end = len.min(start + block);
+	let n = (end - start) / G::E::BYTES;
+	for (v, src) in in_elems.iter_mut().zip(inputs) {
+	    *v = elements_from_bytes(&src[start..end], endian);
+	}
+	let in_refs : Vec<&[G::E]> = in_elems.iter().map(|v| &v[..]).collect();
+	let mut out_refs : Vec<&mut [G::E]> = out_elems.iter_mut().map(|v| &mut v[..n]).collect();
+	f.matrix_apply(coeffs, &in_refs, &mut out_refs);
+	for (v, dest) in out_elems.iter().zip(outputs.iter_mut()) {
+	    elements_to_bytes(&v[..n], &mut dest[start..end], endian);
+	}
+	start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::new_gf32;
+
+    #[test]
+    fn endianness_is_explicit() {
+	let bytes = [0x01u8, 0x02, 0x03, 0x04];
+	assert_eq!(elements_from_bytes::<u16>(&bytes, Endian::Little), vec![0x0201, 0x0403]);
+	assert_eq!(elements_from_bytes::<u16>(&bytes, Endian::Big), vec![0x0102, 0x0304]);
+	assert_eq!(elements_from_bytes::<u32>(&bytes, Endian::Big), vec![0x01020304]);
+	let mut out = [0u8; 4];
+	elements_to_bytes(&[0x01020304u32], &mut out, Endian::Little);
+	assert_eq!(out, [4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn byte_buffers_through_wide_fields() {
+	let f = new_gf32(0x1_0000_008d, 0x8d);
+	let a : Vec<u8> = (0..40_000u32).map(|i| (i * 13) as u8).collect();
+	let b : Vec<u8> = (0..40_000u32).map(|i| (i * 101 + 5) as u8).collect();
+	for endian in [Endian::Little, Endian::Big].iter() {
+	    let mut out = vec![0u8; a.len()];
+	    matrix_apply_bytes(&f, &[0xdead_beef, 2], &[&a, &b], &mut [&mut out], *endian);
+	    let ea : Vec<u32> = elements_from_bytes(&a, *endian);
+	    let eb : Vec<u32> = elements_from_bytes(&b, *endian);
+	    let eo : Vec<u32> = elements_from_bytes(&out, *endian);
+	    for t in [0usize, 1, 4095, 4096, 9999].iter() {
+		assert_eq!(eo[*t], f.mul(0xdead_beef, ea[*t]) ^ f.mul(2, eb[*t]));
+	    }
+	}
+    }
+}
@@ -0,0 +1,253 @@
+//! # Extended Euclidean algorithm and the key equation
+//!
+//! Running the extended Euclidean algorithm on polynomials a(x) and
+//! b(x) produces a sequence of remainders r<sub>i</sub>, each with
+//! cofactors s<sub>i</sub>, t<sub>i</sub> such that
+//!
+//! s<sub>i</sub>·a + t<sub>i</sub>·b = r<sub>i</sub>
+//!
+//! where the degrees of the r<sub>i</sub> fall while those of the
+//! t<sub>i</sub> rise. Stopping part-way, once the remainder drops
+//! below some degree bound ([partial_xgcd]), gives the basis of both
+//! rational function reconstruction and Sugiyama's method for solving
+//! the *key equation* of Reed-Solomon and BCH decoding:
+//!
+//! S(x)·Λ(x) ≡ Ω(x) mod x<sup>2t</sup>
+//!
+//! where S(x) is the syndrome polynomial, Λ(x) the error locator and
+//! Ω(x) the error evaluator. This is an alternative to
+//! [crate::bm::berlekamp_massey], and the locator it finds is
+//! normalised the same way (Λ(0) = 1):
+//!
+//! ```rust
+//! use guff::{GaloisField, new_gf8};
+//! use guff::euclid::solve_key_equation;
+//! use guff::bm::berlekamp_massey;
+//!
+//! let f = new_gf8(0x11d, 0x1d);
+//!
+//! // syndromes of two errors, values 5 and 9 at locations 2 and 0x40
+//! let s : Vec<u8> = (0..6u16)
+//!     .map(|j| f.mul(5, f.pow(2, j)) ^ f.mul(9, f.pow(0x40, j)))
+//!     .collect();
+//!
+//! let (locator, _evaluator) = solve_key_equation(&f, &s, 3).unwrap();
+//! assert_eq!(locator, berlekamp_massey(&f, &s).0);
+//! assert_eq!(locator, vec![1, 0x42, f.mul(2, 0x40)]);
+//! ```
+//!
+//! As elsewhere, polynomials are in the format described in
+//! [crate::poly].
+
+use crate::GaloisField;
+use crate::poly::{self, Poly};
+
+use num::{One,Zero};
+
+/// Extended Euclidean algorithm stopped at a degree bound
+///
+/// Works through the remainder sequence a, b, r<sub>1</sub>,
+/// r<sub>2</sub>, ... and returns the first remainder of degree less
+/// than `stop`, along with its cofactors, as (r, s, t) where
+/// s·a + t·b = r. The zero polynomial counts as being below any
+/// bound, so this always terminates.
+pub fn partial_xgcd<G>(f : &G, a : &[G::E], b : &[G::E], stop : usize)
+		       -> (Poly<G>, Poly<G>, Poly<G>)
+where G : GaloisField
+{
+    let below = |p : &[G::E]| poly::degree(p).map_or(true, |d| d < stop);
+
+    let (mut r0, mut r1) = (a.to_vec(), b.to_vec());
+    poly::normalise(&mut r0);
+    poly::normalise(&mut r1);
+    let (mut s0, mut s1) : (Poly<G>, Poly<G>) = (vec![G::E::one()], Vec::new());
+    let (mut t0, mut t1) : (Poly<G>, Poly<G>) = (Vec::new(), vec![G::E::one()]);
+    if below(&r0) { return (r0, s0, t0) }
+
+    while !below(&r1) {
+	let (q, r) = poly::divrem(f, &r0, &r1);
+	let s = poly::add(f, &s0, &poly::mul(f, &q, &s1));
+	let t = poly::add(f, &t0, &poly::mul(f, &q, &t1));
+	r0 = std::mem::replace(&mut r1, r);
+	s0 = std::mem::replace(&mut s1, s);
+	t0 = std::mem::replace(&mut t1, t);
+    }
+    (r1, s1, t1)
+}
+
+/// Full extended Euclidean algorithm
+///
+/// Returns (g, s, t) where g is the monic gcd of a and b, and
+/// s·a + t·b = g.
+pub fn xgcd<G>(f : &G, a : &[G::E], b : &[G::E]) -> (Poly<G>, Poly<G>, Poly<G>)
+where G : GaloisField
+{
+    let (mut r0, mut r1) = (a.to_vec(), b.to_vec());
+    poly::normalise(&mut r0);
+    poly::normalise(&mut r1);
+    let (mut s0, mut s1) : (Poly<G>, Poly<G>) = (vec![G::E::one()], Vec::new());
+    let (mut t0, mut t1) : (Poly<G>, Poly<G>) = (Vec::new(), vec![G::E::one()]);
+    while !r1.is_empty() {
+	let (q, r) = poly::divrem(f, &r0, &r1);
+	let s = poly::add(f, &s0, &poly::mul(f, &q, &s1));
+	let t = poly::add(f, &t0, &poly::mul(f, &q, &t1));
+	r0 = std::mem::replace(&mut r1, r);
+	s0 = std::mem::replace(&mut s1, s);
+	t0 = std::mem::replace(&mut t1, t);
+    }
+    let l = poly::lead(&r0);
+    if l == G::E::zero() { return (r0, s0, t0) }
+    let inv = f.inv(l);
+    (poly::scale(f, &r0, inv), poly::scale(f, &s0, inv), poly::scale(f, &t0, inv))
+}
+
+/// Inverse of a modulo m, if it exists
+pub fn invmod<G>(f : &G, a : &[G::E], m : &[G::E]) -> Option<Poly<G>>
+where G : GaloisField
+{
+    let (g, _, t) = xgcd(f, m, &poly::rem(f, a, m));
+    if g.len() == 1 { Some(poly::rem(f, &t, m)) } else { None }
+}
+
+/// Rational function reconstruction
+///
+/// Given u of degree less than that of the modulus m, find n and d
+/// with d·u ≡ n (mod m), deg n < `k` and deg d ≤ deg m - `k`. The
+/// denominator is made monic. Returns `None` if there is no such
+/// fraction with d invertible modulo m.
+pub fn rational_reconstruction<G>(f : &G, u : &[G::E], m : &[G::E], k : usize)
+				  -> Option<(Poly<G>, Poly<G>)>
+where G : GaloisField
+{
+    let dm = poly::degree(m).expect("modulus must be non-zero");
+    assert!(k <= dm, "numerator degree bound exceeds that of modulus");
+    let (n, _, d) = partial_xgcd(f, m, &poly::rem(f, u, m), k);
+    match poly::degree(&d) {
+	Some(dd) if dd <= dm - k => (),
+	_ => return None
+    }
+    if poly::gcd(f, &d, m).len() != 1 { return None }
+    let inv = f.inv(poly::lead(&d));
+    Some((poly::scale(f, &n, inv), poly::scale(f, &d, inv)))
+}
+
+/// Solve the key equation by Sugiyama's method
+///
+/// Takes syndromes S<sub>0</sub>, S<sub>1</sub>, ... (as the
+/// coefficients of S(x)) and runs the extended Euclidean algorithm on
+/// x<sup>2t</sup> and S(x), where 2t is the number of syndromes,
+/// stopping when the remainder has degree less than `stop`. For
+/// errors-only decoding `stop` is t; with e known erasures folded
+/// into the syndromes it becomes t + ⌈e/2⌉.
+///
+/// Returns (Λ, Ω) scaled so that Λ(0) = 1, or `None` if that isn't
+/// possible, which means that there were too many errors.
+pub fn solve_key_equation<G>(f : &G, syndromes : &[G::E], stop : usize)
+			     -> Option<(Poly<G>, Poly<G>)>
+where G : GaloisField
+{
+    let x2t = poly::monomial(f, syndromes.len());
+    let (omega, _, lambda) = partial_xgcd(f, &x2t, syndromes, stop);
+    let l0 = match lambda.first() {
+	Some(c) if *c != G::E::zero() => *c,
+	_ => return None
+    };
+    let inv = f.inv(l0);
+    Some((poly::scale(f, &lambda, inv), poly::scale(f, &omega, inv)))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{new_gf8, new_gf16};
+    use crate::good::new_gf8_0x11b;
+    use crate::bm::berlekamp_massey;
+
+    // syndromes S_j = Σ Y_k X_k^j for j in 0..n
+    fn syndromes<G>(f : &G, errors : &[(G::E, G::E)], n : usize) -> Vec<G::E>
+    where G : GaloisField
+    {
+	let mut s = vec![G::E::zero(); n];
+	for (x, y) in errors.iter() {
+	    let mut term = *y;
+	    for sj in s.iter_mut() {
+		*sj = *sj ^ term;
+		term = f.mul(term, *x);
+	    }
+	}
+	s
+    }
+
+    #[test]
+    fn partial_xgcd_invariant() {
+	let f = new_gf8_0x11b();
+	let a = vec![1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+	let b = vec![0x53u8, 0xca, 1, 0, 0x11, 0x22];
+	for stop in 0..10 {
+	    let (r, s, t) = partial_xgcd(&f, &a, &b, stop);
+	    assert!(poly::degree(&r).map_or(true, |d| d < stop));
+	    let lhs = poly::add(&f, &poly::mul(&f, &s, &a), &poly::mul(&f, &t, &b));
+	    assert_eq!(lhs, r);
+	}
+    }
+
+    #[test]
+    fn xgcd_and_invmod() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let common = poly::from_roots(&f, &[7, 0x1234]);
+	let a = poly::mul(&f, &common, &[1, 2, 3]);
+	let b = poly::mul(&f, &common, &[9, 0, 0, 1]);
+	let (g, s, t) = xgcd(&f, &a, &b);
+	assert_eq!(g, common);
+	assert_eq!(poly::add(&f, &poly::mul(&f, &s, &a), &poly::mul(&f, &t, &b)), g);
+
+	let m = vec![0x2bu16, 0, 0, 1, 0, 1];
+	let a = vec![5u16, 0xffff, 3];
+	let inv = invmod(&f, &a, &m).unwrap();
+	assert_eq!(poly::mulmod(&f, &a, &inv, &m), vec![1]);
+	assert_eq!(invmod(&f, &[0u16, 1], &[0u16, 0, 1]), None);
+    }
+
+    #[test]
+    fn reconstructs_fraction() {
+	let f = new_gf8(0x11d, 0x1d);
+	let m = poly::monomial(&f, 10);
+	let n = vec![3u8, 0, 0x80, 9];
+	let d = vec![1u8, 0x42, 0, 7, 1];
+	let u = poly::mulmod(&f, &n, &invmod(&f, &d, &m).unwrap(), &m);
+	// monic d
+	let d_monic = poly::monic(&f, &d);
+	let n_scaled = poly::scale(&f, &n, f.inv(poly::lead(&d)));
+	assert_eq!(rational_reconstruction(&f, &u, &m, 5),
+		   Some((n_scaled, d_monic)));
+	// denominator sharing a factor with m can't be found
+	assert_eq!(rational_reconstruction(&f, &[0u8, 0, 1], &[0u8, 0, 0, 0, 1], 1),
+		   None);
+    }
+
+    #[test]
+    fn key_equation_matches_bm() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let errors = [(0x8000u16, 1u16), (3, 0x4444), (0x1111, 0xbeef)];
+	let s = syndromes(&f, &errors, 8);
+	let (lambda, omega) = solve_key_equation(&f, &s, 4).unwrap();
+	assert_eq!(lambda, berlekamp_massey(&f, &s).0);
+	assert_eq!(lambda.len(), 4);
+	for (x, _) in errors.iter() {
+	    assert_eq!(poly::eval(&f, &lambda, f.inv(*x)), 0);
+	}
+	// S·Λ ≡ Ω mod x^2t
+	let mut prod = poly::mul(&f, &s, &lambda);
+	prod.truncate(8);
+	poly::normalise(&mut prod);
+	assert_eq!(prod, omega);
+	assert!(omega.len() <= 3);
+    }
+
+    #[test]
+    fn key_equation_with_no_errors() {
+	let f = new_gf8_0x11b();
+	assert_eq!(solve_key_equation(&f, &[0u8; 6], 3), Some((vec![1], vec![])));
+    }
+}
@@ -0,0 +1,358 @@
+//! # Factoring polynomials over GF(2<sup>x</sup>)
+//!
+//! Complete factorisation of a polynomial into monic irreducible
+//! factors is done in the usual three stages:
+//!
+//! * [square_free]: split into square-free parts, each tagged with
+//!   its multiplicity
+//!
+//! * [distinct_degree]: split a square-free polynomial into products
+//!   of irreducibles that all have the same degree
+//!
+//! * [equal_degree]: split such a product into its irreducible
+//!   factors (Cantor–Zassenhaus, using the trace map since we're in
+//!   characteristic 2)
+//!
+//! [factor] does all three:
+//!
+//! ```rust
+//! use guff::new_gf8;
+//! use guff::poly;
+//! use guff::factor::{factor, is_irreducible};
+//!
+//! let f = new_gf8(0x11b, 0x1b);
+//!
+//! // x² + x + c is irreducible for suitable c
+//! let quad = vec![0x20, 1, 1];
+//! assert!(is_irreducible(&f, &quad));
+//!
+//! let p = poly::mul(&f, &poly::mul(&f, &quad, &quad), &[5, 1]);
+//! let (lead, factors) = factor(&f, &p);
+//! assert_eq!(lead, 1);
+//! assert_eq!(factors, vec![(vec![5, 1], 1), (quad, 2)]);
+//! ```
+//!
+//! The final stage is a randomised algorithm. A small internal
+//! pseudo-random generator with a fixed seed is used, so results (and
+//! running times) are repeatable.
+
+use crate::GaloisField;
+use crate::poly::{self, Poly};
+
+use num::{One,Zero,FromPrimitive,ToPrimitive};
+
+// xorshift64: good enough for picking random polynomials, and saves
+// pulling in a dependency.
+struct XorShift { state : u64 }
+impl XorShift {
+    fn new() -> XorShift { XorShift { state : 0x2545_f491_4f6c_dd1d } }
+    fn next(&mut self) -> u64 {
+	let mut x = self.state;
+	x ^= x << 13;
+	x ^= x >> 7;
+	x ^= x << 17;
+	self.state = x;
+	x
+    }
+}
+
+fn is_one<G>(_f : &G, p : &[G::E]) -> bool
+where G : GaloisField
+{
+    poly::degree(p) == Some(0) && p[0] == G::E::one()
+}
+
+// Square root of a polynomial with only even powers of x. In
+// GF(2**n), sqrt(a) = a**(2**(n-1)).
+fn poly_sqrt<G>(f : &G, p : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    let e = G::EE::one() << (G::ORDER - 1) as usize;
+    let mut r : Poly<G> = p.iter().step_by(2).map(|c| f.pow(*c, e)).collect();
+    poly::normalise(&mut r);
+    r
+}
+
+/// Square-free decomposition
+///
+/// Returns square-free, pairwise coprime, monic polynomials
+/// a<sub>i</sub> with multiplicities m<sub>i</sub>, such that the
+/// monic version of p is the product of all
+/// a<sub>i</sub><sup>m<sub>i</sub></sup>. Ordered by multiplicity.
+pub fn square_free<G>(f : &G, p : &[G::E]) -> Vec<(Poly<G>, usize)>
+where G : GaloisField
+{
+    let mut out = Vec::new();
+    let p = poly::monic(f, p);
+    if poly::degree(&p).unwrap_or(0) == 0 { return out }
+    square_free_inner(f, &p, 1, &mut out);
+    out.sort_by_key(|(_, m)| *m);
+    out
+}
+
+fn square_free_inner<G>(f : &G, p : &[G::E], mult : usize,
+			out : &mut Vec<(Poly<G>, usize)>)
+where G : GaloisField
+{
+    let d = poly::derivative(f, p);
+    if d.is_empty() {
+	// p is a perfect square
+	let s = poly_sqrt(f, p);
+	square_free_inner(f, &s, mult * 2, out);
+	return
+    }
+    let mut c = poly::gcd(f, p, &d);
+    let mut w = poly::divrem(f, p, &c).0;
+    let mut i = 1;
+    while !is_one(f, &w) {
+	let y = poly::gcd(f, &w, &c);
+	let fac = poly::divrem(f, &w, &y).0;
+	if !is_one(f, &fac) { out.push((fac, i * mult)) }
+	w = y;
+	c = poly::divrem(f, &c, &w).0;
+	i += 1;
+    }
+    if !is_one(f, &c) {
+	// what remains only has factors with multiplicity divisible
+	// by 2
+	let s = poly_sqrt(f, &c);
+	square_free_inner(f, &s, mult * 2, out);
+    }
+}
+
+// x**q mod m, where q = 2**ORDER is the field size
+fn frobenius<G>(f : &G, a : &[G::E], m : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    let mut a = poly::rem(f, a, m);
+    for _ in 0..G::ORDER {
+	a = poly::mulmod(f, &a, &a, m);
+    }
+    a
+}
+
+/// Distinct-degree factorisation of a square-free polynomial
+///
+/// Returns pairs (g<sub>d</sub>, d), where each g<sub>d</sub> is the
+/// (monic) product of all the irreducible factors of p that have
+/// degree d. Ordered by d.
+pub fn distinct_degree<G>(f : &G, p : &[G::E]) -> Vec<(Poly<G>, usize)>
+where G : GaloisField
+{
+    let zero = G::E::zero();
+    let x = vec![zero, G::E::one()];
+    let mut out = Vec::new();
+    let mut rest = poly::monic(f, p);
+    // x**(q**i) mod rest
+    let mut xqi = x.clone();
+    let mut i = 1;
+    while rest.len() > 2 * i {
+	xqi = frobenius(f, &xqi, &rest);
+	let g = poly::gcd(f, &rest, &poly::add(f, &xqi, &x));
+	if !is_one(f, &g) {
+	    rest = poly::divrem(f, &rest, &g).0;
+	    xqi = poly::rem(f, &xqi, &rest);
+	    out.push((g, i));
+	}
+	i += 1;
+    }
+    if rest.len() > 1 {
+	let d = rest.len() - 1;
+	out.push((rest, d));
+    }
+    out
+}
+
+/// Equal-degree factorisation (Cantor–Zassenhaus)
+///
+/// p must be a square-free product of irreducible polynomials that
+/// all have degree d, as returned by [distinct_degree]. The factors
+/// are returned monic, sorted by their coefficients. A zero or
+/// constant p has no factors, so gives an empty result.
+///
+/// In characteristic 2, the usual a<sup>(q<sup>d</sup>-1)/2</sup>
+/// splitting polynomial is replaced with the trace
+/// a + a<sup>2</sup> + a<sup>4</sup> + ... +
+/// a<sup>2<sup>nd-1</sup></sup>, which takes values in GF(2) modulo
+/// each irreducible factor.
+pub fn equal_degree<G>(f : &G, p : &[G::E], d : usize) -> Vec<Poly<G>>
+where G : GaloisField
+{
+    let mut out = Vec::new();
+    let mut rng = XorShift::new();
+    let p = poly::monic(f, p);
+    if p.len() <= 1 { return out }
+    assert!(d > 0 && (p.len() - 1) % d == 0,
+	    "degree of p must be a multiple of d");
+    equal_degree_inner(f, p, d, &mut rng, &mut out);
+    out.sort();
+    out
+}
+
+fn equal_degree_inner<G>(f : &G, p : Poly<G>, d : usize,
+			 rng : &mut XorShift, out : &mut Vec<Poly<G>>)
+where G : GaloisField
+{
+    let n = p.len() - 1;
+    if n <= d {
+	out.push(p);
+	return
+    }
+    let mask = G::FIELD_MASK.to_u64().unwrap();
+    let steps = G::ORDER as usize * d;
+    loop {
+	let a : Poly<G> = (0..n)
+	    .map(|_| G::E::from_u64(rng.next() & mask).unwrap())
+	    .collect();
+	let mut y = poly::rem(f, &a, &p);
+	let mut t = y.clone();
+	for _ in 1..steps {
+	    y = poly::mulmod(f, &y, &y, &p);
+	    t = poly::add(f, &t, &y);
+	}
+	let g = poly::gcd(f, &p, &t);
+	let dg = g.len().saturating_sub(1);
+	if dg > 0 && dg < n {
+	    let other = poly::divrem(f, &p, &g).0;
+	    equal_degree_inner(f, g, d, rng, out);
+	    equal_degree_inner(f, other, d, rng, out);
+	    return
+	}
+    }
+}
+
+/// Test whether a polynomial is irreducible over the field
+///
+/// Useful for finding field polynomials for extension fields
+/// GF((2<sup>x</sup>)<sup>d</sup>) built on top of an existing field.
+pub fn is_irreducible<G>(f : &G, p : &[G::E]) -> bool
+where G : GaloisField
+{
+    let n = match poly::degree(p) {
+	None | Some(0) => return false,
+	Some(n) => n
+    };
+    if poly::derivative(f, p).is_empty() { return false }
+    let sf = square_free(f, p);
+    if sf.len() != 1 || sf[0].1 != 1 { return false }
+    let dd = distinct_degree(f, p);
+    dd.len() == 1 && dd[0].1 == n
+}
+
+/// Factor a polynomial into monic irreducible factors
+///
+/// Returns the leading coefficient of p along with a list of
+/// (factor, multiplicity) pairs, ordered by degree and then by
+/// coefficients.
+pub fn factor<G>(f : &G, p : &[G::E]) -> (G::E, Vec<(Poly<G>, usize)>)
+where G : GaloisField
+{
+    let lead = poly::lead(p);
+    let mut out = Vec::new();
+    for (sf, mult) in square_free(f, p) {
+	for (dd, d) in distinct_degree(f, &sf) {
+	    for irr in equal_degree(f, &dd, d) {
+		out.push((irr, mult))
+	    }
+	}
+    }
+    out.sort_by(|(a, _), (b, _)| {
+	a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+    });
+    (lead, out)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{new_gf4, new_gf8, new_gf16};
+    use crate::roots::find_roots;
+
+    // multiply out a factorisation
+    fn expand<G>(f : &G, lead : G::E, factors : &[(Poly<G>, usize)]) -> Poly<G>
+    where G : GaloisField
+    {
+	let mut p = vec![lead];
+	for (fac, m) in factors {
+	    for _ in 0..*m { p = poly::mul(f, &p, fac) }
+	}
+	p
+    }
+
+    #[test]
+    fn square_free_of_known_product() {
+	let f = new_gf8(0x11b, 0x1b);
+	let a = vec![1u8, 1];		// x + 1
+	let b = vec![7u8, 0, 1];	// x² + 7
+	let c = vec![9u8, 3, 1];
+	let p = expand(&f, 1, &[(a.clone(), 1), (b.clone(), 2),
+				(c.clone(), 4)]);
+	let sf = square_free(&f, &p);
+	assert_eq!(expand(&f, 1, &sf), p);
+	for (part, _) in sf.iter() {
+	    assert_eq!(square_free(&f, part), vec![(part.clone(), 1)]);
+	}
+    }
+
+    #[test]
+    fn distinct_degree_separates_by_degree() {
+	let f = new_gf4(19, 3);
+	// collect some irreducible quadratics and cubics by brute
+	// force
+	let quads : Vec<Vec<u8>> = (0..16).flat_map(|b| (1..16).map(
+	    move |c| vec![c, b, 1])).filter(|q| is_irreducible(&f, q))
+	    .take(2).collect();
+	let cubic = (1..16).map(|c| vec![c, 0, 1, 1])
+	    .find(|q| is_irreducible(&f, q)).unwrap();
+	let linear = vec![3u8, 1];
+	let p = expand(&f, 1, &[(quads[0].clone(), 1), (quads[1].clone(), 1),
+				(cubic.clone(), 1), (linear.clone(), 1)]);
+	let dd = distinct_degree(&f, &p);
+	assert_eq!(dd.len(), 3);
+	assert_eq!(dd[0], (linear, 1));
+	assert_eq!(dd[1].0, poly::mul(&f, &quads[0], &quads[1]));
+	assert_eq!(dd[2], (cubic, 3));
+    }
+
+    #[test]
+    fn equal_degree_splits_linear_factors() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let roots = [3u16, 0x8000, 1234, 55, 0xffff];
+	let p = poly::from_roots(&f, &roots);
+	let mut want : Vec<Poly<crate::F16>> = roots.iter()
+	    .map(|r| vec![*r, 1]).collect();
+	want.sort();
+	assert_eq!(equal_degree(&f, &p, 1), want);
+	assert!(equal_degree(&f, &[], 1).is_empty());
+	assert!(equal_degree(&f, &[0, 0], 2).is_empty());
+    }
+
+    #[test]
+    fn factor_round_trip() {
+	let f = new_gf8(0x11d, 0x1d);
+	let p = vec![9u8, 0, 0x33, 7, 1, 0, 0xfe, 0x80, 0, 1, 4, 3];
+	let (lead, factors) = factor(&f, &p);
+	assert_eq!(expand(&f, lead, &factors), p);
+	for (irr, _) in factors.iter() {
+	    assert!(is_irreducible(&f, irr));
+	}
+	// linear factors must agree with root finding
+	let linear : Vec<(u8, usize)> = factors.iter()
+	    .filter(|(irr, _)| irr.len() == 2)
+	    .map(|(irr, m)| (irr[0], *m)).collect();
+	let mut roots = find_roots(&f, &p);
+	roots.sort();
+	let mut linear_sorted = linear.clone();
+	linear_sorted.sort();
+	assert_eq!(linear_sorted, roots);
+    }
+
+    #[test]
+    fn factor_perfect_power() {
+	let f = new_gf8(0x11b, 0x1b);
+	let q = vec![0x20u8, 1, 1];
+	let p = expand(&f, 0x11, &[(q.clone(), 6)]);
+	assert_eq!(factor(&f, &p), (0x11, vec![(q, 6)]));
+    }
+}
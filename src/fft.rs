@@ -0,0 +1,314 @@
+//! # Additive FFT over GF(2<sup>x</sup>)
+//!
+//! The usual (multiplicative) FFT needs a field with a 2<sup>k</sup>th
+//! root of unity, which binary fields don't have. The *additive* FFT
+//! instead evaluates a polynomial at all the points of an affine
+//! subspace of the field, viewed as a vector space over GF(2). This
+//! module implements the O(n log n) algorithm of Lin, Chung and Han
+//! ("Novel Polynomial Basis and Its Application to Reed-Solomon
+//! Erasure Codes", 2014).
+//!
+//! # Evaluation points
+//!
+//! We use the standard polynomial basis v<sub>j</sub> = 2<sup>j</sup>
+//! (ie, x<sup>j</sup>) of the field, so the subspace spanned by the
+//! first k basis vectors is simply the set of elements less than
+//! 2<sup>k</sup>. A transform of length n = 2<sup>k</sup> with a given
+//! `shift` evaluates at the points `i ^ shift` for i in 0..n, in that
+//! order. The shift should normally be a multiple of n (ie, have its
+//! low k bits clear), making the points a coset of the subspace.
+//!
+//! # Novel polynomial basis
+//!
+//! The transforms don't work on the usual monomial coefficients.
+//! Instead, polynomials are expressed in the "novel" basis
+//! X<sub>i</sub>(x), which is a product of normalised subspace
+//! vanishing polynomials, one for each set bit of i. [to_novel] and
+//! [from_novel] convert between the two in O(n log<sup>2</sup> n)
+//! time.
+//!
+//! # Fast multiplication
+//!
+//! [mul] multiplies two polynomials (in the monomial basis used by
+//! [crate::poly]) by converting them to the novel basis, transforming,
+//! multiplying pointwise and transforming back:
+//!
+//! ```rust
+//! use guff::good::new_gf16_0x1002b;
+//! use guff::{fft, poly};
+//!
+//! let f = new_gf16_0x1002b();
+//! let a : Vec<u16> = (1..=300).collect();
+//! let b : Vec<u16> = (1000..1200).collect();
+//! assert_eq!(fft::mul(&f, &a, &b), poly::mul(&f, &a, &b));
+//! ```
+//!
+//! All transforms are done in place on `&mut [G::E]` slices, whose
+//! length must be a power of two no greater than the field size.
+
+use crate::GaloisField;
+use crate::poly::{self, Poly};
+
+use num::{One,Zero,FromPrimitive};
+
+// Subspace vanishing polynomials s_j(x) = Π (x - a) for a in
+// span(v_0 .. v_{j-1}) are linearised polynomials:
+//
+//   s_j(x) = Σ_{t<=j} c[j][t] x**(2**t)
+//
+// with s_0(x) = x and s_{j+1}(x) = s_j(x)**2 + s_j(v_j) s_j(x). We
+// also need norm[j] = s_j(v_j) to normalise them.
+struct Vanishing<G> where G : GaloisField {
+    coef : Vec<Vec<G::E>>,
+    norm : Vec<G::E>,
+}
+
+impl<G> Vanishing<G> where G : GaloisField {
+    fn new(f : &G, levels : usize) -> Vanishing<G> {
+	let zero = G::E::zero();
+	let mut coef : Vec<Vec<G::E>> = Vec::with_capacity(levels);
+	let mut norm = Vec::with_capacity(levels);
+	let mut c = vec![G::E::one()];
+	for j in 0..levels {
+	    let v = G::E::one() << j;
+	    let this = Vanishing::<G>::eval_coef(f, &c, v);
+	    norm.push(this);
+	    coef.push(c.clone());
+	    // next: c[t] = c[t-1]**2 + norm * c[t]
+	    let mut next = vec![zero; c.len() + 1];
+	    for (t, ct) in c.iter().enumerate() {
+		next[t + 1] = next[t + 1] ^ f.mul(*ct, *ct);
+		next[t]     = next[t]     ^ f.mul(*ct, this);
+	    }
+	    c = next;
+	}
+	Vanishing { coef, norm }
+    }
+
+    fn eval_coef(f : &G, c : &[G::E], mut y : G::E) -> G::E {
+	let mut sum = G::E::zero();
+	for ct in c.iter() {
+	    sum = sum ^ f.mul(*ct, y);
+	    y = f.mul(y, y);
+	}
+	sum
+    }
+
+    // normalised ŝ_j(y) = s_j(y) / s_j(v_j)
+    fn eval_hat(&self, f : &G, j : usize, y : G::E) -> G::E {
+	f.div(Vanishing::<G>::eval_coef(f, &self.coef[j], y), self.norm[j])
+    }
+}
+
+// log2 of a power-of-two transform length, with sanity checks
+fn levels<G>(n : usize) -> usize where G : GaloisField {
+    assert!(n.is_power_of_two(), "transform length must be a power of 2");
+    let k = n.trailing_zeros() as usize;
+    assert!(k <= G::ORDER as usize, "transform length exceeds field size");
+    k
+}
+
+/// Forward additive FFT
+///
+/// On entry, `data` holds the coefficients of a polynomial in the
+/// novel basis. On exit, `data[i]` holds its value at the point
+/// `i ^ shift`.
+pub fn fft<G>(f : &G, data : &mut [G::E], shift : G::E)
+where G : GaloisField
+{
+    let k = levels::<G>(data.len());
+    let s = Vanishing::new(f, k);
+    for level in (0..k).rev() {
+	let half = 1 << level;
+	for start in (0..data.len()).step_by(half * 2) {
+	    let beta = shift ^ G::E::from_usize(start).unwrap();
+	    let c = s.eval_hat(f, level, beta);
+	    let (lo, hi) = data[start..start + 2 * half].split_at_mut(half);
+	    for (l, h) in lo.iter_mut().zip(hi.iter_mut()) {
+		*l = *l ^ f.mul(c, *h);
+		*h = *h ^ *l;
+	    }
+	}
+    }
+}
+
+/// Inverse additive FFT
+///
+/// Takes the values of a polynomial at the points `i ^ shift` and
+/// recovers its coefficients in the novel basis.
+pub fn ifft<G>(f : &G, data : &mut [G::E], shift : G::E)
+where G : GaloisField
+{
+    let k = levels::<G>(data.len());
+    let s = Vanishing::new(f, k);
+    for level in 0..k {
+	let half = 1 << level;
+	for start in (0..data.len()).step_by(half * 2) {
+	    let beta = shift ^ G::E::from_usize(start).unwrap();
+	    let c = s.eval_hat(f, level, beta);
+	    let (lo, hi) = data[start..start + 2 * half].split_at_mut(half);
+	    for (l, h) in lo.iter_mut().zip(hi.iter_mut()) {
+		*h = *h ^ *l;
+		*l = *l ^ f.mul(c, *h);
+	    }
+	}
+    }
+}
+
+/// Convert monomial coefficients to novel basis coefficients, in
+/// place
+///
+/// Works by repeated division by the (sparse) vanishing polynomials,
+/// splitting p = lo + ŝ<sub>j</sub>·hi at each level.
+pub fn to_novel<G>(f : &G, data : &mut [G::E])
+where G : GaloisField
+{
+    let k = levels::<G>(data.len());
+    let s = Vanishing::new(f, k);
+    let zero = G::E::zero();
+    for level in (0..k).rev() {
+	let half = 1 << level;
+	let c = &s.coef[level];
+	for block in data.chunks_mut(half * 2) {
+	    // divide by monic s_j in place: quotient ends up in the
+	    // top half, remainder in the bottom
+	    for i in (half..2 * half).rev() {
+		let q = block[i];
+		if q == zero { continue }
+		for (t, ct) in c[..level].iter().enumerate() {
+		    let idx = i - half + (1 << t);
+		    block[idx] = block[idx] ^ f.mul(q, *ct);
+		}
+	    }
+	    // quotient by ŝ_j is s_j(v_j) times quotient by s_j
+	    f.vec_constant_scale_in_place(&mut block[half..], s.norm[level]);
+	}
+    }
+}
+
+/// Convert novel basis coefficients back to monomial coefficients,
+/// in place
+pub fn from_novel<G>(f : &G, data : &mut [G::E])
+where G : GaloisField
+{
+    let k = levels::<G>(data.len());
+    let s = Vanishing::new(f, k);
+    let zero = G::E::zero();
+    for level in 0..k {
+	let half = 1 << level;
+	let c = &s.coef[level];
+	let inv_norm = f.inv(s.norm[level]);
+	for block in data.chunks_mut(half * 2) {
+	    f.vec_constant_scale_in_place(&mut block[half..], inv_norm);
+	    // lo + s_j·hi, in ascending order so that hi values are
+	    // used before they are overwritten
+	    for i in 0..half {
+		let h = block[half + i];
+		if h == zero { continue }
+		for (t, ct) in c[..level].iter().enumerate() {
+		    let idx = i + (1 << t);
+		    block[idx] = block[idx] ^ f.mul(h, *ct);
+		}
+	    }
+	}
+    }
+}
+
+/// Fast polynomial multiplication via the additive FFT
+///
+/// Inputs and output use the monomial basis, as in [crate::poly].
+/// The product must have fewer coefficients than there are elements
+/// in the field.
+pub fn mul<G>(f : &G, a : &[G::E], b : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    let (da, db) = match (poly::degree(a), poly::degree(b)) {
+	(Some(da), Some(db)) => (da, db),
+	_ => return Vec::new()
+    };
+    let n = (da + db + 1).next_power_of_two();
+    let zero = G::E::zero();
+
+    let mut fa = vec![zero; n];
+    fa[..=da].copy_from_slice(&a[..=da]);
+    let mut fb = vec![zero; n];
+    fb[..=db].copy_from_slice(&b[..=db]);
+
+    to_novel(f, &mut fa);
+    to_novel(f, &mut fb);
+    fft(f, &mut fa, zero);
+    fft(f, &mut fb, zero);
+    for (x, y) in fa.iter_mut().zip(fb.iter()) {
+	*x = f.mul(*x, *y)
+    }
+    ifft(f, &mut fa, zero);
+    from_novel(f, &mut fa);
+    poly::normalise(&mut fa);
+    fa
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{new_gf4, new_gf8, new_gf16, new_gf32};
+    use crate::good::{new_gf8_0x11b, new_gf16_0x1002b};
+
+    #[test]
+    fn novel_basis_round_trip() {
+	let f = new_gf8(0x11d, 0x1d);
+	let orig : Vec<u8> = (0..64).map(|i| (i * 37 + 11) as u8).collect();
+	let mut data = orig.clone();
+	to_novel(&f, &mut data);
+	assert_ne!(data, orig);
+	from_novel(&f, &mut data);
+	assert_eq!(data, orig);
+    }
+
+    #[test]
+    fn fft_evaluates_polynomial() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let p : Vec<u16> = (0..32).map(|i| i * 1001 + 7).collect();
+	for shift in [0u16, 32, 0x4000].iter() {
+	    let mut data = p.clone();
+	    to_novel(&f, &mut data);
+	    fft(&f, &mut data, *shift);
+	    for (i, v) in data.iter().enumerate() {
+		assert_eq!(*v, poly::eval(&f, &p, (i as u16) ^ *shift));
+	    }
+	    ifft(&f, &mut data, *shift);
+	    from_novel(&f, &mut data);
+	    assert_eq!(data, p);
+	}
+    }
+
+    #[test]
+    fn fft_whole_field() {
+	let f = new_gf4(19, 3);
+	let p = vec![3u8, 0, 9, 1, 15, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7];
+	let mut data = p.clone();
+	to_novel(&f, &mut data);
+	fft(&f, &mut data, 0);
+	for x in 0..16u8 {
+	    assert_eq!(data[x as usize], poly::eval(&f, &p, x));
+	}
+    }
+
+    #[test]
+    fn mul_agrees_with_schoolbook() {
+	let f = new_gf8_0x11b();
+	let a : Vec<u8> = (0..100).map(|i| (i * 3) as u8).collect();
+	let b : Vec<u8> = (0..55).map(|i| (i ^ 0x5a) as u8).collect();
+	assert_eq!(mul(&f, &a, &b), poly::mul(&f, &a, &b));
+
+	let f = new_gf16_0x1002b();
+	let a : Vec<u16> = (0..700).map(|i| i * 91).collect();
+	let b = vec![1u16, 0, 0, 0x8000];
+	assert_eq!(mul(&f, &a, &b), poly::mul(&f, &a, &b));
+
+	let f = new_gf32(0x1_0000_008d, 0x8d);
+	let a : Vec<u32> = (0..40).map(|i| i * 0x0101_0101).collect();
+	let b : Vec<u32> = (0..33).map(|i| 0xffff_0000 ^ i).collect();
+	assert_eq!(mul(&f, &a, &b), poly::mul(&f, &a, &b));
+    }
+}
@@ -51,7 +51,7 @@
 use crate::{ GaloisField };
 use crate::tables::mull::{lmull,rmull};
 
-use num::{One,Zero};
+use num::{One,Zero,ToPrimitive,FromPrimitive};
 use std::convert::TryInto;
 
 //use num_traits;
@@ -107,12 +107,17 @@ where G : GaloisField, G::E : Into<usize>
     #[inline(always)]
     fn mul(&self, a : G::E, b : G::E) -> G::E {
 	let index : usize = (a.into() << G::ORDER) + b.into();
-	// Can't do unsafe access without ensuring a,b < 16: 
+	// Can't do unsafe access without ensuring a,b < 16:
 	// unsafe {
 	//    *self.table.get_unchecked(index)
 	// }
 	self.table[index]
     }
+    // all products a·x, indexed by x
+    fn row(&self, a : G::E) -> &[G::E] {
+	let start : usize = a.into() << G::ORDER;
+	&self.table[start .. start + (1 << G::ORDER)]
+    }
 }
 
 // Note that I didn't have to make the above generic on a particular
@@ -148,7 +153,7 @@ fn fill_inverse<T>(f : & T,
 // * 0x19 (25) (primitive)
 // * 0x1f (31)
 //
-// There's probably no reason to use 0x1f. 
+// There's probably no reason to use 0x1f.
 
 // "good" F4 with fixed poly 0x13 using above mul table
 // Not meant to be used directly. Use [new_gf4_0x13] constructor
@@ -190,6 +195,19 @@ impl GaloisField for F4_0x13 {
 	// can use 'a as usize' since its type is known to be u8
 	self.inv_lut[a as usize]
     }
+
+    // one row of the mul table has all the products we need
+    fn vec_add_scaled_vec_in_place(&self,
+				   dest  : &mut [Self::E],
+				   other : &[Self::E],
+				   a     : Self::E) {
+	assert_eq!(dest.len(), other.len());
+	if a == 0 { return }
+	let row = self.mul_lut.row(a);
+	for (d,o) in dest.iter_mut().zip(other) {
+	    *d ^= row[*o as usize]
+	}
+    }
 }
 
 /// Optimised GF(2<sup>4</sup>) with the (primitive) polynomial 0x13
@@ -200,7 +218,7 @@ pub fn new_gf4_0x13() -> F4_0x13 {
     let mut inv = Vec::<u8>::with_capacity(16);
 
     fill_inverse(&f, &mut inv, 15);
-    
+
     F4_0x13 {
 	mul_lut : FullMulLUT::<crate::F4>::new(&f),
 	inv_lut : inv,
@@ -224,6 +242,8 @@ pub fn new_gf4_0x13() -> F4_0x13 {
 
 struct BigLogExpTables<G> where G : GaloisField {
     log  : Vec<G::SEE>,
+    // only accessed through exp_entry, but must be kept alive
+    #[allow(dead_code)]
     exp  : Vec<G::E>,
     exp_entry : *const G::E,
 }
@@ -290,7 +310,7 @@ where G : GaloisField,
 	}
 
 	// We have inserted all log table entries.
-	// 
+	//
 	// exp entries currently stand at:
 	//
 	// 512 zero values
@@ -306,7 +326,7 @@ where G : GaloisField,
 	// We make the final entry be 0, though, so that inv(0) = 0
 	// works as expected: index 255 - log 0 = 255 + 256
 	assert_eq!(p, G::E::one());
-	for _ in 0..log_size-1 { // 
+	for _ in 0..log_size-1 { //
 	    p = f.mul(p,g);
 	    exp.push(p);
 	}
@@ -422,13 +442,66 @@ impl GaloisField for F8_0x11b {
     fn pow(&self, a : Self::E, b : Self::EE) -> Self::E {
 	self.tables.pow(a,b)
     }
+
+    // For long enough slices, it pays to build the 256-entry table
+    // of products up front
+    fn vec_add_scaled_vec_in_place(&self,
+				   dest  : &mut [Self::E],
+				   other : &[Self::E],
+				   a     : Self::E) {
+	assert_eq!(dest.len(), other.len());
+	if a == 0 { return }
+	if dest.len() < 256 {
+	    for (d,o) in dest.iter_mut().zip(other) {
+		*d ^= self.tables.mul(*o, a)
+	    }
+	    return
+	}
+	let table = self.product_table(a);
+	for (d,o) in dest.iter_mut().zip(other) {
+	    *d ^= table[*o as usize]
+	}
+    }
+
+    // Build every coefficient's table once, not once per block
+    fn matrix_apply(&self,
+		    coeffs  : &[Self::E],
+		    inputs  : &[&[Self::E]],
+		    outputs : &mut [&mut [Self::E]]) {
+	let k = inputs.len();
+	assert_eq!(coeffs.len(), k * outputs.len(), "need m×k coefficients");
+	// short slices: building the tables would cost more than it saves
+	if outputs.first().map_or(0, |o| o.len()) < 256 {
+	    crate::apply_blocked(inputs, outputs, |i, j, dest, src| {
+		self.vec_add_scaled_vec_in_place(dest, src, coeffs[i * k + j])
+	    });
+	    return
+	}
+	let tables : Vec<[u8; 256]> = coeffs.iter().map(|c| self.product_table(*c)).collect();
+	crate::apply_blocked(inputs, outputs, |i, j, dest, src| {
+	    let table = &tables[i * k + j];
+	    for (d,s) in dest.iter_mut().zip(src) {
+		*d ^= table[*s as usize]
+	    }
+	});
+    }
+}
+
+impl F8_0x11b {
+    fn product_table(&self, a : u8) -> [u8; 256] {
+	let mut table = [0u8; 256];
+	for (x, t) in table.iter_mut().enumerate() {
+	    *t = self.tables.mul(a, x as u8)
+	}
+	table
+    }
 }
 
 /// Optimised GF(2<sup>8</sup>) with the (non-primitive) polynomial 0x11b
 pub fn new_gf8_0x11b() -> F8_0x11b {
     // reference field object
     let f = crate::new_gf8(0x11b,0x1b);
-    
+
     let this = F8_0x11b {	// field has generator 3
 	tables : BigLogExpTables::<crate::F8>::new(&f, 3),
     };
@@ -479,6 +552,32 @@ pub fn new_gf8_0x11b() -> F8_0x11b {
 
 // In addition to using table-based mull (using fixed size fragments)
 // we'll need poly-specific mod-reduce tables and inverse tables
+// The shared part of the bytewise tables: for each top byte t, the
+// remainder (t << degree) mod p(x) and the quotient bits, for a
+// binary polynomial given as degree (1..=64) and compact value. This
+// is a Horner loop, one bit of t at a time, so it also works where
+// t << degree would overflow a u64. lfsr uses it for stepping binary
+// Galois LFSRs a byte at a time.
+pub(crate) fn bytewise_reduce_table(degree : u32, compact : u64) -> (Vec<u8>, Vec<u64>) {
+    assert!(degree > 0 && degree <= 64);
+    let mask = u64::MAX >> (64 - degree);
+    let mut quotients = Vec::with_capacity(256);
+    let mut remainders = Vec::with_capacity(256);
+    for top in 0..=255u8 {
+	let (mut q, mut r) = (0u8, 0u64);
+	for bit in (0..8).rev() {
+	    // coefficient of x^degree in r * x + t_bit * x^degree
+	    let high = ((r >> (degree - 1)) as u8 & 1) ^ ((top >> bit) & 1);
+	    r = (r << 1) & mask;
+	    if high == 1 { r ^= compact & mask }
+	    q = (q << 1) | high;
+	}
+	quotients.push(q);
+	remainders.push(r);
+    }
+    (quotients, remainders)
+}
+
 struct BytewiseReduceTable<G> where G : GaloisField {
     reduce  : Vec<G::E>,
 }
@@ -489,26 +588,13 @@ where G : GaloisField,
 //      G::E   : From<G::EE>,
       G::E   : std::fmt::Debug
 {
-    fn new(f : &G) -> BytewiseReduceTable<G>
-    where
-    // G::EE  : Into<usize>,
-    //	G::E   : From<G::EE>, // offending line
-	G::E   : std::convert::TryFrom<G::EE>,
-	G::E   : Into<usize>
-    {
-
-	let mut reduce = Vec::<G::E>::with_capacity(256);
-
-	// for u8, would count 0, 256, 512, 768, 1024, ..., 65280
-	let mut i = G::EE::zero();
-	let delta = G::EE::one() << G::ORDER.into();
-	for _ in 0..=254 {
-	    let add = G::mod_reduce(i, f.full_poly());
-	    // eprintln!("Adding {} mod poly = {}", i, add);
-	    reduce.push(add);
-	    i = i + delta;	// can overflow if 0..=255
-	}
-	reduce.push(G::mod_reduce(i, f.full_poly()));
+    fn new(f : &G) -> BytewiseReduceTable<G> {
+	// for u8, entries are 0, 256, 512, 768, 1024, ..., 65280 mod poly
+	let poly = f.poly().to_u64().unwrap();
+	let (_, table) = bytewise_reduce_table(G::ORDER as u32, poly);
+	let reduce : Vec<G::E> = table.iter()
+	    .map(|r| G::E::from_u64(*r).unwrap())
+	    .collect();
 	assert_eq!(reduce.len(), 256);
 	BytewiseReduceTable::<G> { reduce }
     }
@@ -542,6 +628,7 @@ where G : GaloisField,
     }
 
     // Take a G::EE, reduce it to a G::E one byte at a time
+    #[allow(dead_code)]
     fn mod_reduce_bytewise(&self, mut a : G::EE) -> G::E
     where G::E  : Into<G::EE>,
 	  G::EE : std::convert::TryInto<G::E>,
@@ -614,20 +701,73 @@ impl GaloisField for F16_0x1002b {
 	c = lmull(b1, a3) ^ rmull(b1, a2);
 
 	c = self.reduce.mod_shift_left_8(c);
-	
+
 	c = c ^ lmull(b0, a3) ^ rmull(b0, a2);
 	c = c ^ lmull(b1, a1) ^ rmull(b1, a0);
 
 	c = self.reduce.mod_shift_left_8(c);
 
 	c = c ^ lmull(b0, a1) ^ rmull(b0, a0);
-	
+
 	c
     }
     fn inv(&self, a : Self::E) -> Self::E
     {
 	self.inv[a as usize]
     }
+
+    // a·x = a·(x & 0xff) ^ a·(x & 0xff00), so two 256-entry tables
+    // cover every product
+    fn vec_add_scaled_vec_in_place(&self,
+				   dest  : &mut [Self::E],
+				   other : &[Self::E],
+				   a     : Self::E) {
+	assert_eq!(dest.len(), other.len());
+	if a == 0 { return }
+	if dest.len() < 512 {
+	    for (d,o) in dest.iter_mut().zip(other) {
+		*d ^= self.mul(*o, a)
+	    }
+	    return
+	}
+	let (lo, hi) = self.split_tables(a);
+	for (d,o) in dest.iter_mut().zip(other) {
+	    *d ^= lo[(*o & 0xff) as usize] ^ hi[(*o >> 8) as usize]
+	}
+    }
+
+    fn matrix_apply(&self,
+		    coeffs  : &[Self::E],
+		    inputs  : &[&[Self::E]],
+		    outputs : &mut [&mut [Self::E]]) {
+	let k = inputs.len();
+	assert_eq!(coeffs.len(), k * outputs.len(), "need m×k coefficients");
+	if outputs.first().map_or(0, |o| o.len()) < 512 {
+	    crate::apply_blocked(inputs, outputs, |i, j, dest, src| {
+		self.vec_add_scaled_vec_in_place(dest, src, coeffs[i * k + j])
+	    });
+	    return
+	}
+	let tables : Vec<_> = coeffs.iter().map(|c| self.split_tables(*c)).collect();
+	crate::apply_blocked(inputs, outputs, |i, j, dest, src| {
+	    let (lo, hi) = &tables[i * k + j];
+	    for (d,s) in dest.iter_mut().zip(src) {
+		*d ^= lo[(*s & 0xff) as usize] ^ hi[(*s >> 8) as usize]
+	    }
+	});
+    }
+}
+
+impl F16_0x1002b {
+    fn split_tables(&self, a : u16) -> ([u16; 256], [u16; 256]) {
+	let mut lo = [0u16; 256];
+	let mut hi = [0u16; 256];
+	for x in 0..256 {
+	    lo[x] = self.mul(a, x as u16);
+	    hi[x] = self.mul(a, (x as u16) << 8);
+	}
+	(lo, hi)
+    }
 }
 
 /// Optimised GF(2<sup>16</sup>) with the (primitive?) polynomial 0x1002b
@@ -641,7 +781,7 @@ pub fn new_gf16_0x1002b() -> F16_0x1002b {
 
     // generate mod reduce table
     let reduce = BytewiseReduceTable::<crate::F16>::new(&f);
-    
+
     F16_0x1002b {
 	reduce,	inv
     }
@@ -655,14 +795,14 @@ pub fn new_gf16_0x1002b() -> F16_0x1002b {
 //   for `mul`
 // * rest supplied by default
 //
-// 
+//
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
     use crate::{new_gf4, new_gf8, new_gf16};
-    use crate::{F4, F8, F16};
+    use crate::F8;
 
     #[test]
     fn test_f4_0x13_mul_conformance() {
@@ -920,7 +1060,7 @@ mod tests {
 	for i in 0u16..=65535 {
 	    let ref_res      : u8 = F8::mod_reduce(i,0x11b);
 	    let bytewise_res : u8 = reduce.mod_reduce_bytewise(i);
-	    if ref_res != bytewise_res { fails += 1} 
+	    if ref_res != bytewise_res { fails += 1}
 	}
 	assert_eq!(fails, 0);
     }
@@ -943,6 +1083,58 @@ mod tests {
 
 	assert_eq!(ref_res, shift_res);
     }
-    
-    
+
+
+
+    // straightforward reference version of matrix_apply
+    fn reference<G : GaloisField>(f : &G, coeffs : &[G::E], inputs : &[Vec<G::E>]) -> Vec<Vec<G::E>> {
+	let k = inputs.len();
+	let len = inputs[0].len();
+	(0..coeffs.len() / k).map(|i| (0..len).map(|t| {
+	    (0..k).fold(G::E::zero(), |acc, j| acc ^ f.mul(coeffs[i * k + j], inputs[j][t]))
+	}).collect()).collect()
+    }
+
+    fn check<G : GaloisField>(f : &G, coeffs : &[G::E], inputs : &[Vec<G::E>]) {
+	let m = coeffs.len() / inputs.len();
+	let len = inputs[0].len();
+	let mut out = vec![vec![G::E::zero(); len]; m];
+	let in_refs : Vec<&[G::E]> = inputs.iter().map(|v| &v[..]).collect();
+	let mut out_refs : Vec<&mut [G::E]> = out.iter_mut().map(|v| &mut v[..]).collect();
+	f.matrix_apply(coeffs, &in_refs, &mut out_refs);
+	assert_eq!(out, reference(f, coeffs, inputs));
+    }
+
+    #[test]
+    fn matrix_apply_default_and_good_agree() {
+	// long enough to span several blocks and use the table paths
+	let len = 10_000;
+	let in8 : Vec<Vec<u8>> = (0..3).map(|j| (0..len).map(|t| (t * 7 + j * 91) as u8).collect()).collect();
+	let c8 = [1u8, 2, 3, 0x53, 0xca, 0, 0xff, 0x80, 7, 9, 10, 11];
+	check(&new_gf8(0x11b, 0x1b), &c8, &in8);
+	check(&new_gf8_0x11b(), &c8, &in8);
+
+	let in16 : Vec<Vec<u16>> = (0..2).map(|j| (0..len).map(|t| (t * 4099 + j) as u16).collect()).collect();
+	let c16 = [0x1234u16, 1, 0, 0xffff];
+	check(&new_gf16(0x1002b, 0x2b), &c16, &in16);
+	check(&new_gf16_0x1002b(), &c16, &in16);
+
+	let in4 : Vec<Vec<u8>> = (0..2).map(|j| (0..100).map(|t| ((t + j) % 16) as u8).collect()).collect();
+	check(&new_gf4_0x13(), &[3u8, 9, 15, 0], &in4);
+
+	// below the table cutoffs
+	check(&new_gf8_0x11b(), &c8, &[in8[0][..100].to_vec(), in8[1][..100].to_vec(), in8[2][..100].to_vec()]);
+	check(&new_gf16_0x1002b(), &c16, &[in16[0][..300].to_vec(), in16[1][..300].to_vec()]);
+    }
+
+    #[test]
+    fn good_scaled_add_matches_default() {
+	let f = new_gf16_0x1002b();
+	let src : Vec<u16> = (0..2000u16).map(|x| x.wrapping_mul(31)).collect();
+	let mut a = vec![5u16; 2000];
+	let mut b = a.clone();
+	f.vec_add_scaled_vec_in_place(&mut a, &src, 0xbeef);
+	for (d, s) in b.iter_mut().zip(&src) { *d ^= f.mul(*s, 0xbeef) }
+	assert_eq!(a, b);
+    }
 }
@@ -0,0 +1,299 @@
+//! # Rabin's Information Dispersal Algorithm
+//!
+//! IDA splits a block of data into n *shares*, each about 1/k of the
+//! size of the original, such that any k of them are enough to put
+//! the original back together. It works by treating the data as a
+//! matrix with k rows, and multiplying it by an n×k *transform
+//! matrix*, any k rows of which are linearly independent. Share i is
+//! row i of the product. To combine, the k×k submatrix for the shares
+//! at hand is inverted and applied to them.
+//!
+//! The transform can be either a [Transform::Vandermonde] or a
+//! [Transform::Cauchy] matrix. Either way, inversion uses the
+//! O(k<sup>2</sup>) closed forms from [crate::matrix], and the bulk
+//! of the work is done by [GaloisField::matrix_apply], so the
+//! optimised fields from [crate::good] speed everything up.
+//!
+//! ```rust
+//! use guff::good::new_gf8_0x11b;
+//! use guff::ida::{split, combine, Transform};
+//!
+//! let f = new_gf8_0x11b();
+//! let data = b"The quick brown fox jumps over the lazy dog";
+//!
+//! let shares = split(&f, data, 3, 5, Transform::Cauchy).unwrap();
+//! assert_eq!(shares.len(), 5);
+//! assert_eq!(shares[0].data.len(), 15);
+//!
+//! // any three will do
+//! let some = vec![shares[4].clone(), shares[0].clone(), shares[2].clone()];
+//! assert_eq!(combine(&f, &some).unwrap(), data.to_vec());
+//! ```
+//!
+//! For GF(2<sup>16</sup>), data bytes are taken in pairs as
+//! big-endian elements, and padded with a zero byte if need be.
+
+use crate::GaloisField;
+use crate::matrix::Matrix;
+use crate::bytes::{ElementBytes, Endian, elements_from_bytes, elements_to_bytes};
+
+use num::{Zero,FromPrimitive};
+use std::fmt;
+
+/// Which kind of transform matrix to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// Row i is (1, x<sub>i</sub>, x<sub>i</sub><sup>2</sup>, ...)
+    /// with x<sub>i</sub> = i
+    Vandermonde,
+    /// Entry (i, j) is 1/(x<sub>i</sub> + y<sub>j</sub>) with
+    /// x<sub>i</sub> = i and y<sub>j</sub> = n + j
+    Cauchy,
+}
+
+/// Errors from [split] and [combine]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdaError {
+    /// k or n out of range (need 1 ≤ k ≤ n, and the field must be
+    /// big enough for the transform's points)
+    BadParameters,
+    /// Fewer than k shares supplied
+    NotEnoughShares,
+    /// Shares come from different splits, or have been truncated
+    InconsistentShares,
+    /// The same share index was supplied twice
+    DuplicateShare,
+}
+
+impl fmt::Display for IdaError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    IdaError::BadParameters      => write!(f, "bad IDA parameters"),
+	    IdaError::NotEnoughShares    => write!(f, "not enough shares to combine"),
+	    IdaError::InconsistentShares => write!(f, "shares are inconsistent"),
+	    IdaError::DuplicateShare     => write!(f, "duplicate share"),
+	}
+    }
+}
+
+impl std::error::Error for IdaError {}
+
+/// One share of some dispersed data
+///
+/// Along with the share's own row of data, this carries everything
+/// needed to recombine it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share<E> {
+    /// Row of the transform matrix used for this share (0..n)
+    pub index     : usize,
+    /// Quorum: number of shares needed to combine
+    pub k         : usize,
+    /// Total number of shares created
+    pub n         : usize,
+    /// Transform matrix type
+    pub transform : Transform,
+    /// Length of the original data in bytes
+    pub len       : usize,
+    /// Share contents
+    pub data      : Vec<E>,
+}
+
+fn points<G>(from : usize, to : usize) -> Vec<G::E> where G : GaloisField {
+    (from..to).map(|i| G::E::from_usize(i).unwrap()).collect()
+}
+
+// k and n must fit the field: n points for Vandermonde, n + k for
+// Cauchy
+fn check_parameters<G>(transform : Transform, k : usize, n : usize) -> Result<(), IdaError>
+where G : GaloisField
+{
+    let size = 1u64 << G::ORDER;
+    let needed = match transform {
+	Transform::Vandermonde => Some(n),
+	Transform::Cauchy      => n.checked_add(k),
+    };
+    match needed {
+	Some(needed) if k > 0 && k <= n && needed as u64 <= size => Ok(()),
+	_ => Err(IdaError::BadParameters)
+    }
+}
+
+// build the rows of the transform for the given share indexes
+fn transform_rows<G>(f : &G, transform : Transform, k : usize, n : usize, rows : &[usize])
+		     -> Result<Matrix<G>, IdaError>
+where G : GaloisField
+{
+    let xs : Vec<G::E> = rows.iter().map(|r| G::E::from_usize(*r).unwrap()).collect();
+    Ok(match transform {
+	Transform::Vandermonde => Matrix::vandermonde(f, &xs, k),
+	Transform::Cauchy => Matrix::cauchy(f, &xs, &points::<G>(n, n + k))
+	    .map_err(|_| IdaError::BadParameters)?,
+    })
+}
+
+/// Split `data` into n shares, any k of which can recreate it
+pub fn split<G>(f : &G, data : &[u8], k : usize, n : usize, transform : Transform)
+		-> Result<Vec<Share<G::E>>, IdaError>
+where G : GaloisField, G::E : ElementBytes
+{
+    check_parameters::<G>(transform, k, n)?;
+    let all : Vec<usize> = (0..n).collect();
+    let t = transform_rows(f, transform, k, n, &all)?;
+
+    // pad to a whole number of k-element columns
+    let bytes = G::E::BYTES;
+    let cols = (data.len() + k * bytes - 1) / (k * bytes);
+    let mut padded = data.to_vec();
+    padded.resize(cols * k * bytes, 0);
+    let elements : Vec<G::E> = elements_from_bytes(&padded, Endian::Big);
+
+    // row j of the data matrix takes every kth element
+    let rows : Vec<Vec<G::E>> = (0..k)
+	.map(|j| elements.iter().skip(j).step_by(k).cloned().collect())
+	.collect();
+    let inputs : Vec<&[G::E]> = rows.iter().map(|r| &r[..]).collect();
+    let mut out = vec![vec![G::E::zero(); cols]; n];
+    let mut outputs : Vec<&mut [G::E]> = out.iter_mut().map(|r| &mut r[..]).collect();
+    f.matrix_apply(t.as_slice(), &inputs, &mut outputs);
+
+    let len = data.len();
+    Ok(out.into_iter().enumerate().map(|(index, row)| Share {
+	index, k, n, transform, len, data : row
+    }).collect())
+}
+
+/// Recreate the original data from any k (or more) shares
+///
+/// Only the first k shares are used; any others are ignored.
+pub fn combine<G>(f : &G, shares : &[Share<G::E>]) -> Result<Vec<u8>, IdaError>
+where G : GaloisField, G::E : ElementBytes
+{
+    let first = shares.first().ok_or(IdaError::NotEnoughShares)?;
+    let (k, n, transform, len) = (first.k, first.n, first.transform, first.len);
+    // check parameters as split() would have
+    check_parameters::<G>(transform, k, n)?;
+    if shares.len() < k { return Err(IdaError::NotEnoughShares) }
+    let shares = &shares[..k];
+
+    let bytes = G::E::BYTES;
+    let cols = (len + k * bytes - 1) / (k * bytes);
+    let mut seen = vec![false; n];
+    for s in shares {
+	if s.k != k || s.n != n || s.transform != transform || s.len != len
+	    || s.index >= n || s.data.len() != cols {
+		return Err(IdaError::InconsistentShares)
+	    }
+	if seen[s.index] { return Err(IdaError::DuplicateShare) }
+	seen[s.index] = true;
+    }
+
+    // invert the k×k submatrix for these shares
+    let rows : Vec<usize> = shares.iter().map(|s| s.index).collect();
+    let xs : Vec<G::E> = points::<G>(0, n);
+    let sub_xs : Vec<G::E> = rows.iter().map(|r| xs[*r]).collect();
+    let inv = match transform {
+	Transform::Vandermonde => Matrix::vandermonde_inverse(f, &sub_xs),
+	Transform::Cauchy => Matrix::cauchy_inverse(f, &sub_xs, &points::<G>(n, n + k)),
+    }.map_err(|_| IdaError::InconsistentShares)?;
+
+    let inputs : Vec<&[G::E]> = shares.iter().map(|s| &s.data[..]).collect();
+    let mut out = vec![vec![G::E::zero(); cols]; k];
+    let mut outputs : Vec<&mut [G::E]> = out.iter_mut().map(|r| &mut r[..]).collect();
+    f.matrix_apply(inv.as_slice(), &inputs, &mut outputs);
+
+    // interleave the data rows back into one stream
+    let mut elements = Vec::with_capacity(cols * k);
+    for c in 0..cols {
+	for row in out.iter() { elements.push(row[c]) }
+    }
+    let mut data = vec![0u8; cols * k * bytes];
+    elements_to_bytes(&elements, &mut data, Endian::Big);
+    data.truncate(len);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{new_gf8, new_gf16};
+    use crate::good::{new_gf8_0x11b, new_gf16_0x1002b};
+
+    fn sample(len : usize) -> Vec<u8> {
+	(0..len).map(|i| (i * 37 + i / 256) as u8).collect()
+    }
+
+    // try combining every k-subset (in a couple of orders)
+    fn all_subsets<G>(f : &G, shares : &[Share<G::E>], data : &[u8])
+    where G : GaloisField, G::E : ElementBytes
+    {
+	let n = shares.len();
+	let k = shares[0].k;
+	for mask in 0u32..(1 << n) {
+	    if mask.count_ones() as usize != k { continue }
+	    let mut pick : Vec<Share<G::E>> = (0..n)
+		.filter(|i| mask & (1 << i) != 0)
+		.map(|i| shares[i].clone()).collect();
+	    assert_eq!(combine(f, &pick).unwrap(), data);
+	    pick.reverse();
+	    assert_eq!(combine(f, &pick).unwrap(), data);
+	}
+    }
+
+    #[test]
+    fn gf8_both_transforms() {
+	let data = sample(1001);
+	for f_good in [false, true].iter() {
+	    for t in [Transform::Vandermonde, Transform::Cauchy].iter() {
+		if *f_good {
+		    let f = new_gf8_0x11b();
+		    let shares = split(&f, &data, 4, 7, *t).unwrap();
+		    assert_eq!(shares[3].data.len(), 251);
+		    all_subsets(&f, &shares, &data);
+		} else {
+		    let f = new_gf8(0x11d, 0x1d);
+		    let shares = split(&f, &data, 4, 7, *t).unwrap();
+		    all_subsets(&f, &shares, &data);
+		}
+	    }
+	}
+    }
+
+    #[test]
+    fn gf16_odd_length() {
+	let data = sample(12345);
+	let f = new_gf16_0x1002b();
+	for t in [Transform::Vandermonde, Transform::Cauchy].iter() {
+	    let shares = split(&f, &data, 3, 6, *t).unwrap();
+	    // 12345 bytes -> 6173 u16 -> 2058 columns of 3
+	    assert_eq!(shares[0].data.len(), 2058);
+	    all_subsets(&f, &shares, &data);
+	}
+	let f = new_gf16(0x1002b, 0x2b);
+	let shares = split(&f, &data[..99], 2, 3, Transform::Cauchy).unwrap();
+	assert_eq!(combine(&f, &shares[1..]).unwrap(), &data[..99]);
+    }
+
+    #[test]
+    fn errors() {
+	let f = new_gf8_0x11b();
+	let data = sample(100);
+	assert_eq!(split(&f, &data, 0, 3, Transform::Vandermonde).err(), Some(IdaError::BadParameters));
+	assert_eq!(split(&f, &data, 4, 3, Transform::Vandermonde).err(), Some(IdaError::BadParameters));
+	assert_eq!(split(&f, &data, 10, 250, Transform::Cauchy).err(), Some(IdaError::BadParameters));
+	assert_eq!(split(&f, &data, usize::MAX, usize::MAX, Transform::Cauchy).err(), Some(IdaError::BadParameters));
+	assert!(split(&f, &data, 10, 256, Transform::Vandermonde).is_ok());
+
+	let shares = split(&f, &data, 3, 5, Transform::Cauchy).unwrap();
+	assert_eq!(combine(&f, &shares[..2]).err(), Some(IdaError::NotEnoughShares));
+	assert_eq!(combine::<crate::good::F8_0x11b>(&f, &[]).err(), Some(IdaError::NotEnoughShares));
+	let dup = vec![shares[0].clone(), shares[1].clone(), shares[0].clone()];
+	assert_eq!(combine(&f, &dup).err(), Some(IdaError::DuplicateShare));
+	let mut bad = shares.clone();
+	bad[1].data.pop();
+	assert_eq!(combine(&f, &bad).err(), Some(IdaError::InconsistentShares));
+	let mut bad = shares;
+	bad[2].transform = Transform::Vandermonde;
+	assert_eq!(combine(&f, &bad).err(), Some(IdaError::InconsistentShares));
+    }
+}
@@ -0,0 +1,516 @@
+//! # Linear feedback shift registers and sequence generators
+//!
+//! This module has two kinds of LFSR:
+//!
+//! * binary ones ([GaloisLfsr], [FibonacciLfsr]) with registers of up
+//!   to 64 bits, which can be stepped one bit or one byte at a time;
+//!
+//! * ones over GF(2<sup>x</sup>) ([FieldGaloisLfsr],
+//!   [FieldFibonacciLfsr]), which output one field element per step
+//!
+//! along with generators for m-sequences, Gold and Kasami code
+//! families, and self-synchronising scramblers.
+//!
+//! # Binary polynomials
+//!
+//! Following the `full`/`compact` convention used for field
+//! polynomials, a binary feedback polynomial
+//! p(x) = x<sup>n</sup> + c<sub>n-1</sub>x<sup>n-1</sup> + ... +
+//! c<sub>0</sub> is passed in as its degree n plus the `compact` value
+//! with the x<sup>n</sup> bit removed. This allows for degree 64
+//! polynomials. For example, x<sup>4</sup> + x + 1 is `(4, 0x3)`.
+//!
+//! ```rust
+//! use guff::lfsr::{GaloisLfsr, m_sequence};
+//!
+//! // x^16 + x^5 + x^3 + x + 1 (0x1002b) is primitive
+//! let mut a = GaloisLfsr::new(16, 0x2b, 1);
+//! let mut b = GaloisLfsr::new(16, 0x2b, 1);
+//! let byte = a.next_byte();
+//! let mut bits = 0;
+//! for _ in 0..8 { bits = (bits << 1) | b.next_bit() }
+//! assert_eq!(byte, bits);
+//!
+//! let m = m_sequence(4, 0x3);
+//! assert_eq!(m.len(), 15);
+//! assert_eq!(m.iter().filter(|b| **b == 1).count(), 8);
+//! ```
+//!
+//! # Byte-at-a-time stepping
+//!
+//! [GaloisLfsr::next_byte] uses the same tables as the bytewise
+//! modular reduction in [crate::good]: shifting the register
+//! left by 8 bits is multiplication by x<sup>8</sup> modulo p(x), and
+//! the bits shifted out (along with the feedback they cause) depend
+//! only on the top byte, so both can be looked up in 256-entry
+//! tables.
+
+use crate::GaloisField;
+use crate::good::bytewise_reduce_table;
+use crate::poly::Poly;
+use crate::binpoly::BinaryPoly;
+
+use num::Zero;
+use std::collections::VecDeque;
+
+// all-ones mask for an n-bit register
+fn reg_mask(degree : u32) -> u64 {
+    assert!(degree > 0 && degree <= 64, "register size must be 1..=64 bits");
+    u64::MAX >> (64 - degree)
+}
+
+fn parity(x : u64) -> u8 {
+    (x.count_ones() & 1) as u8
+}
+
+/// Binary Galois (internal XOR, or "one-to-many") LFSR
+///
+/// Each step multiplies the register by x modulo p(x) and outputs the
+/// bit shifted out of the top.
+pub struct GaloisLfsr {
+    degree : u32,
+    poly   : u64,
+    mask   : u64,
+    state  : u64,
+    // indexed by top byte of the register (only if degree >= 8)
+    out_table    : Vec<u8>,
+    reduce_table : Vec<u64>,
+}
+
+impl GaloisLfsr {
+
+    /// New register with feedback polynomial (degree, compact) and
+    /// initial state
+    pub fn new(degree : u32, compact : u64, state : u64) -> GaloisLfsr {
+	let mask = reg_mask(degree);
+	let mut lfsr = GaloisLfsr {
+	    degree, poly : compact & mask, mask, state : state & mask,
+	    out_table : Vec::new(), reduce_table : Vec::new(),
+	};
+	if degree >= 8 {
+	    // the outputs are the quotient bits
+	    let (out, reduce) = bytewise_reduce_table(degree, compact);
+	    lfsr.out_table = out;
+	    lfsr.reduce_table = reduce;
+	}
+	lfsr
+    }
+
+    /// Current register contents
+    pub fn state(&self) -> u64 { self.state }
+
+    /// Output one bit (0 or 1) and step the register
+    pub fn next_bit(&mut self) -> u8 {
+	let out = (self.state >> (self.degree - 1)) as u8 & 1;
+	self.state = (self.state << 1) & self.mask;
+	if out == 1 { self.state ^= self.poly }
+	out
+    }
+
+    /// Output eight bits (first bit in the most significant position)
+    /// and step the register eight times
+    pub fn next_byte(&mut self) -> u8 {
+	if self.degree < 8 {
+	    let mut out = 0u8;
+	    for _ in 0..8 { out = (out << 1) | self.next_bit() }
+	    return out
+	}
+	let top = (self.state >> (self.degree - 8)) as usize;
+	// shifting a u64 by 64 is not allowed
+	let shifted = if self.degree == 8 { 0 } else {
+	    (self.state << 8) & self.mask
+	};
+	self.state = shifted ^ self.reduce_table[top];
+	self.out_table[top]
+    }
+}
+
+/// Binary Fibonacci (external XOR, or "many-to-one") LFSR
+///
+/// With feedback polynomial p(x) as above, the output sequence obeys
+/// s<sub>k+n</sub> = c<sub>n-1</sub>s<sub>k+n-1</sub> + ... +
+/// c<sub>0</sub>s<sub>k</sub>. The register holds the next n outputs,
+/// with the next one in bit 0.
+pub struct FibonacciLfsr {
+    degree : u32,
+    taps   : u64,
+    mask   : u64,
+    state  : u64,
+}
+
+impl FibonacciLfsr {
+
+    /// New register with feedback polynomial (degree, compact) and
+    /// initial state (the first n outputs, first in bit 0)
+    pub fn new(degree : u32, compact : u64, state : u64) -> FibonacciLfsr {
+	let mask = reg_mask(degree);
+	FibonacciLfsr { degree, taps : compact & mask, mask, state : state & mask }
+    }
+
+    /// Current register contents
+    pub fn state(&self) -> u64 { self.state }
+
+    /// Output one bit (0 or 1) and step the register
+    pub fn next_bit(&mut self) -> u8 {
+	let out = (self.state & 1) as u8;
+	let fb = parity(self.state & self.taps) as u64;
+	self.state = (self.state >> 1) | (fb << (self.degree - 1));
+	self.state &= self.mask;
+	out
+    }
+
+    /// Output eight bits (first bit in the most significant position)
+    /// and step the register eight times
+    pub fn next_byte(&mut self) -> u8 {
+	let mut out = 0u8;
+	for _ in 0..8 { out = (out << 1) | self.next_bit() }
+	out
+    }
+}
+
+/// One period of the m-sequence for a primitive polynomial
+///
+/// Returns 2<sup>n</sup> - 1 bits (as 0/1 values) output by a
+/// [FibonacciLfsr] started in state 1. Panics if the polynomial is
+/// not primitive.
+pub fn m_sequence(degree : u32, compact : u64) -> Vec<u8> {
+    assert!(degree <= 32, "m-sequence would be too long");
+    let p = BinaryPoly::from_u64(compact & reg_mask(degree))
+	.add(&BinaryPoly::x_pow(degree as usize));
+    assert!(p.is_primitive(), "feedback polynomial is not primitive");
+    let mut lfsr = FibonacciLfsr::new(degree, compact, 1);
+    (0..reg_mask(degree)).map(|_| lfsr.next_bit()).collect()
+}
+
+/// Gold code family from a preferred pair of primitive polynomials
+///
+/// Returns the 2<sup>n</sup> + 1 sequences u, v and u ⊕ T<sup>k</sup>v
+/// for each cyclic shift k. It is up to the caller to supply a
+/// preferred pair (eg, for n = 5, `(5, 0x05)` and `(5, 0x1d)`); with
+/// one, periodic cross-correlations take only the values -1, -t and
+/// t - 2, where t = 2<sup>⌊(n+2)/2⌋</sup> + 1.
+pub fn gold_codes(degree : u32, compact_a : u64, compact_b : u64) -> Vec<Vec<u8>> {
+    let u = m_sequence(degree, compact_a);
+    let v = m_sequence(degree, compact_b);
+    let n = u.len();
+    let mut codes = Vec::with_capacity(n + 2);
+    for k in 0..n {
+	codes.push((0..n).map(|i| u[i] ^ v[(i + k) % n]).collect());
+    }
+    codes.push(u);
+    codes.push(v);
+    codes
+}
+
+/// Small Kasami code family
+///
+/// For even n, decimating an m-sequence u by 2<sup>n/2</sup> + 1
+/// gives a shorter m-sequence w. The family consists of u and
+/// u ⊕ T<sup>k</sup>w for each distinct shift of w, 2<sup>n/2</sup>
+/// sequences in all, with periodic cross-correlations in {-1,
+/// -(2<sup>n/2</sup> + 1), 2<sup>n/2</sup> - 1}.
+pub fn kasami_codes(degree : u32, compact : u64) -> Vec<Vec<u8>> {
+    assert!(degree % 2 == 0, "Kasami codes need even degree");
+    let u = m_sequence(degree, compact);
+    let n = u.len();
+    let q = (1usize << (degree / 2)) + 1;
+    let w : Vec<u8> = (0..n).map(|i| u[(i * q) % n]).collect();
+    let short = (1usize << (degree / 2)) - 1;
+    let mut codes = vec![u.clone()];
+    for k in 0..short {
+	codes.push((0..n).map(|i| u[i] ^ w[(i + k) % n]).collect());
+    }
+    codes
+}
+
+/// Self-synchronising (multiplicative) scrambler and descrambler
+///
+/// For polynomial 1 + c<sub>1</sub>x + ... + x<sup>n</sup>, the
+/// scrambler outputs y<sub>k</sub> = d<sub>k</sub> +
+/// c<sub>1</sub>y<sub>k-1</sub> + ... + y<sub>k-n</sub>, and the
+/// descrambler inverts this using the received scrambled bits only,
+/// so it falls into step after n bits without any need to share
+/// state. Polynomials are specified in the usual (degree, compact)
+/// form, eg x<sup>58</sup> + x<sup>39</sup> + 1 (64b/66b Ethernet) is
+/// `(58, (1 << 39) | 1)`.
+///
+/// A single instance should be used to either scramble or descramble,
+/// not both. Bytes are processed most significant bit first.
+pub struct Scrambler {
+    taps  : u64,
+    mask  : u64,
+    state : u64,
+}
+
+impl Scrambler {
+
+    /// New (de)scrambler with all-zero initial state
+    pub fn new(degree : u32, compact : u64) -> Scrambler {
+	let mask = reg_mask(degree);
+	// bit i-1 of taps is the coefficient of x^i
+	let taps = ((compact & mask) >> 1) | (1 << (degree - 1));
+	Scrambler { taps, mask, state : 0 }
+    }
+
+    fn feedback(&self) -> u8 {
+	parity(self.state & self.taps)
+    }
+
+    fn shift_in(&mut self, y : u8) {
+	self.state = ((self.state << 1) | y as u64) & self.mask;
+    }
+
+    /// Scramble one bit
+    pub fn scramble_bit(&mut self, d : u8) -> u8 {
+	let y = (d & 1) ^ self.feedback();
+	self.shift_in(y);
+	y
+    }
+
+    /// Descramble one bit
+    pub fn descramble_bit(&mut self, y : u8) -> u8 {
+	let d = (y & 1) ^ self.feedback();
+	self.shift_in(y & 1);
+	d
+    }
+
+    /// Scramble a buffer of bytes in place
+    pub fn scramble(&mut self, data : &mut [u8]) {
+	for byte in data.iter_mut() {
+	    let mut out = 0u8;
+	    for bit in (0..8).rev() {
+		out = (out << 1) | self.scramble_bit(*byte >> bit);
+	    }
+	    *byte = out;
+	}
+    }
+
+    /// Descramble a buffer of bytes in place
+    pub fn descramble(&mut self, data : &mut [u8]) {
+	for byte in data.iter_mut() {
+	    let mut out = 0u8;
+	    for bit in (0..8).rev() {
+		out = (out << 1) | self.descramble_bit(*byte >> bit);
+	    }
+	    *byte = out;
+	}
+    }
+}
+
+/// Fibonacci LFSR over GF(2<sup>x</sup>)
+///
+/// Uses the connection polynomial convention of [crate::bm], so it
+/// can directly run an LFSR found by Berlekamp–Massey: with
+/// C(x) = 1 + c<sub>1</sub>x + ... + c<sub>L</sub>x<sup>L</sup>, the
+/// output satisfies s<sub>k</sub> = c<sub>1</sub>s<sub>k-1</sub> +
+/// ... + c<sub>L</sub>s<sub>k-L</sub>.
+pub struct FieldFibonacciLfsr<G> where G : GaloisField {
+    conn  : Poly<G>,
+    // next L outputs, next one at the front
+    state : VecDeque<G::E>,
+}
+
+impl<G> FieldFibonacciLfsr<G> where G : GaloisField {
+
+    /// New register from connection polynomial and the first L
+    /// outputs
+    ///
+    /// C(x) may have degree less than L, as [crate::bm] returns when
+    /// the last few taps are zero; it is padded out to L + 1
+    /// coefficients.
+    pub fn new(conn : &[G::E], init : &[G::E]) -> FieldFibonacciLfsr<G> {
+	assert!(!conn.is_empty() && conn.len() <= init.len() + 1,
+		"need one initial value per register stage");
+	let mut conn = conn.to_vec();
+	conn.resize(init.len() + 1, G::E::zero());
+	FieldFibonacciLfsr {
+	    conn,
+	    state : init.iter().cloned().collect(),
+	}
+    }
+
+    /// Output one element and step the register
+    pub fn next(&mut self, f : &G) -> G::E {
+	let len = self.state.len();
+	let mut fb = G::E::zero();
+	for (i, c) in self.conn.iter().enumerate().skip(1) {
+	    fb = fb ^ f.mul(*c, self.state[len - i]);
+	}
+	self.state.push_back(fb);
+	self.state.pop_front().unwrap()
+    }
+}
+
+/// Galois LFSR over GF(2<sup>x</sup>)
+///
+/// The register holds a polynomial of degree less than L, and each
+/// step multiplies it by x modulo the monic characteristic
+/// polynomial p(x) (in [crate::poly] format), outputting the
+/// coefficient shifted out of the top.
+pub struct FieldGaloisLfsr<G> where G : GaloisField {
+    poly  : Poly<G>,
+    state : Vec<G::E>,
+}
+
+impl<G> FieldGaloisLfsr<G> where G : GaloisField {
+
+    /// New register from monic characteristic polynomial and initial
+    /// register contents (lowest power first)
+    pub fn new(poly : &[G::E], init : &[G::E]) -> FieldGaloisLfsr<G> {
+	assert_eq!(poly.len(), init.len() + 1,
+		   "need one initial value per register stage");
+	FieldGaloisLfsr { poly : poly.to_vec(), state : init.to_vec() }
+    }
+
+    /// Current register contents
+    pub fn state(&self) -> &[G::E] { &self.state }
+
+    /// Output one element and step the register
+    pub fn next(&mut self, f : &G) -> G::E {
+	let len = self.state.len();
+	let out = self.state[len - 1];
+	for i in (1..len).rev() {
+	    self.state[i] = self.state[i - 1] ^ f.mul(out, self.poly[i]);
+	}
+	self.state[0] = f.mul(out, self.poly[0]);
+	out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::new_gf4;
+    use crate::good::new_gf8_0x11b;
+    use crate::bm::berlekamp_massey;
+
+    // periodic correlation of two ±1 sequences
+    fn correlation(a : &[u8], b : &[u8], shift : usize) -> i64 {
+	let n = a.len();
+	(0..n).map(|i| if a[i] == b[(i + shift) % n] { 1 } else { -1 }).sum()
+    }
+
+    #[test]
+    fn galois_byte_step_matches_bits() {
+	for (degree, poly) in [(16u32, 0x2bu64), (8, 0x1d), (9, 0x11),
+			       (64, 0x1b), (5, 0x05)].iter() {
+	    let mut a = GaloisLfsr::new(*degree, *poly, 0xace1);
+	    let mut b = GaloisLfsr::new(*degree, *poly, 0xace1);
+	    for _ in 0..100 {
+		let mut bits = 0;
+		for _ in 0..8 { bits = (bits << 1) | b.next_bit() }
+		assert_eq!(a.next_byte(), bits);
+		assert_eq!(a.state(), b.state());
+	    }
+	}
+    }
+
+    #[test]
+    fn galois_period_is_maximal() {
+	let mut lfsr = GaloisLfsr::new(8, 0x1d, 1);
+	let mut period = 0;
+	loop {
+	    lfsr.next_bit();
+	    period += 1;
+	    if lfsr.state() == 1 { break }
+	}
+	assert_eq!(period, 255);
+    }
+
+    #[test]
+    fn galois_and_fibonacci_agree() {
+	// same polynomial gives the same sequence, up to a shift
+	let mut g = GaloisLfsr::new(5, 0x05, 1);
+	let gs : Vec<u8> = (0..62).map(|_| g.next_bit()).collect();
+	let fs = m_sequence(5, 0x05);
+	assert!((0..31).any(|k| (0..31).all(|i| gs[i + k] == fs[i])));
+    }
+
+    #[test]
+    fn m_sequence_properties() {
+	let m = m_sequence(7, 0x03);
+	assert_eq!(m.len(), 127);
+	assert_eq!(m.iter().filter(|b| **b == 1).count(), 64);
+	// two-valued autocorrelation
+	for k in 1..127 { assert_eq!(correlation(&m, &m, k), -1) }
+	// linear complexity equals degree
+	let f = new_gf4(19, 3);
+	assert_eq!(berlekamp_massey(&f, &m).1, 7);
+    }
+
+    #[test]
+    fn gold_codes_three_valued() {
+	let codes = gold_codes(5, 0x05, 0x1d);
+	assert_eq!(codes.len(), 33);
+	for a in codes.iter().take(6) {
+	    for b in codes.iter().skip(10).take(6) {
+		for k in 0..31 {
+		    let c = correlation(a, b, k);
+		    assert!(c == -1 || c == -9 || c == 7, "c = {}", c);
+		}
+	    }
+	}
+    }
+
+    #[test]
+    fn kasami_codes_three_valued() {
+	let codes = kasami_codes(6, 0x03);
+	assert_eq!(codes.len(), 8);
+	for (i, a) in codes.iter().enumerate() {
+	    for b in codes.iter().skip(i + 1) {
+		for k in 0..63 {
+		    let c = correlation(a, b, k);
+		    assert!(c == -1 || c == -9 || c == 7, "c = {}", c);
+		}
+	    }
+	}
+    }
+
+    #[test]
+    fn scrambler_round_trip_and_self_sync() {
+	let data : Vec<u8> = (0..200u32).map(|i| (i * 7) as u8).collect();
+	let mut scrambled = data.clone();
+	Scrambler::new(58, (1 << 39) | 1).scramble(&mut scrambled);
+	assert_ne!(scrambled, data);
+
+	let mut out = scrambled.clone();
+	Scrambler::new(58, (1 << 39) | 1).descramble(&mut out);
+	assert_eq!(out, data);
+
+	// descrambler with a different starting state syncs up after
+	// 58 bits (8 bytes)
+	let mut d = Scrambler::new(58, (1 << 39) | 1);
+	d.state = 0x0123_4567_89ab_cdef & d.mask;
+	let mut out = scrambled.clone();
+	d.descramble(&mut out);
+	assert_eq!(out[8..], data[8..]);
+    }
+
+    #[test]
+    fn field_lfsrs() {
+	let f = new_gf8_0x11b();
+	// Galois LFSR output satisfies recurrence with connection
+	// polynomial equal to the reciprocal of p(x)
+	let p = vec![0x53u8, 7, 0xca, 1];
+	let mut g = FieldGaloisLfsr::new(&p, &[1, 2, 3]);
+	let s : Vec<u8> = (0..20).map(|_| g.next(&f)).collect();
+	let (conn, l) = berlekamp_massey(&f, &s);
+	assert_eq!(l, 3);
+	assert_eq!(conn, vec![1, 0xca, 7, 0x53]);
+
+	// and Fibonacci LFSR with that connection poly regenerates it
+	let mut fib = FieldFibonacciLfsr::new(&conn, &s[..3]);
+	let t : Vec<u8> = (0..20).map(|_| fib.next(&f)).collect();
+	assert_eq!(s, t);
+
+	// s_k = 3 s_(k-1) from k = 3 on: L is 3 but C(x) = 1 + 3x
+	let mut s = vec![0u8, 0, 1];
+	for _ in 0..17 { let last = s[s.len() - 1]; s.push(f.mul(last, 3)) }
+	let (conn, l) = berlekamp_massey(&f, &s);
+	assert_eq!((conn.clone(), l), (vec![1, 3], 3));
+	let mut fib = FieldFibonacciLfsr::new(&conn, &s[..l]);
+	let t : Vec<u8> = (0..20).map(|_| fib.next(&f)).collect();
+	assert_eq!(s, t);
+    }
+}
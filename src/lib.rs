@@ -8,14 +8,14 @@
 //! The general outline for using this library is:
 //!
 //! * decide what "class" of field you want to use (GF(2<sup>8</sup>),
-//! GF(2<sup>16</sup>), …);
+//!   GF(2<sup>16</sup>), …);
 //! 
 //! * decide if you want to use one of the optimised adaptations or
-//! are happy with the default, generic code;
+//!   are happy with the default, generic code;
 //!
 //! * create a new field object (we can call `f`) of that class with
-//! your chosen field polynomial (aka "irreducible polynomial") by
-//! calling the appropriate constructor;
+//!   your chosen field polynomial (aka "irreducible polynomial") by
+//!   calling the appropriate constructor;
 //!
 //! * use that object to do maths in that field: eg, `result =
 //! f.mul(a,b)`
@@ -69,6 +69,9 @@
 //! * scaling a vector by a constant
 //! * fused multiply add (scale and sum by a pair of constants across
 //!   vector)
+//! * adding a scaled vector to another
+//! * applying a matrix of constants to a set of vectors
+//!   (`matrix_apply`, the core of erasure coding)
 //! 
 //! These are all implemented using slices of the appropriate
 //! [ElementStore] type. Where necessary, if a vector (slice) type is
@@ -85,7 +88,7 @@
 //! \* The crate name is deliberately hyperbolic:
 //!
 //! > Noun *guff* - unacceptable behavior (especially ludicrously
-//!   false statements)
+//! > false statements)
 
 // I want to emit assembly for these
 pub fn _monomorph() {
@@ -111,8 +114,61 @@ use num::{PrimInt,One,Zero};
 // I hate having to use this:
 use std::convert::{TryInto};
 
+// Number of elements per block in matrix_apply. Aim is for a block
+// of every input plus one output to fit in L2 cache for typical k.
+const APPLY_BLOCK : usize = 4096;
+
+// Shared blocking loop for matrix_apply implementations. `kernel(i,
+// j, dest, src)` must add coefficient (i,j) times src into dest.
+pub(crate) fn apply_blocked<E, K>(inputs  : &[&[E]],
+				  outputs : &mut [&mut [E]],
+				  mut kernel : K)
+where E : ElementStore, K : FnMut(usize, usize, &mut [E], &[E])
+{
+    // nothing to write; don't insist the inputs have length 0
+    if outputs.is_empty() { return }
+    let len = outputs[0].len();
+    assert!(inputs.iter().all(|s| s.len() == len), "slice lengths differ");
+    assert!(outputs.iter().all(|s| s.len() == len), "slice lengths differ");
+    let mut start = 0;
+    while start < len {
+	let end = len.min(start + APPLY_BLOCK);
+	for (i, out) in outputs.iter_mut().enumerate() {
+	    let dest = &mut out[start..end];
+	    dest.fill(E::zero());
+	    for (j, src) in inputs.iter().enumerate() {
+		kernel(i, j, dest, &src[start..end]);
+	    }
+	}
+	start = end;
+    }
+}
+
 pub mod good;
 
+pub mod poly;
+pub mod roots;
+pub mod factor;
+pub mod fft;
+pub mod binpoly;
+pub mod bm;
+pub mod lfsr;
+pub mod euclid;
+pub mod matrix;
+pub mod online;
+pub mod bitmatrix;
+pub mod sparse;
+pub mod bytes;
+pub mod mds;
+pub mod ida;
+pub mod rs;
+pub mod shamir;
+pub mod raid6;
+pub mod rlnc;
+pub mod sliding;
+pub mod lrc;
+pub mod bch;
+
 // I think that if I want to keep a flat directory structure, while
 // still supporting an arbitrarily deep module tree, I would have to
 // create `tables.rs` and have a `pub mod mull` line within it.
@@ -160,10 +216,10 @@ where Self::EE : From<Self::E> {
     /// should be U16. Used for:
     ///
     /// * storing/returning field polynomial, which is always larger
-    /// than the largest field element
+    ///   than the largest field element
     ///
     /// * storing the result of a non-modular (overflowing) multiply
-    /// of two field elements
+    ///   of two field elements
     type EE : ElementStore; // where Self::EE : From<Self::E>;
     /// As EE, but signed. Used internally to implement some lookup
     /// tables.
@@ -260,7 +316,8 @@ where Self::EE : From<Self::E> {
 	// unroll first loop iteration (knowing initial i >= 0)
 	// rustc can't determine that i is always positive here,
 	// though, so we have to try_into()
-	let fixup = if self.order() == 4 { 4 } else { 0 };
+	// unused high bits of the storage type (eg, GF(16) in a u8)
+	let fixup = zero.leading_zeros() - self.order() as u32;
 	let mut i : u32 = 1 + v.leading_zeros() - fixup;
 	u = (u ^ v << i as usize) & mask;
 	z = (z ^ g << i as usize) & mask;
@@ -341,7 +398,7 @@ where Self::EE : From<Self::E> {
 
     #[doc(hidden)]
     /// Long Polynomial multiplication, *not* modulo the field polynomial
-
+    //
     // Note the extra type constraint for conversion E -> EE.
     // Changing this to an associated function since it doesn't depend
     // on a poly.
@@ -365,7 +422,7 @@ where Self::EE : From<Self::E> {
 
     #[doc(hidden)]
     /// Bitwise modular reduction from EE to E
-
+    //
     // Here we have the reverse problem of converting an EE to E
     // Changing this to an associated function.
     fn mod_reduce(mut a : Self::EE, mut poly : Self::EE) -> Self::E
@@ -455,6 +512,35 @@ where Self::EE : From<Self::E> {
 	}
     }
 
+    /// dest += a · other (the row operation used in elimination)
+    fn vec_add_scaled_vec_in_place(&self,
+				   dest  : &mut [Self::E],
+				   other : &[Self::E],
+				   a     : Self::E) {
+	assert_eq!(dest.len(), other.len());
+	if a == Self::E::zero() { return }
+	for (d,o) in dest.iter_mut().zip(other) {
+	    *d = *d ^ self.mul(*o, a)
+	}
+    }
+
+    /// Apply an m×k coding matrix to k input slices, giving m outputs
+    ///
+    /// Computes `outputs[i]` = Σ<sub>j</sub> `coeffs[i·k + j]` · `inputs[j]`
+    /// (coefficients in row-major order). All slices must be the same
+    /// length. Work is done in cache-sized blocks, so that each block
+    /// of input is reused for every output while it is still hot.
+    fn matrix_apply(&self,
+		    coeffs  : &[Self::E],
+		    inputs  : &[&[Self::E]],
+		    outputs : &mut [&mut [Self::E]]) {
+	let k = inputs.len();
+	assert_eq!(coeffs.len(), k * outputs.len(), "need m×k coefficients");
+	apply_blocked(inputs, outputs, |i, j, dest, src| {
+	    self.vec_add_scaled_vec_in_place(dest, src, coeffs[i * k + j])
+	});
+    }
+
 
     // Other accessors provide syntactic sugar
     
@@ -583,6 +669,56 @@ pub fn new_gf32(full : u64, compact : u32) -> F32  {
     F32 { full, compact }
 }
 
+/// A type implementing (default) maths in GF(2<sup>m</sup>) for any
+/// 2 ≤ m ≤ 16
+///
+/// The natural sizes above cover most uses, but some codes (eg,
+/// [bch]) want a field of some other size. Since [GaloisField::ORDER]
+/// is an associated constant, m is a const parameter. Elements are
+/// stored in a u16 whatever the size. Unlike the types above, the
+/// fields are private, so that [new_gf2m] can check m and the
+/// polynomial.
+///
+/// ```rust
+/// use guff::{GaloisField, F2m, new_gf2m};
+///
+/// // GF(2**10) with primitive polynomial x**10 + x**3 + 1
+/// let f : F2m<10> = new_gf2m(0x409, 0x9);
+/// assert_eq!(f.order(), 10);
+/// assert_eq!(f.mul(0x200, 2), 0x9);
+/// assert_eq!(f.mul(0x123, f.inv(0x123)), 1);
+/// ```
+#[derive(Debug)]
+pub struct F2m<const M : u16> { full : u32, compact : u16 }
+
+impl<const M : u16> GaloisField for F2m<M> {
+    type E = u16;
+    type EE = u32;
+    type SEE = i32;
+
+    const ORDER      : u16 = M;
+    const POLY_BIT   : u32 = 1 << M;
+    const FIELD_MASK : u16 = ((1u32 << M) - 1) as u16;
+    const HIGH_BIT   : u16 = 1 << (M - 1);
+
+    // the two required methods (everything else is default)
+    fn poly(&self)      -> u16 { self.compact }
+    fn full_poly(&self) -> u32 { self.full }
+}
+
+// Constructor for GF(2<sup>m</sup>)
+/// Create a new GF(2<sup>m</sup>) field with a supplied field
+/// polynomial (using the default implementation)
+///
+/// Panics unless 2 ≤ m ≤ 16 and the polynomial has degree m.
+pub fn new_gf2m<const M : u16>(full : u32, compact : u16) -> F2m<M> {
+    assert!((2..=16).contains(&M), "field size must be from 2 to 16 bits");
+    assert_eq!(full >> M, 1, "field polynomial must have degree m");
+    assert_eq!(full as u16 & F2m::<M>::FIELD_MASK, compact,
+	       "compact polynomial must be full polynomial without high bit");
+    F2m { full, compact }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -1036,4 +1172,47 @@ mod tests {
 	use crate::tables::mull;
 	assert_eq!(mull::MULL.len(), 4096);
     }
+
+    #[test]
+    fn matrix_apply_with_no_outputs() {
+	let f = new_gf8(0x11b, 0x1b);
+	let a = [1u8, 2, 3];
+	f.matrix_apply(&[], &[&a], &mut []);
+	crate::good::new_gf8_0x11b().matrix_apply(&[], &[&a], &mut []);
+    }
+
+    #[test]
+    fn gf2m_agrees_with_natural_sizes() {
+	let (f4, m4) = (new_gf4(19, 3), new_gf2m::<4>(19, 3));
+	let (f8, m8) = (new_gf8(0x11d, 0x1d), new_gf2m::<8>(0x11d, 0x1d));
+	for a in 0..256u16 {
+	    if a < 16 {
+		assert_eq!(m4.inv(a), f4.inv(a as u8) as u16);
+		for b in 0..16u16 { assert_eq!(m4.mul(a, b), f4.mul(a as u8, b as u8) as u16) }
+	    }
+	    assert_eq!(m8.inv(a), f8.inv(a as u8) as u16);
+	    for b in (0..256u16).step_by(5) {
+		assert_eq!(m8.mul(a, b), f8.mul(a as u8, b as u8) as u16)
+	    }
+	}
+	let (f16, m16) = (new_gf16(0x1002b, 0x2b), new_gf2m::<16>(0x1002b, 0x2b));
+	for a in (1..=65535u16).step_by(997) {
+	    assert_eq!(m16.inv(a), f16.inv(a));
+	    assert_eq!(m16.mul(a, 0x1234), f16.mul(a, 0x1234));
+	}
+    }
+
+    #[test]
+    fn gf2m_odd_sizes() {
+	let f = new_gf2m::<13>(0x201b, 0x1b);
+	for a in 1..8192u16 {
+	    let i = f.inv(a);
+	    assert!(i < 8192);
+	    assert_eq!(f.mul(a, i), 1);
+	}
+	assert_eq!(f.pow(2, 8191), 1);
+	assert_eq!(f.pow(3, 100), (0..100).fold(1, |acc, _| f.mul(acc, 3)));
+	let f = new_gf2m::<2>(7, 3);
+	assert_eq!((f.mul(2, 2), f.mul(2, 3), f.inv(2)), (3, 1, 3));
+    }
 }
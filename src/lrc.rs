@@ -0,0 +1,343 @@
+//! # Locally repairable codes
+//!
+//! With a Reed-Solomon code, rebuilding even a single lost shard
+//! means reading k others. An LRC(k, l, r) code, as used in Windows
+//! Azure Storage, splits the k data shards into l groups of k/l and
+//! adds a *local parity* to each group (the sum of its data shards),
+//! plus r *global parities* over all the data. A single failure
+//! within a group is then repaired from just the k/l other shards of
+//! that group, while the global parities still cover heavier losses.
+//!
+//! Shards are laid out as the k data shards, then the l local
+//! parities, then the r global parities. Following the Azure paper,
+//! global parity j (for j = 1 ... r) has coefficient
+//! a<sub>i</sub><sup>j</sup> for data shard i, where the
+//! a<sub>i</sub> are distinct non-zero field elements (here, powers
+//! of a generator).
+//!
+//! ```rust
+//! use guff::good::new_gf8_0x11b;
+//! use guff::lrc::Lrc;
+//!
+//! let f = new_gf8_0x11b();
+//! let lrc = Lrc::new(&f, 6, 2, 2).unwrap();
+//! assert_eq!(lrc.total_shards(), 10);
+//!
+//! let mut shards : Vec<Vec<u8>> = (0..10).map(|i| vec![i as u8; 16]).collect();
+//! lrc.encode_shards(&f, &mut shards).unwrap();
+//!
+//! // one lost shard: repaired from its group of three
+//! let mut damaged : Vec<Option<Vec<u8>>> = shards.iter().cloned().map(Some).collect();
+//! damaged[4] = None;
+//! assert_eq!(lrc.repair_reads(&[4]), Some(vec![3, 5, 7]));
+//! lrc.reconstruct(&f, &mut damaged).unwrap();
+//! assert_eq!(damaged[4].as_ref(), Some(&shards[4]));
+//!
+//! // any three failures can be recovered, and some four
+//! assert!(lrc.is_recoverable(&f, &[0, 1, 2]));
+//! assert!(lrc.is_recoverable(&f, &[0, 1, 3, 4]));
+//! assert!(!lrc.is_recoverable(&f, &[0, 1, 2, 6]));
+//! ```
+
+use crate::GaloisField;
+use crate::matrix::Matrix;
+use crate::roots::find_generator;
+
+use num::{Zero,One};
+use std::fmt;
+
+/// Errors from [Lrc] operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LrcError {
+    /// Need k, l, r ≥ 1 with l dividing k, and k < field size
+    BadParameters,
+    /// Number of shards passed in doesn't match the code
+    WrongShardCount,
+    /// Shards are not all the same length
+    ShardSizeMismatch,
+    /// The missing shards can't be rebuilt from those present
+    Unrecoverable,
+}
+
+impl fmt::Display for LrcError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    LrcError::BadParameters     => write!(f, "bad LRC parameters"),
+	    LrcError::WrongShardCount   => write!(f, "wrong number of shards"),
+	    LrcError::ShardSizeMismatch => write!(f, "shards differ in size"),
+	    LrcError::Unrecoverable     => write!(f, "failure pattern is unrecoverable"),
+	}
+    }
+}
+
+impl std::error::Error for LrcError {}
+
+/// An Azure-style LRC(k, l, r) code
+pub struct Lrc<G> where G : GaloisField {
+    k         : usize,
+    l         : usize,
+    r         : usize,
+    // full n×k generator: identity, then local rows, then global rows
+    generator : Matrix<G>,
+}
+
+impl<G> Lrc<G> where G : GaloisField {
+
+    /// New code with k data shards in l local groups, and r global
+    /// parities
+    pub fn new(f : &G, k : usize, l : usize, r : usize) -> Result<Lrc<G>, LrcError> {
+	if k == 0 || l == 0 || r == 0 || k % l != 0 { return Err(LrcError::BadParameters) }
+	if k as u64 >= 1u64 << G::ORDER { return Err(LrcError::BadParameters) }
+	let one = G::E::one();
+	let group = k / l;
+	let mut generator = Matrix::zero(f, k + l + r, k);
+	for i in 0..k {
+	    generator.row_mut(i)[i] = one;
+	    generator.row_mut(k + i / group)[i] = one;
+	}
+	let g = find_generator(f);
+	let mut a = one;
+	for i in 0..k {
+	    let mut c = a;
+	    for j in 0..r {
+		generator.row_mut(k + l + j)[i] = c;
+		c = f.mul(c, a);
+	    }
+	    a = f.mul(a, g);
+	}
+	Ok(Lrc { k, l, r, generator })
+    }
+
+    /// Number of data shards
+    pub fn data_shards(&self) -> usize { self.k }
+
+    /// Number of local groups (and local parities)
+    pub fn local_groups(&self) -> usize { self.l }
+
+    /// Number of global parities
+    pub fn global_parities(&self) -> usize { self.r }
+
+    /// Total number of shards, k + l + r
+    pub fn total_shards(&self) -> usize { self.k + self.l + self.r }
+
+    /// Which local group a shard is in, if any (global parities
+    /// aren't in one)
+    pub fn group_of(&self, shard : usize) -> Option<usize> {
+	if shard < self.k { Some(shard / (self.k / self.l)) }
+	else if shard < self.k + self.l { Some(shard - self.k) }
+	else { None }
+    }
+
+    // data shards of a group, plus its local parity
+    fn group_members(&self, group : usize) -> Vec<usize> {
+	let size = self.k / self.l;
+	let mut m : Vec<usize> = (group * size..(group + 1) * size).collect();
+	m.push(self.k + group);
+	m
+    }
+
+    /// Compute local and global parities in place: the first k
+    /// shards are data and the rest are overwritten
+    pub fn encode_shards(&self, f : &G, shards : &mut [Vec<G::E>]) -> Result<(), LrcError> {
+	if shards.len() != self.total_shards() { return Err(LrcError::WrongShardCount) }
+	let len = shards[0].len();
+	if shards.iter().any(|s| s.len() != len) { return Err(LrcError::ShardSizeMismatch) }
+	let (data, parity) = shards.split_at_mut(self.k);
+	let data : Vec<&[G::E]> = data.iter().map(|s| &s[..]).collect();
+	let mut parity : Vec<&mut [G::E]> = parity.iter_mut().map(|s| &mut s[..]).collect();
+	f.matrix_apply(&self.generator.as_slice()[self.k * self.k..], &data, &mut parity);
+	Ok(())
+    }
+
+    /// Shards to read to rebuild a single lost shard locally
+    ///
+    /// Returns `None` if any of them is also in `missing`, or if the
+    /// first shard is a global parity (which needs all the data).
+    pub fn repair_reads(&self, missing : &[usize]) -> Option<Vec<usize>> {
+	let lost = *missing.first()?;
+	let group = self.group_of(lost)?;
+	let reads : Vec<usize> = self.group_members(group).into_iter().filter(|s| *s != lost).collect();
+	if reads.iter().any(|s| missing.contains(s)) { return None }
+	Some(reads)
+    }
+
+    // independent rows among those present, up to k of them
+    fn independent(&self, f : &G, present : &[usize]) -> Vec<usize> {
+	let rows : Vec<&[G::E]> = present.iter().map(|r| self.generator.row(*r)).collect();
+	// pivot columns of the transpose are independent rows
+	let (_, pivots) = Matrix::from_rows(f, &rows).transpose().rref(f);
+	pivots.into_iter().map(|c| present[c]).collect()
+    }
+
+    /// Whether losing exactly the shards in `missing` is recoverable
+    pub fn is_recoverable(&self, f : &G, missing : &[usize]) -> bool {
+	let present : Vec<usize> = (0..self.total_shards()).filter(|s| !missing.contains(s)).collect();
+	present.len() >= self.k && self.independent(f, &present).len() == self.k
+    }
+
+    /// All recoverable patterns of exactly `failures` lost shards
+    ///
+    /// Each pattern is a sorted list of shard indexes. There are
+    /// n choose `failures` patterns to check, so keep this small.
+    pub fn recoverable_patterns(&self, f : &G, failures : usize) -> Vec<Vec<usize>> {
+	let n = self.total_shards();
+	let mut out = Vec::new();
+	if failures > n { return out }
+	let mut pat : Vec<usize> = (0..failures).collect();
+	loop {
+	    if self.is_recoverable(f, &pat) { out.push(pat.clone()) }
+	    // next combination in lexicographic order
+	    let mut i = failures;
+	    while i > 0 && pat[i - 1] == n - failures + i - 1 { i -= 1 }
+	    if i == 0 { return out }
+	    pat[i - 1] += 1;
+	    for j in i..failures { pat[j] = pat[j - 1] + 1 }
+	}
+    }
+
+    /// Rebuild all missing (`None`) shards
+    ///
+    /// Groups with a single missing shard are repaired locally first,
+    /// which only reads that group. Anything left is then decoded
+    /// globally from k independent shards. Returns whether global
+    /// decoding was needed.
+    pub fn reconstruct(&self, f : &G, shards : &mut [Option<Vec<G::E>>]) -> Result<bool, LrcError> {
+	let n = self.total_shards();
+	if shards.len() != n { return Err(LrcError::WrongShardCount) }
+	let len = match shards.iter().flatten().next() {
+	    Some(s) => s.len(),
+	    None => return Err(LrcError::Unrecoverable)
+	};
+	if shards.iter().flatten().any(|s| s.len() != len) { return Err(LrcError::ShardSizeMismatch) }
+
+	// local repair: the shards of a group (with its parity) sum to zero
+	for group in 0..self.l {
+	    let members = self.group_members(group);
+	    let lost : Vec<usize> = members.iter().cloned().filter(|s| shards[*s].is_none()).collect();
+	    if lost.len() != 1 { continue }
+	    let mut fixed = vec![G::E::zero(); len];
+	    for s in members.iter().filter(|s| **s != lost[0]) {
+		f.vec_add_vec_in_place(&mut fixed, shards[*s].as_ref().unwrap());
+	    }
+	    shards[lost[0]] = Some(fixed);
+	}
+
+	let missing : Vec<usize> = (0..n).filter(|s| shards[*s].is_none()).collect();
+	if missing.is_empty() { return Ok(false) }
+
+	// global decoding of the missing data shards
+	let missing_data : Vec<usize> = missing.iter().cloned().filter(|s| *s < self.k).collect();
+	if !missing_data.is_empty() {
+	    let present : Vec<usize> = (0..n).filter(|s| shards[*s].is_some()).collect();
+	    let use_rows = self.independent(f, &present);
+	    if use_rows.len() < self.k { return Err(LrcError::Unrecoverable) }
+	    let rows : Vec<&[G::E]> = use_rows.iter().map(|r| self.generator.row(*r)).collect();
+	    let inv = Matrix::from_rows(f, &rows).inverse(f).map_err(|_| LrcError::Unrecoverable)?;
+	    let mut coeffs = Vec::with_capacity(missing_data.len() * self.k);
+	    for d in missing_data.iter() { coeffs.extend_from_slice(inv.row(*d)) }
+	    let mut out = vec![vec![G::E::zero(); len]; missing_data.len()];
+	    {
+		let inputs : Vec<&[G::E]> = use_rows.iter().map(|r| &shards[*r].as_ref().unwrap()[..]).collect();
+		let mut outputs : Vec<&mut [G::E]> = out.iter_mut().map(|s| &mut s[..]).collect();
+		f.matrix_apply(&coeffs, &inputs, &mut outputs);
+	    }
+	    for (d, s) in missing_data.iter().zip(out) { shards[*d] = Some(s) }
+	}
+
+	// then re-encode any missing parities
+	let missing_parity : Vec<usize> = missing.into_iter().filter(|s| *s >= self.k).collect();
+	if !missing_parity.is_empty() {
+	    let mut coeffs = Vec::with_capacity(missing_parity.len() * self.k);
+	    for p in missing_parity.iter() { coeffs.extend_from_slice(self.generator.row(*p)) }
+	    let mut out = vec![vec![G::E::zero(); len]; missing_parity.len()];
+	    {
+		let inputs : Vec<&[G::E]> = shards[..self.k].iter().map(|s| &s.as_ref().unwrap()[..]).collect();
+		let mut outputs : Vec<&mut [G::E]> = out.iter_mut().map(|s| &mut s[..]).collect();
+		f.matrix_apply(&coeffs, &inputs, &mut outputs);
+	    }
+	    for (p, s) in missing_parity.iter().zip(out) { shards[*p] = Some(s) }
+	}
+	Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::good::{new_gf8_0x11b, new_gf16_0x1002b};
+    use num::FromPrimitive;
+
+    fn encoded<G : GaloisField>(f : &G, lrc : &Lrc<G>, len : usize) -> Vec<Vec<G::E>> {
+	let mut shards : Vec<Vec<G::E>> = (0..lrc.total_shards()).map(|i| {
+	    (0..len).map(|j| G::E::from_usize((i * 37 + j * 11 + 1) % 256).unwrap()).collect()
+	}).collect();
+	lrc.encode_shards(f, &mut shards).unwrap();
+	shards
+    }
+
+    #[test]
+    fn local_parity_is_group_sum() {
+	let f = new_gf8_0x11b();
+	let lrc = Lrc::new(&f, 6, 2, 2).unwrap();
+	let s = encoded(&f, &lrc, 8);
+	assert!((0..8).all(|j| s[6][j] == s[0][j] ^ s[1][j] ^ s[2][j]));
+	assert!((0..8).all(|j| s[7][j] == s[3][j] ^ s[4][j] ^ s[5][j]));
+	assert_eq!(lrc.group_of(7), Some(1));
+	assert_eq!(lrc.group_of(8), None);
+	assert_eq!(lrc.repair_reads(&[8]), None);
+	assert_eq!(lrc.repair_reads(&[0, 1]), None);
+    }
+
+    #[test]
+    fn single_failures_are_local() {
+	let f = new_gf8_0x11b();
+	let lrc = Lrc::new(&f, 12, 3, 2).unwrap();
+	let good = encoded(&f, &lrc, 20);
+	for lost in 0..lrc.total_shards() {
+	    let mut s : Vec<Option<Vec<u8>>> = good.iter().cloned().map(Some).collect();
+	    s[lost] = None;
+	    let global = lrc.reconstruct(&f, &mut s).unwrap();
+	    assert_eq!(global, lost >= 15);
+	    assert_eq!(s[lost].as_ref(), Some(&good[lost]));
+	}
+    }
+
+    #[test]
+    fn recoverable_patterns_match_reconstruct() {
+	let f = new_gf8_0x11b();
+	let lrc = Lrc::new(&f, 6, 2, 2).unwrap();
+	let good = encoded(&f, &lrc, 4);
+	// any r + 1 = 3 failures are fine
+	assert_eq!(lrc.recoverable_patterns(&f, 3).len(), 120);
+	let four = lrc.recoverable_patterns(&f, 4);
+	// the Azure paper: 86% of 4-failure patterns for LRC(6,2,2)
+	assert_eq!(four.len(), 180);
+	for pat in four.iter() {
+	    let mut s : Vec<Option<Vec<u8>>> = good.iter().cloned().map(Some).collect();
+	    for p in pat { s[*p] = None }
+	    lrc.reconstruct(&f, &mut s).unwrap();
+	    assert!(s.iter().zip(&good).all(|(a, b)| a.as_ref() == Some(b)));
+	}
+	let mut s : Vec<Option<Vec<u8>>> = good.iter().cloned().map(Some).collect();
+	for p in [0usize, 1, 2, 6].iter() { s[*p] = None }
+	assert_eq!(lrc.reconstruct(&f, &mut s), Err(LrcError::Unrecoverable));
+    }
+
+    #[test]
+    fn gf16_and_errors() {
+	let f = new_gf16_0x1002b();
+	let lrc = Lrc::new(&f, 20, 4, 3).unwrap();
+	let good = encoded(&f, &lrc, 10);
+	let mut s : Vec<Option<Vec<u16>>> = good.iter().cloned().map(Some).collect();
+	for p in [0usize, 1, 7, 20, 25].iter() { s[*p] = None }
+	assert!(lrc.reconstruct(&f, &mut s).unwrap());
+	assert!(s.iter().zip(&good).all(|(a, b)| a.as_ref() == Some(b)));
+
+	assert_eq!(Lrc::new(&f, 7, 2, 2).err(), Some(LrcError::BadParameters));
+	assert_eq!(Lrc::new(&f, 6, 2, 0).err(), Some(LrcError::BadParameters));
+	let mut short = good.clone();
+	short.pop();
+	assert_eq!(lrc.encode_shards(&f, &mut short), Err(LrcError::WrongShardCount));
+    }
+}
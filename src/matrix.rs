@@ -0,0 +1,828 @@
+//! # Dense matrices over GF(2<sup>x</sup>)
+//!
+//! [Matrix] stores its elements in a single vector in row-major
+//! order. Like the polynomial routines, anything that does field
+//! maths takes the field object as an argument. All row operations
+//! are done with the field's `vec_*` methods, so an optimised
+//! [GaloisField] implementation speeds them up automatically.
+//!
+//! ```rust
+//! use guff::{GaloisField, new_gf8};
+//! use guff::matrix::{Matrix, MatrixError};
+//!
+//! let f = new_gf8(0x11b, 0x1b);
+//! let m = Matrix::<_>::from_rows(&f, &[[1u8, 2, 3], [4, 5, 6], [7, 8, 10]]);
+//!
+//! let inv = m.inverse(&f).unwrap();
+//! assert_eq!(m.mul(&f, &inv), Matrix::identity(&f, 3));
+//!
+//! // solve m·x = b
+//! let b = vec![1u8, 0, 0x80];
+//! let x = m.solve(&f, &b).unwrap();
+//! assert_eq!(m.mul_vec(&f, &x), b);
+//!
+//! // row 3 = row 1 + row 2 (in characteristic 2)
+//! let s = Matrix::<_>::from_rows(&f, &[[1u8, 2], [4, 5], [5, 7]]);
+//! assert_eq!(s.rank(&f), 2);
+//! let sq = Matrix::<_>::from_rows(&f, &[[1u8, 1], [1, 1]]);
+//! assert_eq!(sq.inverse(&f).err(), Some(MatrixError::Singular));
+//! ```
+//!
+//! Besides inversion, there are [Matrix::rref] (reduced row echelon
+//! form plus pivot columns), [Matrix::null_space] (eg, to get a
+//! parity-check matrix from a generator matrix) and [Matrix::plu],
+//! which factorises a matrix once so that [Plu::solve] can be used on
+//! any number of right-hand sides.
+//!
+//! # Structured matrices
+//!
+//! Erasure codes are built from Vandermonde and Cauchy matrices,
+//! which can be created with [Matrix::vandermonde] and
+//! [Matrix::cauchy]. A decoder only ever needs to invert a square
+//! submatrix made up of some of the rows of these, which is itself a
+//! Vandermonde or Cauchy matrix on a subset of the points. So
+//! [Matrix::vandermonde_inverse] and [Matrix::cauchy_inverse] take
+//! the points directly and use closed-form O(n<sup>2</sup>) inverses
+//! instead of O(n<sup>3</sup>) elimination:
+//!
+//! ```rust
+//! use guff::good::new_gf8_0x11b;
+//! use guff::matrix::Matrix;
+//!
+//! let f = new_gf8_0x11b();
+//! let points = [1u8, 2, 3, 4, 5, 6, 7];
+//!
+//! // 7×4 generator whose top 4 rows are the identity
+//! let g = Matrix::systematic_vandermonde(&f, &points, 4).unwrap();
+//! assert_eq!(g.row(0), &[1, 0, 0, 0]);
+//!
+//! let x = [0x10u8, 0x20, 0x30];
+//! let y = [1u8, 2, 3];
+//! let c = Matrix::cauchy(&f, &x, &y).unwrap();
+//! let inv = Matrix::cauchy_inverse(&f, &x, &y).unwrap();
+//! assert_eq!(c.mul(&f, &inv), Matrix::identity(&f, 3));
+//! ```
+
+use crate::GaloisField;
+
+use num::{One,Zero};
+use std::fmt;
+
+/// Errors returned by matrix operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatrixError {
+    /// Matrix (or system of equations) has no inverse/unique solution
+    Singular,
+    /// Operation needs a square matrix
+    NotSquare,
+    /// Dimensions of the arguments don't match
+    DimensionMismatch,
+    /// Field has too few elements for a matrix of the requested size
+    FieldTooSmall,
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    MatrixError::Singular          => write!(f, "matrix is singular"),
+	    MatrixError::NotSquare         => write!(f, "matrix is not square"),
+	    MatrixError::DimensionMismatch => write!(f, "matrix dimensions don't match"),
+	    MatrixError::FieldTooSmall     => write!(f, "field too small for matrix size"),
+	}
+    }
+}
+
+impl std::error::Error for MatrixError {}
+
+/// Dense matrix with elements from field `G`, stored row-major
+pub struct Matrix<G> where G : GaloisField {
+    rows : usize,
+    cols : usize,
+    data : Vec<G::E>,
+}
+
+// derive() would want G itself to be Clone, etc.
+impl<G> Clone for Matrix<G> where G : GaloisField {
+    fn clone(&self) -> Self {
+	Matrix { rows : self.rows, cols : self.cols, data : self.data.clone() }
+    }
+}
+
+impl<G> PartialEq for Matrix<G> where G : GaloisField {
+    fn eq(&self, other : &Self) -> bool {
+	self.rows == other.rows && self.cols == other.cols && self.data == other.data
+    }
+}
+
+impl<G> Eq for Matrix<G> where G : GaloisField {}
+
+impl<G> fmt::Debug for Matrix<G> where G : GaloisField {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	writeln!(f, "Matrix {}x{} [", self.rows, self.cols)?;
+	for r in 0..self.rows {
+	    writeln!(f, "  {:?}", self.row(r))?;
+	}
+	write!(f, "]")
+    }
+}
+
+impl<G> std::ops::Index<(usize, usize)> for Matrix<G> where G : GaloisField {
+    type Output = G::E;
+    fn index(&self, (r, c) : (usize, usize)) -> &G::E {
+	assert!(c < self.cols);
+	&self.data[r * self.cols + c]
+    }
+}
+
+impl<G> std::ops::IndexMut<(usize, usize)> for Matrix<G> where G : GaloisField {
+    fn index_mut(&mut self, (r, c) : (usize, usize)) -> &mut G::E {
+	assert!(c < self.cols);
+	&mut self.data[r * self.cols + c]
+    }
+}
+
+impl<G> Matrix<G> where G : GaloisField {
+
+    /// All-zero matrix
+    pub fn zero(_f : &G, rows : usize, cols : usize) -> Matrix<G> {
+	Matrix { rows, cols, data : vec![G::E::zero(); rows * cols] }
+    }
+
+    /// n×n identity matrix
+    pub fn identity(f : &G, n : usize) -> Matrix<G> {
+	let mut m = Matrix::zero(f, n, n);
+	for i in 0..n { m[(i, i)] = G::E::one() }
+	m
+    }
+
+    /// Build from a row-major vector of elements
+    pub fn from_vec(_f : &G, rows : usize, cols : usize, data : Vec<G::E>) -> Matrix<G> {
+	assert_eq!(data.len(), rows * cols, "data length doesn't match dimensions");
+	Matrix { rows, cols, data }
+    }
+
+    /// Build from a list of rows, which must all be the same length
+    pub fn from_rows<R>(_f : &G, rows : &[R]) -> Matrix<G>
+    where R : AsRef<[G::E]>
+    {
+	let cols = rows.first().map_or(0, |r| r.as_ref().len());
+	let mut data = Vec::with_capacity(rows.len() * cols);
+	for r in rows {
+	    assert_eq!(r.as_ref().len(), cols, "rows must all be the same length");
+	    data.extend_from_slice(r.as_ref());
+	}
+	Matrix { rows : rows.len(), cols, data }
+    }
+
+    /// Number of rows
+    pub fn rows(&self) -> usize { self.rows }
+
+    /// Number of columns
+    pub fn cols(&self) -> usize { self.cols }
+
+    /// Test whether the matrix is square
+    pub fn is_square(&self) -> bool { self.rows == self.cols }
+
+    /// Elements in row-major order
+    pub fn as_slice(&self) -> &[G::E] { &self.data }
+
+    /// Consume the matrix, returning its row-major elements
+    pub fn into_vec(self) -> Vec<G::E> { self.data }
+
+    /// A single row
+    pub fn row(&self, r : usize) -> &[G::E] {
+	&self.data[r * self.cols .. (r + 1) * self.cols]
+    }
+
+    /// A single row, mutably
+    pub fn row_mut(&mut self, r : usize) -> &mut [G::E] {
+	&mut self.data[r * self.cols .. (r + 1) * self.cols]
+    }
+
+    /// Copy of a single column
+    pub fn col(&self, c : usize) -> Vec<G::E> {
+	(0..self.rows).map(|r| self[(r, c)]).collect()
+    }
+
+    /// Swap two rows
+    pub fn swap_rows(&mut self, a : usize, b : usize) {
+	if a == b { return }
+	let (lo, hi) = if a < b { (a, b) } else { (b, a) };
+	let (top, bottom) = self.data.split_at_mut(hi * self.cols);
+	top[lo * self.cols .. (lo + 1) * self.cols]
+	    .swap_with_slice(&mut bottom[..self.cols]);
+    }
+
+    // row dest += c · row src
+    fn add_scaled_row(&mut self, f : &G, dest : usize, src : usize, c : G::E) {
+	assert_ne!(dest, src);
+	let cols = self.cols;
+	let (d, s) = if dest < src {
+	    let (top, bottom) = self.data.split_at_mut(src * cols);
+	    (&mut top[dest * cols .. (dest + 1) * cols], &bottom[..cols])
+	} else {
+	    let (top, bottom) = self.data.split_at_mut(dest * cols);
+	    (&mut bottom[..cols], &top[src * cols .. (src + 1) * cols])
+	};
+	f.vec_add_scaled_vec_in_place(d, s, c);
+    }
+
+    /// Transposed copy
+    pub fn transpose(&self) -> Matrix<G> {
+	let mut data = Vec::with_capacity(self.data.len());
+	for c in 0..self.cols {
+	    for r in 0..self.rows { data.push(self[(r, c)]) }
+	}
+	Matrix { rows : self.cols, cols : self.rows, data }
+    }
+
+    /// Matrix product self · other
+    ///
+    /// Panics if the inner dimensions don't match.
+    pub fn mul(&self, f : &G, other : &Matrix<G>) -> Matrix<G> {
+	assert_eq!(self.cols, other.rows, "matrix dimensions don't match");
+	let mut prod = Matrix::zero(f, self.rows, other.cols);
+	// each output row is a combination of rows of other
+	for r in 0..self.rows {
+	    let out = prod.row_mut(r);
+	    for (k, a) in self.row(r).iter().enumerate() {
+		f.vec_add_scaled_vec_in_place(out, other.row(k), *a);
+	    }
+	}
+	prod
+    }
+
+    /// Matrix-vector product self · v
+    pub fn mul_vec(&self, f : &G, v : &[G::E]) -> Vec<G::E> {
+	assert_eq!(self.cols, v.len(), "vector length doesn't match matrix");
+	(0..self.rows).map(|r| f.vec_dot_product(self.row(r), v)).collect()
+    }
+
+    /// Convert to reduced row echelon form in place, returning the rank
+    pub fn row_reduce(&mut self, f : &G) -> usize {
+	self.reduce_with_pivots(f).len()
+    }
+
+    // Gauss-Jordan elimination, returning the pivot columns
+    fn reduce_with_pivots(&mut self, f : &G) -> Vec<usize> {
+	let zero = G::E::zero();
+	let mut pivots = Vec::new();
+	for c in 0..self.cols {
+	    let rank = pivots.len();
+	    if rank == self.rows { break }
+	    let pivot = match (rank..self.rows).find(|r| self[(*r, c)] != zero) {
+		Some(p) => p,
+		None => continue
+	    };
+	    self.swap_rows(rank, pivot);
+	    let inv = f.inv(self[(rank, c)]);
+	    f.vec_constant_scale_in_place(self.row_mut(rank), inv);
+	    for r in 0..self.rows {
+		if r == rank { continue }
+		let factor = self[(r, c)];
+		if factor != zero { self.add_scaled_row(f, r, rank, factor) }
+	    }
+	    pivots.push(c);
+	}
+	pivots
+    }
+
+    /// Reduced row echelon form, along with the pivot columns
+    ///
+    /// Row i of the result has its leading 1 in column `pivots[i]`,
+    /// and the number of pivots is the rank.
+    pub fn rref(&self, f : &G) -> (Matrix<G>, Vec<usize>) {
+	let mut m = self.clone();
+	let pivots = m.reduce_with_pivots(f);
+	(m, pivots)
+    }
+
+    /// Basis for the null space (kernel), one vector per row
+    ///
+    /// The result has `cols - rank` rows, each a vector x with
+    /// self · x = 0. If the rows of `self` generate a linear code,
+    /// this is a parity-check matrix for it.
+    pub fn null_space(&self, f : &G) -> Matrix<G> {
+	let (r, pivots) = self.rref(f);
+	let mut is_pivot = vec![false; self.cols];
+	for c in pivots.iter() { is_pivot[*c] = true }
+	let free : Vec<usize> = (0..self.cols).filter(|c| !is_pivot[*c]).collect();
+	let mut basis = Matrix::zero(f, free.len(), self.cols);
+	for (b, fc) in free.iter().enumerate() {
+	    basis[(b, *fc)] = G::E::one();
+	    // x[pivot] = -r[i][free], and negation does nothing here
+	    for (i, pc) in pivots.iter().enumerate() {
+		basis[(b, *pc)] = r[(i, *fc)];
+	    }
+	}
+	basis
+    }
+
+    /// PLU decomposition of a square matrix, for solving many
+    /// systems with the same left-hand side
+    pub fn plu(&self, f : &G) -> Result<Plu<G>, MatrixError> {
+	if !self.is_square() { return Err(MatrixError::NotSquare) }
+	let zero = G::E::zero();
+	let n = self.rows;
+	let mut lu = self.clone();
+	let mut perm : Vec<usize> = (0..n).collect();
+	for c in 0..n {
+	    let pivot = (c..n).find(|r| lu[(*r, c)] != zero)
+		.ok_or(MatrixError::Singular)?;
+	    lu.swap_rows(c, pivot);
+	    perm.swap(c, pivot);
+	    let inv = f.inv(lu[(c, c)]);
+	    for r in c + 1 .. n {
+		let factor = f.mul(lu[(r, c)], inv);
+		if factor == zero { continue }
+		// eliminate to the right of the diagonal, then store
+		// the multiplier in the space freed up below it
+		let cols = lu.cols;
+		let (top, bottom) = lu.data.split_at_mut(r * cols);
+		f.vec_add_scaled_vec_in_place(&mut bottom[c + 1 .. cols],
+					      &top[c * cols + c + 1 .. (c + 1) * cols],
+					      factor);
+		bottom[c] = factor;
+	    }
+	}
+	Ok(Plu { lu, perm })
+    }
+
+    /// Rank of the matrix
+    pub fn rank(&self, f : &G) -> usize {
+	self.clone().row_reduce(f)
+    }
+
+    /// Determinant of a square matrix
+    ///
+    /// (Row swaps don't change the sign in characteristic 2.)
+    pub fn determinant(&self, f : &G) -> Result<G::E, MatrixError> {
+	if !self.is_square() { return Err(MatrixError::NotSquare) }
+	let zero = G::E::zero();
+	let mut m = self.clone();
+	let mut det = G::E::one();
+	for c in 0..m.cols {
+	    let pivot = match (c..m.rows).find(|r| m[(*r, c)] != zero) {
+		Some(p) => p,
+		None => return Ok(zero)
+	    };
+	    m.swap_rows(c, pivot);
+	    let p = m[(c, c)];
+	    det = f.mul(det, p);
+	    let inv = f.inv(p);
+	    for r in c + 1 .. m.rows {
+		let factor = m[(r, c)];
+		if factor != zero { m.add_scaled_row(f, r, c, f.mul(factor, inv)) }
+	    }
+	}
+	Ok(det)
+    }
+
+    /// Inverse of a square matrix
+    pub fn inverse(&self, f : &G) -> Result<Matrix<G>, MatrixError> {
+	if !self.is_square() { return Err(MatrixError::NotSquare) }
+	let n = self.rows;
+	// reduce [self | I] to [I | inverse]
+	let mut aug = Matrix::zero(f, n, 2 * n);
+	for r in 0..n {
+	    let row = aug.row_mut(r);
+	    row[..n].copy_from_slice(self.row(r));
+	    row[n + r] = G::E::one();
+	}
+	aug.row_reduce(f);
+	if n > 0 && aug[(n - 1, n - 1)] != G::E::one() {
+	    return Err(MatrixError::Singular)
+	}
+	let mut data = Vec::with_capacity(n * n);
+	for r in 0..n { data.extend_from_slice(&aug.row(r)[n..]) }
+	Ok(Matrix { rows : n, cols : n, data })
+    }
+
+    /// Solve self · x = b for a square, non-singular matrix
+    pub fn solve(&self, f : &G, b : &[G::E]) -> Result<Vec<G::E>, MatrixError> {
+	if !self.is_square() { return Err(MatrixError::NotSquare) }
+	if b.len() != self.rows { return Err(MatrixError::DimensionMismatch) }
+	let n = self.rows;
+	let mut aug = Matrix::zero(f, n, n + 1);
+	for (r, br) in b.iter().enumerate() {
+	    let row = aug.row_mut(r);
+	    row[..n].copy_from_slice(self.row(r));
+	    row[n] = *br;
+	}
+	aug.row_reduce(f);
+	if n > 0 && aug[(n - 1, n - 1)] != G::E::one() {
+	    return Err(MatrixError::Singular)
+	}
+	Ok(aug.col(n))
+    }
+}
+
+/// PLU decomposition P·A = L·U of a square matrix
+///
+/// L (unit lower triangular) and U (upper triangular) are stored
+/// together in a single matrix. Created by [Matrix::plu].
+pub struct Plu<G> where G : GaloisField {
+    lu   : Matrix<G>,
+    perm : Vec<usize>,
+}
+
+impl<G> Plu<G> where G : GaloisField {
+
+    /// Row permutation: row i of P·A is row `perm()[i]` of A
+    pub fn perm(&self) -> &[usize] { &self.perm }
+
+    /// Permutation as a matrix P
+    pub fn p(&self, f : &G) -> Matrix<G> {
+	let n = self.perm.len();
+	let mut p = Matrix::zero(f, n, n);
+	for (i, r) in self.perm.iter().enumerate() { p[(i, *r)] = G::E::one() }
+	p
+    }
+
+    /// Unit lower triangular factor L
+    pub fn l(&self, f : &G) -> Matrix<G> {
+	let n = self.perm.len();
+	let mut l = Matrix::identity(f, n);
+	for r in 0..n {
+	    for c in 0..r { l[(r, c)] = self.lu[(r, c)] }
+	}
+	l
+    }
+
+    /// Upper triangular factor U
+    pub fn u(&self, f : &G) -> Matrix<G> {
+	let n = self.perm.len();
+	let mut u = Matrix::zero(f, n, n);
+	for r in 0..n {
+	    for c in r..n { u[(r, c)] = self.lu[(r, c)] }
+	}
+	u
+    }
+
+    /// Determinant of the original matrix
+    pub fn determinant(&self, f : &G) -> G::E {
+	(0..self.perm.len()).fold(G::E::one(), |d, i| f.mul(d, self.lu[(i, i)]))
+    }
+
+    /// Solve A·x = b
+    pub fn solve(&self, f : &G, b : &[G::E]) -> Vec<G::E> {
+	let n = self.perm.len();
+	assert_eq!(b.len(), n, "vector length doesn't match matrix");
+	let mut x : Vec<G::E> = self.perm.iter().map(|r| b[*r]).collect();
+	// forward substitution with L (unit diagonal)
+	for i in 0..n {
+	    let sum = f.vec_dot_product(&self.lu.row(i)[..i], &x[..i]);
+	    x[i] = x[i] ^ sum;
+	}
+	// back substitution with U
+	for i in (0..n).rev() {
+	    let row = self.lu.row(i);
+	    let sum = f.vec_dot_product(&row[i + 1..], &x[i + 1..]);
+	    x[i] = f.div(x[i] ^ sum, row[i]);
+	}
+	x
+    }
+
+    /// Solve A·X = B for every column of B at once
+    pub fn solve_matrix(&self, f : &G, b : &Matrix<G>) -> Matrix<G> {
+	let cols : Vec<Vec<G::E>> = (0..b.cols()).map(|c| self.solve(f, &b.col(c))).collect();
+	Matrix::from_rows(f, &cols).transpose()
+    }
+}
+
+// Structured matrices. These are the building blocks of erasure
+// codes, and have closed-form inverses that are much cheaper than
+// general elimination.
+impl<G> Matrix<G> where G : GaloisField {
+
+    /// Vandermonde matrix with one row per point, V<sub>ij</sub> =
+    /// x<sub>i</sub><sup>j</sup>
+    ///
+    /// Any `cols` rows are linearly independent if the points are
+    /// distinct.
+    pub fn vandermonde(f : &G, points : &[G::E], cols : usize) -> Matrix<G> {
+	let mut m = Matrix::zero(f, points.len(), cols);
+	for (r, x) in points.iter().enumerate() {
+	    let mut power = G::E::one();
+	    for e in m.row_mut(r).iter_mut() {
+		*e = power;
+		power = f.mul(power, *x);
+	    }
+	}
+	m
+    }
+
+    /// Cauchy matrix C<sub>ij</sub> = 1/(x<sub>i</sub> + y<sub>j</sub>)
+    ///
+    /// Every square submatrix is non-singular as long as all the x and
+    /// y values are distinct. Fails if any x<sub>i</sub> =
+    /// y<sub>j</sub>.
+    pub fn cauchy(f : &G, xs : &[G::E], ys : &[G::E]) -> Result<Matrix<G>, MatrixError> {
+	let mut m = Matrix::zero(f, xs.len(), ys.len());
+	for (r, x) in xs.iter().enumerate() {
+	    for (e, y) in m.row_mut(r).iter_mut().zip(ys) {
+		let sum = *x ^ *y;
+		if sum == G::E::zero() { return Err(MatrixError::Singular) }
+		*e = f.inv(sum);
+	    }
+	}
+	Ok(m)
+    }
+
+    /// Convert a tall (n×k, n ≥ k) generator matrix to systematic
+    /// form, ie so that its first k rows are the identity
+    ///
+    /// The result is self · T<sup>-1</sup>, where T is the top k×k
+    /// submatrix, so it generates the same code.
+    pub fn to_systematic(&self, f : &G) -> Result<Matrix<G>, MatrixError> {
+	if self.rows < self.cols { return Err(MatrixError::DimensionMismatch) }
+	let k = self.cols;
+	let top = Matrix::from_vec(f, k, k, self.data[..k * k].to_vec());
+	Ok(self.mul(f, &top.inverse(f)?))
+    }
+
+    /// Systematic n×k generator matrix derived from a Vandermonde
+    /// matrix on n distinct points
+    ///
+    /// Uses [vandermonde_inverse](Matrix::vandermonde_inverse) on the
+    /// top k points, so costs O(nk<sup>2</sup>) rather than needing
+    /// elimination.
+    pub fn systematic_vandermonde(f : &G, points : &[G::E], k : usize)
+				  -> Result<Matrix<G>, MatrixError> {
+	if points.len() < k { return Err(MatrixError::DimensionMismatch) }
+	let inv = Matrix::vandermonde_inverse(f, &points[..k])?;
+	let mut m = Matrix::vandermonde(f, &points[k..], k).mul(f, &inv);
+	// the top part is exactly the identity
+	let mut data = Matrix::identity(f, k).data;
+	data.append(&mut m.data);
+	m.data = data;
+	m.rows += k;
+	Ok(m)
+    }
+
+    /// Inverse of the square Vandermonde matrix on the given points,
+    /// in O(n<sup>2</sup>) operations
+    ///
+    /// Column i of the inverse holds the coefficients of the Lagrange
+    /// basis polynomial for point x<sub>i</sub>. Fails if the points
+    /// are not distinct.
+    pub fn vandermonde_inverse(f : &G, points : &[G::E]) -> Result<Matrix<G>, MatrixError> {
+	let n = points.len();
+	let zero = G::E::zero();
+	// master polynomial P(x) = Π (x - x_k), low power first
+	let mut master = vec![zero; n + 1];
+	master[0] = G::E::one();
+	for (k, x) in points.iter().enumerate() {
+	    for j in (1..=k + 1).rev() {
+		master[j] = master[j - 1] ^ f.mul(master[j], *x);
+	    }
+	    master[0] = f.mul(master[0], *x);
+	}
+	let mut inv = Matrix::zero(f, n, n);
+	let mut quot = vec![zero; n];
+	for (i, x) in points.iter().enumerate() {
+	    // synthetic division Q(x) = P(x) / (x - x_i)
+	    let mut carry = zero;
+	    for j in (0..n).rev() {
+		carry = master[j + 1] ^ f.mul(carry, *x);
+		quot[j] = carry;
+	    }
+	    // Q(x_i) = Π_{k≠i} (x_i - x_k)
+	    let mut denom = zero;
+	    for q in quot.iter().rev() { denom = f.mul(denom, *x) ^ *q }
+	    if denom == zero { return Err(MatrixError::Singular) }
+	    let scale = f.inv(denom);
+	    for (j, q) in quot.iter().enumerate() {
+		inv[(j, i)] = f.mul(*q, scale);
+	    }
+	}
+	Ok(inv)
+    }
+
+    /// Inverse of the square Cauchy matrix built from xs and ys, in
+    /// O(n<sup>2</sup>) operations
+    ///
+    /// Uses the closed form
+    /// (C<sup>-1</sup>)<sub>ij</sub> = a<sub>j</sub>b<sub>i</sub> /
+    /// ((x<sub>j</sub> + y<sub>i</sub>) c<sub>j</sub> d<sub>i</sub>),
+    /// where a<sub>j</sub> = Π<sub>k</sub> (x<sub>j</sub> +
+    /// y<sub>k</sub>), b<sub>i</sub> = Π<sub>k</sub> (x<sub>k</sub> +
+    /// y<sub>i</sub>), c<sub>j</sub> = Π<sub>k≠j</sub> (x<sub>j</sub> +
+    /// x<sub>k</sub>) and d<sub>i</sub> = Π<sub>k≠i</sub>
+    /// (y<sub>i</sub> + y<sub>k</sub>).
+    pub fn cauchy_inverse(f : &G, xs : &[G::E], ys : &[G::E]) -> Result<Matrix<G>, MatrixError> {
+	if xs.len() != ys.len() { return Err(MatrixError::NotSquare) }
+	let n = xs.len();
+	let zero = G::E::zero();
+	let one = G::E::one();
+	let prod_with = |v : G::E, others : &[G::E], skip : Option<usize>| {
+	    others.iter().enumerate()
+		.filter(|(k, _)| Some(*k) != skip)
+		.fold(one, |acc, (_, o)| f.mul(acc, v ^ *o))
+	};
+	let a : Vec<G::E> = xs.iter().map(|x| prod_with(*x, ys, None)).collect();
+	let b : Vec<G::E> = ys.iter().map(|y| prod_with(*y, xs, None)).collect();
+	let c : Vec<G::E> = (0..n).map(|j| prod_with(xs[j], xs, Some(j))).collect();
+	let d : Vec<G::E> = (0..n).map(|i| prod_with(ys[i], ys, Some(i))).collect();
+	if a.iter().chain(c.iter()).chain(d.iter()).any(|v| *v == zero) {
+	    return Err(MatrixError::Singular)
+	}
+	let mut inv = Matrix::zero(f, n, n);
+	for i in 0..n {
+	    for j in 0..n {
+		let num = f.mul(a[j], b[i]);
+		let den = f.mul(f.mul(xs[j] ^ ys[i], c[j]), d[i]);
+		inv[(i, j)] = f.div(num, den);
+	    }
+	}
+	Ok(inv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{new_gf4, new_gf8, new_gf16};
+    use crate::good::new_gf8_0x11b;
+
+    #[test]
+    fn transpose_and_identity() {
+	let f = new_gf8_0x11b();
+	let m = Matrix::from_rows(&f, &[[1u8, 2, 3], [4, 5, 6]]);
+	let t = m.transpose();
+	assert_eq!((t.rows(), t.cols()), (3, 2));
+	assert_eq!(t.row(2), &[3, 6]);
+	assert_eq!(t.transpose(), m);
+	assert_eq!(m.mul(&f, &Matrix::identity(&f, 3)), m);
+	assert_eq!(Matrix::identity(&f, 2).mul(&f, &m), m);
+    }
+
+    #[test]
+    fn mul_matches_definition() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let a = Matrix::from_vec(&f, 3, 4, (1..=12u16).map(|i| i * 0x1111).collect());
+	let b = Matrix::from_vec(&f, 4, 2, (1..=8u16).map(|i| i * 999).collect());
+	let p = a.mul(&f, &b);
+	for r in 0..3 {
+	    for c in 0..2 {
+		let mut sum = 0;
+		for k in 0..4 { sum ^= f.mul(a[(r, k)], b[(k, c)]) }
+		assert_eq!(p[(r, c)], sum);
+	    }
+	}
+	assert_eq!(a.mul_vec(&f, &b.col(1)), p.col(1));
+    }
+
+    #[test]
+    fn inverse_round_trip() {
+	let f = new_gf8_0x11b();
+	// Vandermonde matrix on distinct points is invertible
+	let n = 8;
+	let mut m = Matrix::zero(&f, n, n);
+	for r in 0..n {
+	    for c in 0..n { m[(r, c)] = f.pow(r as u8 + 1, c as u16) }
+	}
+	let inv = m.inverse(&f).unwrap();
+	assert_eq!(m.mul(&f, &inv), Matrix::identity(&f, n));
+	assert_eq!(inv.mul(&f, &m), Matrix::identity(&f, n));
+	assert_eq!(f.mul(m.determinant(&f).unwrap(), inv.determinant(&f).unwrap()), 1);
+    }
+
+    #[test]
+    fn singular_input() {
+	let f = new_gf4(19, 3);
+	let m = Matrix::from_rows(&f, &[[1u8, 2, 3], [2, 4, 6], [0, 0, 1]]);
+	assert_eq!(m.rank(&f), 2);
+	assert_eq!(m.determinant(&f), Ok(0));
+	assert_eq!(m.inverse(&f).err(), Some(MatrixError::Singular));
+	assert_eq!(m.solve(&f, &[1, 2, 3]), Err(MatrixError::Singular));
+	let r = Matrix::zero(&f, 2, 3);
+	assert_eq!(r.inverse(&f).err(), Some(MatrixError::NotSquare));
+	assert_eq!(r.rank(&f), 0);
+    }
+
+    #[test]
+    fn solve_and_determinant() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let m = Matrix::from_rows(&f, &[[0u16, 7, 1], [3, 0, 9], [1, 1, 1]]);
+	let x = vec![0xabcdu16, 0x1234, 0xffff];
+	let b = m.mul_vec(&f, &x);
+	assert_eq!(m.solve(&f, &b), Ok(x));
+	// cofactor expansion along the first row
+	let det = f.mul(7, 3 ^ 9) ^ 3;
+	assert_eq!(m.determinant(&f), Ok(det));
+    }
+
+    #[test]
+    fn row_reduce_gives_rref() {
+	let f = new_gf8_0x11b();
+	let mut m = Matrix::from_rows(&f, &[[0u8, 2, 4, 1], [0, 1, 2, 0], [3, 0, 0, 1]]);
+	assert_eq!(m.row_reduce(&f), 3);
+	assert_eq!(m.row(0)[0], 1);
+	assert_eq!(m.col(0), vec![1, 0, 0]);
+	assert_eq!(m.col(1), vec![0, 1, 0]);
+	assert_eq!(m.col(3), vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn vandermonde_inverse_matches_elimination() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let points : Vec<u16> = (0..10u16).map(|i| i * 0x1357 + 1).collect();
+	let v = Matrix::vandermonde(&f, &points, 10);
+	let fast = Matrix::vandermonde_inverse(&f, &points).unwrap();
+	assert_eq!(fast, v.inverse(&f).unwrap());
+	// zero is a valid point too
+	let v = Matrix::vandermonde(&f, &[0, 1, 2], 3);
+	assert_eq!(v.mul(&f, &Matrix::vandermonde_inverse(&f, &[0, 1, 2]).unwrap()),
+		   Matrix::identity(&f, 3));
+	assert_eq!(Matrix::vandermonde_inverse(&f, &[1, 2, 1]).err(),
+		   Some(MatrixError::Singular));
+    }
+
+    #[test]
+    fn cauchy_inverse_matches_elimination() {
+	let f = new_gf8_0x11b();
+	let xs = [1u8, 2, 3, 4, 5, 6];
+	let ys = [0x10u8, 0x20, 0x30, 0x40, 0x50, 0x60];
+	let c = Matrix::cauchy(&f, &xs, &ys).unwrap();
+	let fast = Matrix::cauchy_inverse(&f, &xs, &ys).unwrap();
+	assert_eq!(fast, c.inverse(&f).unwrap());
+	assert_eq!(Matrix::cauchy(&f, &[1u8, 2], &[2u8, 3]).err(),
+		   Some(MatrixError::Singular));
+	assert_eq!(Matrix::cauchy_inverse(&f, &[1u8, 1], &[2u8, 3]).err(),
+		   Some(MatrixError::Singular));
+    }
+
+    #[test]
+    fn systematic_generator() {
+	let f = new_gf8_0x11b();
+	let points : Vec<u8> = (0..9).collect();
+	let sys = Matrix::systematic_vandermonde(&f, &points, 5).unwrap();
+	assert_eq!((sys.rows(), sys.cols()), (9, 5));
+	let slow = Matrix::vandermonde(&f, &points, 5).to_systematic(&f).unwrap();
+	assert_eq!(sys, slow);
+	for r in 0..5 { assert_eq!(sys.row(r), Matrix::identity(&f, 5).row(r)) }
+	// still MDS: any 5 rows are invertible
+	let rows = [1usize, 3, 5, 7, 8];
+	let sub = Matrix::from_rows(&f, &rows.iter().map(|r| sys.row(*r)).collect::<Vec<_>>());
+	assert!(sub.inverse(&f).is_ok());
+    }
+
+    #[test]
+    fn rref_and_null_space_gf8() {
+	let f = new_gf8(0x11d, 0x1d);
+	// third row is the sum of the first two, column 2 = 2·column 0
+	let m = Matrix::from_rows(&f, &[[1u8, 3, 2, 0, 5],
+					[4, 1, 8, 1, 0],
+					[5, 2, 10, 1, 5]]);
+	let (r, pivots) = m.rref(&f);
+	assert_eq!(pivots, vec![0, 1]);
+	assert_eq!(r.row(2), &[0, 0, 0, 0, 0]);
+	let ns = m.null_space(&f);
+	assert_eq!((ns.rows(), ns.cols()), (3, 5));
+	assert_eq!(ns.rank(&f), 3);
+	let zero = Matrix::zero(&f, 3, 3);
+	assert_eq!(m.mul(&f, &ns.transpose()), zero);
+    }
+
+    #[test]
+    fn parity_check_from_generator_gf16() {
+	let f = new_gf16(0x1002b, 0x2b);
+	// rows of g span a [7,3] code; rows of h span its dual
+	let points : Vec<u16> = (1..=7).collect();
+	let g = Matrix::vandermonde(&f, &points, 3).transpose();
+	let h = g.null_space(&f);
+	assert_eq!((h.rows(), h.cols()), (4, 7));
+	assert_eq!(g.mul(&f, &h.transpose()), Matrix::zero(&f, 3, 4));
+	// full-rank matrix has trivial null space
+	assert_eq!(Matrix::identity(&f, 4).null_space(&f).rows(), 0);
+    }
+
+    #[test]
+    fn plu_factors_and_solves() {
+	let f = new_gf8(0x11b, 0x1b);
+	// zero in the top-left forces a row swap
+	let a = Matrix::from_rows(&f, &[[0u8, 2, 3, 4],
+					[5, 0, 7, 8],
+					[9, 10, 0, 12],
+					[13, 14, 15, 0]]);
+	let plu = a.plu(&f).unwrap();
+	assert_ne!(plu.perm()[0], 0);
+	assert_eq!(plu.p(&f).mul(&f, &a), plu.l(&f).mul(&f, &plu.u(&f)));
+	assert_eq!(plu.determinant(&f), a.determinant(&f).unwrap());
+
+	let b = Matrix::from_vec(&f, 4, 3, (1..=12).collect());
+	let x = plu.solve_matrix(&f, &b);
+	assert_eq!(a.mul(&f, &x), b);
+	assert_eq!(plu.solve(&f, &b.col(1)), a.solve(&f, &b.col(1)).unwrap());
+    }
+
+    #[test]
+    fn plu_of_singular_matrix() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let a = Matrix::from_rows(&f, &[[1u16, 2], [2, 4]]);
+	assert!(a.plu(&f).is_err());
+	assert_eq!(Matrix::zero(&f, 2, 3).plu(&f).err(), Some(MatrixError::NotSquare));
+    }
+}
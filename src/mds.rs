@@ -0,0 +1,337 @@
+//! # MDS matrices and branch numbers
+//!
+//! A matrix is *maximum distance separable* (MDS) if every square
+//! submatrix of it is non-singular. Used as the redundancy part of a
+//! systematic generator matrix [I | M], it gives an erasure code that
+//! can recover from the loss of any m pieces; used as the diffusion
+//! layer of a block cipher, it gives the best possible *branch
+//! number*, ie, the minimum of wt(x) + wt(M·x) over non-zero x, where
+//! wt counts the non-zero elements of a vector.
+//!
+//! Both checks here are exhaustive, so are only practical for small
+//! matrices (up to 8×8 or so), but they stop at the first
+//! counterexample and prune whole branches of the search when a
+//! partial selection of rows is already dependent.
+//!
+//! ```rust
+//! use guff::{GaloisField, new_gf8};
+//! use guff::mds::{circulant, is_mds, differential_branch_number};
+//!
+//! let f = new_gf8(0x11b, 0x1b);
+//!
+//! // AES MixColumns
+//! let m = circulant(&f, &[2, 3, 1, 1]);
+//! assert!(is_mds(&f, &m));
+//! assert_eq!(differential_branch_number(&f, &m), 5);
+//!
+//! let bad = circulant(&f, &[1, 1, 1, 1]);
+//! assert!(!is_mds(&f, &bad));
+//! ```
+//!
+//! There are also constructors for circulant, Hadamard and Cauchy
+//! matrices, and a search for involutory (self-inverse) MDS Hadamard
+//! matrices, which let a cipher use the same layer for encryption
+//! and decryption.
+
+use crate::GaloisField;
+use crate::matrix::{Matrix, MatrixError};
+
+use num::{One,Zero,FromPrimitive};
+
+// Reduce v against a basis kept in echelon form (each basis vector
+// paired with its pivot position). Returns the reduced vector, or
+// None if it reduced to zero.
+fn reduce<G>(f : &G, basis : &[(usize, Vec<G::E>)], v : &[G::E]) -> Option<(usize, Vec<G::E>)>
+where G : GaloisField
+{
+    let zero = G::E::zero();
+    let mut v = v.to_vec();
+    for (p, b) in basis {
+	let c = v[*p];
+	if c != zero {
+	    // basis vectors are normalised to 1 at their pivot
+	    f.vec_add_scaled_vec_in_place(&mut v, b, c);
+	}
+    }
+    let p = v.iter().position(|e| *e != zero)?;
+    let inv = f.inv(v[p]);
+    f.vec_constant_scale_in_place(&mut v, inv);
+    Some((p, v))
+}
+
+// Depth-first search over row subsets (in increasing order) for a
+// fixed set of columns. Returns false as soon as a dependent set of
+// rows is found.
+fn rows_independent<G>(f : &G, rows : &[Vec<G::E>], start : usize, want : usize,
+		       basis : &mut Vec<(usize, Vec<G::E>)>) -> bool
+where G : GaloisField
+{
+    if basis.len() == want { return true }
+    for r in start..rows.len() {
+	// not enough rows left to finish this selection
+	if rows.len() - r < want - basis.len() { break }
+	match reduce(f, basis, &rows[r]) {
+	    None => return false,
+	    Some(b) => {
+		basis.push(b);
+		let ok = rows_independent(f, rows, r + 1, want, basis);
+		basis.pop();
+		if !ok { return false }
+	    }
+	}
+    }
+    true
+}
+
+// next k-subset of 0..n in lexicographic order
+fn next_combination(c : &mut [usize], n : usize) -> bool {
+    let k = c.len();
+    for i in (0..k).rev() {
+	if c[i] < n - k + i {
+	    c[i] += 1;
+	    for j in i + 1 .. k { c[j] = c[j - 1] + 1 }
+	    return true
+	}
+    }
+    false
+}
+
+/// Test whether every square submatrix is non-singular
+///
+/// Works up through submatrix sizes, so the cheap 1×1 checks (no
+/// zero entries) come first. For each set of columns, rows are added
+/// one at a time and a dependency among the first few rows rules out
+/// every selection that includes them.
+pub fn is_mds<G>(f : &G, m : &Matrix<G>) -> bool
+where G : GaloisField
+{
+    let zero = G::E::zero();
+    if m.as_slice().contains(&zero) { return false }
+    for k in 2..=m.rows().min(m.cols()) {
+	let mut cols : Vec<usize> = (0..k).collect();
+	loop {
+	    let rows : Vec<Vec<G::E>> = (0..m.rows())
+		.map(|r| cols.iter().map(|c| m[(r, *c)]).collect())
+		.collect();
+	    if !rows_independent(f, &rows, 0, k, &mut Vec::new()) { return false }
+	    if !next_combination(&mut cols, m.cols()) { break }
+	}
+    }
+    true
+}
+
+// Size of the smallest linearly dependent set of columns
+fn min_dependent_columns<G>(f : &G, h : &Matrix<G>) -> usize
+where G : GaloisField
+{
+    let cols : Vec<Vec<G::E>> = (0..h.cols()).map(|c| h.col(c)).collect();
+    // any rows + 1 columns are dependent
+    for s in 1..=h.rows() {
+	if !rows_independent(f, &cols, 0, s, &mut Vec::new()) { return s }
+    }
+    h.rows() + 1
+}
+
+/// Differential branch number min<sub>x≠0</sub> wt(x) + wt(M·x)
+///
+/// This is the minimum distance of the code {(x, M·x)}, found as the
+/// smallest dependent set of columns of its parity-check matrix
+/// [M | I]. An m×n matrix has branch number at most m + 1, with
+/// equality exactly when it is MDS.
+pub fn differential_branch_number<G>(f : &G, m : &Matrix<G>) -> usize
+where G : GaloisField
+{
+    let rows = m.rows();
+    let mut h = Matrix::zero(f, rows, m.cols() + rows);
+    for r in 0..rows {
+	h.row_mut(r)[..m.cols()].copy_from_slice(m.row(r));
+	h[(r, m.cols() + r)] = G::E::one();
+    }
+    min_dependent_columns(f, &h)
+}
+
+/// Linear branch number, ie the differential branch number of the
+/// transpose
+pub fn linear_branch_number<G>(f : &G, m : &Matrix<G>) -> usize
+where G : GaloisField
+{
+    differential_branch_number(f, &m.transpose())
+}
+
+/// Circulant matrix: row i is the first row rotated right by i
+pub fn circulant<G>(f : &G, first_row : &[G::E]) -> Matrix<G>
+where G : GaloisField
+{
+    let n = first_row.len();
+    let mut m = Matrix::zero(f, n, n);
+    for r in 0..n {
+	for c in 0..n { m[(r, c)] = first_row[(c + n - r) % n] }
+    }
+    m
+}
+
+/// Hadamard matrix H<sub>ij</sub> = a<sub>i⊕j</sub>
+///
+/// The length of `first_row` must be a power of two. Since H·H =
+/// (Σ a<sub>i</sub>)<sup>2</sup>·I, it is involutory when the
+/// entries sum to 1.
+pub fn hadamard<G>(f : &G, first_row : &[G::E]) -> Matrix<G>
+where G : GaloisField
+{
+    let n = first_row.len();
+    assert!(n.is_power_of_two(), "Hadamard matrix size must be a power of 2");
+    let mut m = Matrix::zero(f, n, n);
+    for r in 0..n {
+	for c in 0..n { m[(r, c)] = first_row[r ^ c] }
+    }
+    m
+}
+
+/// Cauchy matrix on the points x<sub>i</sub> = i, y<sub>j</sub> =
+/// rows + j, which is always MDS
+///
+/// Fails with [MatrixError::FieldTooSmall] if the field has fewer than
+/// rows + cols elements.
+pub fn cauchy_mds<G>(f : &G, rows : usize, cols : usize) -> Result<Matrix<G>, MatrixError>
+where G : GaloisField
+{
+    if (rows + cols) as u64 > 1u64 << G::ORDER { return Err(MatrixError::FieldTooSmall) }
+    let xs : Vec<G::E> = (0..rows).map(|i| G::E::from_usize(i).unwrap()).collect();
+    let ys : Vec<G::E> = (rows..rows + cols).map(|i| G::E::from_usize(i).unwrap()).collect();
+    Matrix::cauchy(f, &xs, &ys)
+}
+
+/// Test whether M·M = I
+pub fn is_involutory<G>(f : &G, m : &Matrix<G>) -> bool
+where G : GaloisField
+{
+    m.is_square() && m.mul(f, m) == Matrix::identity(f, m.rows())
+}
+
+/// Search for an n×n involutory MDS Hadamard matrix
+///
+/// Tries first rows made up of distinct non-zero elements below
+/// `limit`, in lexicographic order, scaling each so that its entries
+/// sum to 1. Returns the first one that is MDS, if any.
+pub fn find_involutory_hadamard<G>(f : &G, n : usize, limit : u64) -> Option<Matrix<G>>
+where G : GaloisField
+{
+    assert!(n.is_power_of_two(), "Hadamard matrix size must be a power of 2");
+    let limit = limit.min(1u64 << G::ORDER) as usize;
+    if limit <= n { return None }
+    // subsets of 1..limit, in every order (first element fixed to
+    // the smallest, since permuting rows/cols doesn't affect MDS)
+    let mut set : Vec<usize> = (1..=n).collect();
+    loop {
+	let mut perm = set.clone();
+	loop {
+	    let row : Vec<G::E> = perm.iter().map(|e| G::E::from_usize(*e).unwrap()).collect();
+	    let sum = row.iter().fold(G::E::zero(), |a, b| a ^ *b);
+	    if sum != G::E::zero() {
+		let inv = f.inv(sum);
+		let row : Vec<G::E> = row.iter().map(|e| f.mul(*e, inv)).collect();
+		let m = hadamard(f, &row);
+		if is_mds(f, &m) { return Some(m) }
+	    }
+	    if !next_permutation(&mut perm[1..]) { break }
+	}
+	// starting from 1..=n, this never brings in zero
+	if !next_combination(&mut set, limit) { break }
+    }
+    None
+}
+
+// next lexicographic permutation, false when wrapped around
+fn next_permutation(p : &mut [usize]) -> bool {
+    if p.len() < 2 { return false }
+    let mut i = p.len() - 1;
+    while i > 0 && p[i - 1] >= p[i] { i -= 1 }
+    if i == 0 { p.reverse(); return false }
+    let mut j = p.len() - 1;
+    while p[j] <= p[i - 1] { j -= 1 }
+    p.swap(i - 1, j);
+    p[i..].reverse();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{new_gf4, new_gf8, new_gf16};
+
+    // number of non-zero elements
+    fn weight<G : GaloisField>(v : &[G::E]) -> usize {
+	v.iter().filter(|e| **e != G::E::zero()).count()
+    }
+
+    // brute-force branch number over all non-zero x (small fields)
+    fn brute_branch<G : GaloisField>(f : &G, m : &Matrix<G>) -> usize {
+	let n = m.cols();
+	let q = 1usize << G::ORDER;
+	let mut best = usize::MAX;
+	for idx in 1..q.pow(n as u32) {
+	    let x : Vec<G::E> = (0..n).map(|i| G::E::from_usize((idx / q.pow(i as u32)) % q).unwrap()).collect();
+	    best = best.min(weight::<G>(&x) + weight::<G>(&m.mul_vec(f, &x)));
+	}
+	best
+    }
+
+    #[test]
+    fn aes_and_known_non_mds() {
+	let f = new_gf8(0x11b, 0x1b);
+	let aes = circulant(&f, &[2, 3, 1, 1]);
+	assert!(is_mds(&f, &aes));
+	assert_eq!(linear_branch_number(&f, &aes), 5);
+	// a zero entry
+	assert!(!is_mds(&f, &circulant(&f, &[2, 3, 1, 0])));
+	// rows 0 and 2 are equal
+	assert!(!is_mds(&f, &circulant(&f, &[1, 2, 1, 2])));
+    }
+
+    #[test]
+    fn branch_number_matches_brute_force() {
+	let f = new_gf4(19, 3);
+	for row in [[1u8, 2, 3], [1, 1, 2], [1, 1, 1], [5, 7, 9]].iter() {
+	    let m = circulant(&f, row);
+	    let b = differential_branch_number(&f, &m);
+	    assert_eq!(b, brute_branch(&f, &m));
+	    assert_eq!(b == 4, is_mds(&f, &m));
+	}
+	let m = Matrix::from_rows(&f, &[[1u8, 0, 3], [0, 1, 0]]);
+	assert_eq!(differential_branch_number(&f, &m), brute_branch(&f, &m));
+    }
+
+    #[test]
+    fn cauchy_matrices_are_mds() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let m = cauchy_mds(&f, 4, 6).unwrap();
+	assert!(is_mds(&f, &m));
+	assert_eq!(differential_branch_number(&f, &m), 5);
+	let f = new_gf4(19, 3);
+	assert_eq!(cauchy_mds(&f, 8, 9).err(), Some(MatrixError::FieldTooSmall));
+	assert!(is_mds(&f, &cauchy_mds(&f, 8, 8).unwrap()));
+    }
+
+    #[test]
+    fn hadamard_involution() {
+	let f = new_gf8(0x11b, 0x1b);
+	let h = hadamard(&f, &[1, 2, 3, 4]);
+	// sum of entries is 4, so H·H = 4²·I
+	assert_eq!(h.mul(&f, &h), circulant(&f, &[f.mul(4, 4), 0, 0, 0]));
+	let m = find_involutory_hadamard(&f, 4, 16).unwrap();
+	assert!(is_involutory(&f, &m));
+	assert!(is_mds(&f, &m));
+	assert_eq!(m.inverse(&f).unwrap(), m);
+    }
+
+    #[test]
+    fn involutory_search_in_small_field() {
+	let f = new_gf4(19, 3);
+	match find_involutory_hadamard(&f, 4, 16) {
+	    Some(m) => assert!(is_involutory(&f, &m) && is_mds(&f, &m)),
+	    None => panic!("expected an involutory 4×4 MDS matrix over GF(16)"),
+	}
+	assert!(!is_involutory(&f, &circulant(&f, &[2, 3, 1, 1])));
+    }
+}
@@ -64,7 +64,7 @@ pub fn lmull(big : u8, small : u8) -> u16 {
 }
 
 /// Straight multiply with small value containing packed left, right nibbles
-
+//
 // Might be slightly slower than calling lmull, rmull directly, due to
 // repeated calculation of shift/mask at the top, but the compiler
 // should be able to optimise these out.
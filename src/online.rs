@@ -0,0 +1,233 @@
+//! # Online Gaussian elimination
+//!
+//! In network coding and fountain-style protocols, coded packets
+//! arrive one at a time, each consisting of a coefficient vector over
+//! the k source packets and a payload that is the corresponding
+//! linear combination of them. An [OnlineDecoder] reduces each
+//! packet against those already held as soon as it arrives, so that
+//! the work is spread out over reception and packets that add nothing
+//! new (non-innovative ones) are spotted and dropped immediately.
+//!
+//! Internally, rows are kept in echelon form, with each stored row
+//! having a leading 1 in its own pivot column. Once rank k is
+//! reached, back-substitution turns the stored payloads into the
+//! source packets.
+//!
+//! ```rust
+//! use guff::{GaloisField, new_gf8};
+//! use guff::online::OnlineDecoder;
+//!
+//! let f = new_gf8(0x11d, 0x1d);
+//! let source = [[1u8, 2, 3], [4, 5, 6]];
+//!
+//! // encode: payload is a linear combination of source packets
+//! let encode = |c : &[u8]| -> Vec<u8> {
+//!     (0..3).map(|i| f.mul(c[0], source[0][i]) ^ f.mul(c[1], source[1][i]))
+//!           .collect()
+//! };
+//!
+//! let mut dec = OnlineDecoder::new(&f, 2, 3);
+//! assert!(dec.push(&f, &[3, 7], &encode(&[3, 7])));
+//! // a multiple of an earlier packet isn't innovative
+//! assert!(!dec.push(&f, &[6, 14], &encode(&[6, 14])));
+//! assert_eq!(dec.rank(), 1);
+//! assert!(dec.push(&f, &[1, 1], &encode(&[1, 1])));
+//!
+//! assert!(dec.is_complete());
+//! assert_eq!(dec.source(1), Some(&[4u8, 5, 6][..]));
+//! ```
+//!
+//! All row operations use the field's `vec_*` methods.
+
+use crate::GaloisField;
+
+use num::{One,Zero};
+
+/// Incremental decoder for k source packets of a fixed length
+pub struct OnlineDecoder<G> where G : GaloisField {
+    k        : usize,
+    width    : usize,
+    // rows[c], if present, is a row with leading 1 in column c. Each
+    // row holds k coefficients followed by the payload.
+    rows     : Vec<Option<Vec<G::E>>>,
+    rank     : usize,
+    received : usize,
+    solved   : bool,
+}
+
+impl<G> OnlineDecoder<G> where G : GaloisField {
+
+    /// New decoder for `k` source packets of `payload_len` elements
+    pub fn new(_f : &G, k : usize, payload_len : usize) -> OnlineDecoder<G> {
+	OnlineDecoder {
+	    k, width : k + payload_len,
+	    rows : (0..k).map(|_| None).collect(),
+	    rank : 0, received : 0, solved : k == 0,
+	}
+    }
+
+    /// Number of source packets, ie the rank needed to decode
+    pub fn k(&self) -> usize { self.k }
+
+    /// Current rank (number of innovative packets received)
+    pub fn rank(&self) -> usize { self.rank }
+
+    /// Total number of packets passed to [push](OnlineDecoder::push)
+    pub fn received(&self) -> usize { self.received }
+
+    /// Test whether all source packets have been recovered
+    pub fn is_complete(&self) -> bool { self.solved }
+
+    /// Test whether column (source packet) i has a pivot yet
+    pub fn has_pivot(&self, i : usize) -> bool { self.rows[i].is_some() }
+
+    /// Add a coded packet, returning whether it was innovative
+    ///
+    /// When this brings the rank up to k, back-substitution is done
+    /// straight away and the source packets become available.
+    pub fn push(&mut self, f : &G, coeffs : &[G::E], payload : &[G::E]) -> bool {
+	assert_eq!(coeffs.len(), self.k, "wrong number of coefficients");
+	assert_eq!(payload.len(), self.width - self.k, "wrong payload length");
+	self.received += 1;
+	if self.solved { return false }
+
+	let mut row = Vec::with_capacity(self.width);
+	row.extend_from_slice(coeffs);
+	row.extend_from_slice(payload);
+	if !self.reduce(f, &mut row) { return false }
+	if self.rank == self.k { self.back_substitute(f) }
+	true
+    }
+
+    // Eliminate existing pivot columns from the new row, then store it
+    // under its leading column (if any)
+    fn reduce(&mut self, f : &G, row : &mut [G::E]) -> bool {
+	let zero = G::E::zero();
+	let mut lead = None;
+	for c in 0..self.k {
+	    let x = row[c];
+	    if x == zero { continue }
+	    match &self.rows[c] {
+		// pivot row has zeros before column c, so this can't
+		// disturb columns already cleared
+		Some(pivot) => f.vec_add_scaled_vec_in_place(&mut row[c..], &pivot[c..], x),
+		None => if lead.is_none() { lead = Some(c) },
+	    }
+	}
+	let lead = match lead { Some(c) => c, None => return false };
+	let inv = f.inv(row[lead]);
+	if inv != G::E::one() {
+	    f.vec_constant_scale_in_place(&mut row[lead..], inv);
+	}
+	self.rows[lead] = Some(row.to_vec());
+	self.rank += 1;
+	true
+    }
+
+    // Clear everything above the diagonal, working up from the bottom
+    fn back_substitute(&mut self, f : &G) {
+	let zero = G::E::zero();
+	for p in (0..self.k).rev() {
+	    let mut row = self.rows[p].take().unwrap();
+	    for c in p + 1 .. self.k {
+		let x = row[c];
+		if x == zero { continue }
+		let other = self.rows[c].as_ref().unwrap();
+		f.vec_add_scaled_vec_in_place(&mut row[c..], &other[c..], x);
+	    }
+	    self.rows[p] = Some(row);
+	}
+	self.solved = true;
+    }
+
+    /// Decoded source packet i, once decoding is complete
+    pub fn source(&self, i : usize) -> Option<&[G::E]> {
+	if !self.solved { return None }
+	self.rows[i].as_ref().map(|r| &r[self.k..])
+    }
+
+    /// Consume the decoder, returning all source packets in order
+    pub fn into_sources(self) -> Option<Vec<Vec<G::E>>> {
+	if !self.solved { return None }
+	let k = self.k;
+	Some(self.rows.into_iter().map(|r| r.unwrap().split_off(k)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::new_gf16;
+    use crate::good::new_gf8_0x11b;
+
+    // simple deterministic "random" coefficients
+    fn coeffs(seed : &mut u32, k : usize) -> Vec<u8> {
+	(0..k).map(|_| {
+	    *seed ^= *seed << 13; *seed ^= *seed >> 17; *seed ^= *seed << 5;
+	    *seed as u8
+	}).collect()
+    }
+
+    fn encode<G : GaloisField>(f : &G, c : &[G::E], src : &[Vec<G::E>]) -> Vec<G::E> {
+	let mut out = vec![G::E::zero(); src[0].len()];
+	for (ci, s) in c.iter().zip(src) {
+	    f.vec_add_scaled_vec_in_place(&mut out, s, *ci);
+	}
+	out
+    }
+
+    #[test]
+    fn decodes_random_combinations() {
+	let f = new_gf8_0x11b();
+	let k = 20;
+	let src : Vec<Vec<u8>> = (0..k)
+	    .map(|i| (0..64).map(|j| (i * 31 + j * 7) as u8).collect())
+	    .collect();
+	let mut dec = OnlineDecoder::new(&f, k, 64);
+	let mut seed = 12345;
+	while !dec.is_complete() {
+	    let c = coeffs(&mut seed, k);
+	    let before = dec.rank();
+	    let innovative = dec.push(&f, &c, &encode(&f, &c, &src));
+	    assert_eq!(dec.rank(), before + innovative as usize);
+	}
+	assert!(dec.received() >= k);
+	assert_eq!(dec.into_sources().unwrap(), src);
+    }
+
+    #[test]
+    fn systematic_then_repair() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let src : Vec<Vec<u16>> = (0..4u16).map(|i| vec![i, i * 1000, 0xffff - i]).collect();
+	let mut dec = OnlineDecoder::new(&f, 4, 3);
+	// source packets 0 and 2 arrive uncoded
+	for i in [0usize, 2].iter() {
+	    let mut c = vec![0u16; 4];
+	    c[*i] = 1;
+	    assert!(dec.push(&f, &c, &src[*i]));
+	}
+	assert!(dec.has_pivot(2) && !dec.has_pivot(1));
+	assert_eq!(dec.source(0), None);
+	// combination of known packets isn't innovative
+	assert!(!dec.push(&f, &[5, 0, 9, 0], &encode(&f, &[5, 0, 9, 0], &src)));
+	for c in [[1u16, 2, 3, 4], [4, 3, 2, 1]].iter() {
+	    assert!(dec.push(&f, c, &encode(&f, c, &src)));
+	}
+	assert!(dec.is_complete());
+	for (i, s) in src.iter().enumerate() {
+	    assert_eq!(dec.source(i), Some(&s[..]));
+	}
+	// anything after completion is redundant
+	assert!(!dec.push(&f, &[1, 0, 0, 0], &src[0]));
+	assert_eq!(dec.received(), 6);
+    }
+
+    #[test]
+    fn zero_row_is_not_innovative() {
+	let f = new_gf8_0x11b();
+	let mut dec = OnlineDecoder::new(&f, 3, 2);
+	assert!(!dec.push(&f, &[0, 0, 0], &[0, 0]));
+	assert_eq!(dec.rank(), 0);
+    }
+}
@@ -0,0 +1,304 @@
+//! # Polynomials over GF(2<sup>x</sup>)
+//!
+//! A polynomial whose coefficients are elements of some field is
+//! stored as a plain vector of field elements, lowest power first, so
+//! that `p[i]` is the coefficient of x<sup>i</sup>. The [Poly] type
+//! is just an alias for `Vec<G::E>`, which means that any slice of
+//! field elements can be passed in wherever a polynomial is expected.
+//!
+//! All functions here return *normalised* polynomials, ie, without
+//! any trailing (high-order) zero coefficients. The zero polynomial
+//! is the empty vector. Inputs do not need to be normalised.
+//!
+//! As with the [GaloisField] methods, the field object does the
+//! maths, so it is always passed as the first argument:
+//!
+//! ```rust
+//! use guff::{GaloisField, new_gf8};
+//! use guff::poly;
+//!
+//! let f = new_gf8(0x11b, 0x1b);
+//!
+//! // (x + 3)(x + 5) = x² + 6x + 15
+//! let p = poly::mul(&f, &[3, 1], &[5, 1]);
+//! assert_eq!(p, vec![f.mul(3,5), 6, 1]);
+//!
+//! let (q, r) = poly::divrem(&f, &p, &[3, 1]);
+//! assert_eq!(q, vec![5, 1]);
+//! assert!(r.is_empty());
+//!
+//! assert_eq!(poly::eval(&f, &p, 3), 0);
+//! ```
+
+use crate::{GaloisField, ElementStore};
+
+use num::{One,Zero};
+
+/// Polynomial with coefficients in field `G`, lowest power first
+pub type Poly<G> = Vec<<G as GaloisField>::E>;
+
+/// Strip off any high-order zero coefficients
+pub fn normalise<E : ElementStore>(p : &mut Vec<E>) {
+    while let Some(c) = p.last() {
+	if *c != E::zero() { break }
+	p.pop();
+    }
+}
+
+/// Degree of the polynomial, or `None` for the zero polynomial
+pub fn degree<E : ElementStore>(p : &[E]) -> Option<usize> {
+    p.iter().rposition(|c| *c != E::zero())
+}
+
+/// Test whether a polynomial is zero (all coefficients zero)
+pub fn is_zero<E : ElementStore>(p : &[E]) -> bool {
+    degree(p).is_none()
+}
+
+/// Coefficient of the highest non-zero power (zero if `p` is zero)
+pub fn lead<E : ElementStore>(p : &[E]) -> E {
+    match degree(p) {
+	Some(d) => p[d],
+	None    => E::zero()
+    }
+}
+
+/// The monomial x<sup>n</sup>
+pub fn monomial<G>(_f : &G, n : usize) -> Poly<G>
+where G : GaloisField
+{
+    let mut p = vec![G::E::zero(); n + 1];
+    p[n] = G::E::one();
+    p
+}
+
+/// Sum (or difference) of two polynomials
+pub fn add<G>(f : &G, a : &[G::E], b : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = long.to_vec();
+    f.vec_add_vec_in_place(&mut sum[..short.len()], short);
+    normalise(&mut sum);
+    sum
+}
+
+/// Multiply each coefficient by a constant
+pub fn scale<G>(f : &G, a : &[G::E], c : G::E) -> Poly<G>
+where G : GaloisField
+{
+    if c == G::E::zero() { return Vec::new() }
+    let mut p = a.to_vec();
+    normalise(&mut p);
+    f.vec_constant_scale_in_place(&mut p, c);
+    p
+}
+
+/// Scale so that the leading coefficient is 1
+pub fn monic<G>(f : &G, a : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    let l = lead(a);
+    if l == G::E::zero() || l == G::E::one() {
+	let mut p = a.to_vec();
+	normalise(&mut p);
+	p
+    } else {
+	scale(f, a, f.inv(l))
+    }
+}
+
+/// Straight (schoolbook) polynomial multiplication
+pub fn mul<G>(f : &G, a : &[G::E], b : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    let (da, db) = match (degree(a), degree(b)) {
+	(Some(da), Some(db)) => (da, db),
+	_ => return Vec::new()
+    };
+    let zero = G::E::zero();
+    let mut prod = vec![zero; da + db + 1];
+    for (i, ai) in a[..=da].iter().enumerate() {
+	if *ai == zero { continue }
+	for (j, bj) in b[..=db].iter().enumerate() {
+	    prod[i + j] = prod[i + j] ^ f.mul(*ai, *bj)
+	}
+    }
+    prod
+}
+
+/// Polynomial long division, returning (quotient, remainder)
+///
+/// Panics if the divisor is the zero polynomial.
+pub fn divrem<G>(f : &G, a : &[G::E], b : &[G::E]) -> (Poly<G>, Poly<G>)
+where G : GaloisField
+{
+    let db = degree(b).expect("polynomial division by zero");
+    let mut r = a.to_vec();
+    normalise(&mut r);
+    if r.len() <= db { return (Vec::new(), r) }
+
+    let zero = G::E::zero();
+    let inv_lead = f.inv(b[db]);
+    let mut q = vec![zero; r.len() - db];
+    for i in (0..q.len()).rev() {
+	let c = r[i + db];
+	if c == zero { continue }
+	let c = f.mul(c, inv_lead);
+	q[i] = c;
+	for (j, bj) in b[..=db].iter().enumerate() {
+	    r[i + j] = r[i + j] ^ f.mul(c, *bj)
+	}
+    }
+    r.truncate(db);
+    normalise(&mut r);
+    normalise(&mut q);
+    (q, r)
+}
+
+/// Remainder of a divided by b
+pub fn rem<G>(f : &G, a : &[G::E], b : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    divrem(f, a, b).1
+}
+
+/// (a · b) mod m
+pub fn mulmod<G>(f : &G, a : &[G::E], b : &[G::E], m : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    rem(f, &mul(f, a, b), m)
+}
+
+/// a<sup>e</sup> mod m, by square and multiply
+pub fn powmod<G>(f : &G, a : &[G::E], mut e : u64, m : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    let mut result = rem(f, &[G::E::one()], m);
+    let mut base = rem(f, a, m);
+    while e > 0 {
+	if e & 1 == 1 { result = mulmod(f, &result, &base, m) }
+	e >>= 1;
+	if e > 0 { base = mulmod(f, &base, &base, m) }
+    }
+    result
+}
+
+/// Monic greatest common divisor of two polynomials
+///
+/// The gcd of two zero polynomials is returned as zero.
+pub fn gcd<G>(f : &G, a : &[G::E], b : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    normalise(&mut a);
+    normalise(&mut b);
+    while !b.is_empty() {
+	let r = rem(f, &a, &b);
+	a = b;
+	b = r;
+    }
+    monic(f, &a)
+}
+
+/// Evaluate the polynomial at point x (Horner's rule)
+pub fn eval<G>(f : &G, p : &[G::E], x : G::E) -> G::E
+where G : GaloisField
+{
+    let mut sum = G::E::zero();
+    for c in p.iter().rev() {
+	sum = f.mul(sum, x) ^ *c
+    }
+    sum
+}
+
+/// Formal derivative
+///
+/// In characteristic 2, d/dx x<sup>i</sup> = i·x<sup>i-1</sup> leaves
+/// only the odd powers, shifted down by one.
+pub fn derivative<G>(_f : &G, p : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    let zero = G::E::zero();
+    let mut d : Poly<G> = p.iter().enumerate().skip(1)
+	.map(|(i, c)| if i & 1 == 1 { *c } else { zero })
+	.collect();
+    normalise(&mut d);
+    d
+}
+
+/// The monic polynomial with the given roots, ie Π (x - r<sub>i</sub>)
+pub fn from_roots<G>(f : &G, roots : &[G::E]) -> Poly<G>
+where G : GaloisField
+{
+    let mut p = vec![G::E::one()];
+    for r in roots {
+	p = mul(f, &p, &[*r, G::E::one()]);
+    }
+    p
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{new_gf8, new_gf16};
+
+    #[test]
+    fn normalise_strips_high_zeros() {
+	let mut p = vec![1u8, 0, 2, 0, 0];
+	normalise(&mut p);
+	assert_eq!(p, vec![1, 0, 2]);
+	assert_eq!(degree(&p), Some(2));
+	assert_eq!(degree::<u8>(&[0, 0]), None);
+    }
+
+    #[test]
+    fn divrem_reconstructs_dividend() {
+	let f = new_gf8(0x11b, 0x1b);
+	let a = vec![7u8, 0x53, 0, 9, 0xca, 1, 200];
+	let b = vec![3u8, 0, 0x11];
+	let (q, r) = divrem(&f, &a, &b);
+	assert!(r.len() < b.len());
+	assert_eq!(add(&f, &mul(&f, &q, &b), &r), a);
+    }
+
+    #[test]
+    fn gcd_of_products() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let common = from_roots(&f, &[1234, 99]);
+	let a = mul(&f, &common, &from_roots(&f, &[5, 6, 7]));
+	let b = mul(&f, &common, &from_roots(&f, &[8, 0x8000]));
+	assert_eq!(gcd(&f, &a, &b), common);
+    }
+
+    #[test]
+    fn eval_at_roots() {
+	let f = new_gf8(0x11d, 0x1d);
+	let p = from_roots(&f, &[1, 2, 3, 0x80]);
+	for r in [1u8, 2, 3, 0x80].iter() {
+	    assert_eq!(eval(&f, &p, *r), 0)
+	}
+	assert_ne!(eval(&f, &p, 4), 0)
+    }
+
+    #[test]
+    fn derivative_of_square_is_zero() {
+	let f = new_gf8(0x11b, 0x1b);
+	let p = vec![3u8, 9, 1];
+	let sq = mul(&f, &p, &p);
+	assert!(derivative(&f, &sq).is_empty());
+	assert_eq!(derivative(&f, &p), vec![9]);
+    }
+
+    #[test]
+    fn powmod_matches_repeated_mulmod() {
+	let f = new_gf8(0x11b, 0x1b);
+	let m = vec![0x17u8, 2, 0, 1, 1];
+	let x = vec![0u8, 1];
+	let mut acc = vec![1u8];
+	for _ in 0..37 { acc = mulmod(&f, &acc, &x, &m) }
+	assert_eq!(powmod(&f, &x, 37, &m), acc);
+    }
+}
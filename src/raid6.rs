@@ -0,0 +1,297 @@
+//! # RAID-6 P+Q syndromes
+//!
+//! This follows the scheme used by Linux md (see H. Peter Anvin's
+//! "The mathematics of RAID-6"). A stripe has n data blocks
+//! D<sub>0</sub> ... D<sub>n-1</sub> and two syndrome blocks:
+//!
+//! P = D<sub>0</sub> + D<sub>1</sub> + ... + D<sub>n-1</sub>
+//!
+//! Q = g<sup>0</sup>D<sub>0</sub> + g<sup>1</sup>D<sub>1</sub> + ... +
+//! g<sup>n-1</sup>D<sub>n-1</sub>
+//!
+//! working bytewise in GF(2<sup>8</sup>) with polynomial 0x11d and
+//! generator g = 2. The output is byte-for-byte the same as the
+//! kernel's `gen_syndrome`, so it can be checked against real disk
+//! images. Since g has order 255, a stripe can have at most 255 data
+//! blocks (the kernel allows fewer).
+//!
+//! Stripes are laid out as the kernel does: data blocks first, then
+//! P, then Q. [recover] rebuilds any one or two missing blocks:
+//!
+//! ```rust
+//! use guff::raid6::{gen_syndrome, recover};
+//!
+//! let data : Vec<Vec<u8>> = (0..4).map(|i| vec![i as u8 * 17 + 1; 512]).collect();
+//! let refs : Vec<&[u8]> = data.iter().map(|d| &d[..]).collect();
+//! let mut p = vec![0u8; 512];
+//! let mut q = vec![0u8; 512];
+//! gen_syndrome(&refs, &mut p, &mut q).unwrap();
+//!
+//! let mut stripe : Vec<Option<Vec<u8>>> = data.iter().cloned().map(Some).collect();
+//! stripe.push(Some(p));
+//! stripe.push(Some(q));
+//! stripe[1] = None;
+//! stripe[3] = None;
+//! recover(&mut stripe).unwrap();
+//! assert_eq!(stripe[1].as_ref(), Some(&data[1]));
+//! assert_eq!(stripe[3].as_ref(), Some(&data[3]));
+//! ```
+//!
+//! When only one data block changes, [update_syndrome] patches P and
+//! Q from the old and new contents of that block, without reading
+//! the rest of the stripe.
+
+use crate::{GaloisField, F8, new_gf8};
+
+use std::fmt;
+
+/// Errors from RAID-6 operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Raid6Error {
+    /// Need between 1 and 255 data blocks
+    BadStripe,
+    /// Blocks are not all the same size
+    SizeMismatch,
+    /// More than two blocks are missing
+    TooManyFailures,
+}
+
+impl fmt::Display for Raid6Error {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    Raid6Error::BadStripe       => write!(f, "bad number of data blocks"),
+	    Raid6Error::SizeMismatch    => write!(f, "blocks differ in size"),
+	    Raid6Error::TooManyFailures => write!(f, "more than two blocks missing"),
+	}
+    }
+}
+
+impl std::error::Error for Raid6Error {}
+
+/// The RAID-6 field: GF(2<sup>8</sup>) with polynomial 0x11d
+pub fn field() -> F8 { new_gf8(0x11d, 0x1d) }
+
+// multiply every byte by g = 2
+#[inline]
+fn times_g(b : u8) -> u8 {
+    (b << 1) ^ if b & 0x80 != 0 { 0x1d } else { 0 }
+}
+
+// 256-entry product table for one constant, as the kernel's raid6_gfmul
+fn mul_table(f : &F8, c : u8) -> [u8; 256] {
+    let mut t = [0u8; 256];
+    for (i, e) in t.iter_mut().enumerate() { *e = f.mul(c, i as u8) }
+    t
+}
+
+// g^i
+fn gexp(f : &F8, i : usize) -> u8 { f.pow(2, (i % 255) as u16) }
+
+fn check_sizes<S : AsRef<[u8]>>(data : &[S], len : usize) -> Result<(), Raid6Error> {
+    if data.is_empty() || data.len() > 255 { return Err(Raid6Error::BadStripe) }
+    if data.iter().any(|d| d.as_ref().len() != len) { return Err(Raid6Error::SizeMismatch) }
+    Ok(())
+}
+
+/// Compute P and Q for a stripe of data blocks
+pub fn gen_syndrome(data : &[&[u8]], p : &mut [u8], q : &mut [u8]) -> Result<(), Raid6Error> {
+    let len = p.len();
+    check_sizes(data, len)?;
+    if q.len() != len { return Err(Raid6Error::SizeMismatch) }
+    // Horner's rule from the highest disk down, as the kernel does
+    let last = data.len() - 1;
+    p.copy_from_slice(data[last]);
+    q.copy_from_slice(data[last]);
+    for d in data[..last].iter().rev() {
+	for ((pb, qb), db) in p.iter_mut().zip(q.iter_mut()).zip(d.iter()) {
+	    *pb ^= *db;
+	    *qb = times_g(*qb) ^ *db;
+	}
+    }
+    Ok(())
+}
+
+/// Patch P and Q after data block `index` changes from `old` to `new`
+///
+/// This is the read-modify-write fast path: only the changed block
+/// and the two syndromes are touched.
+pub fn update_syndrome(index : usize, old : &[u8], new : &[u8], p : &mut [u8], q : &mut [u8])
+		       -> Result<(), Raid6Error> {
+    if index >= 255 { return Err(Raid6Error::BadStripe) }
+    let len = p.len();
+    if old.len() != len || new.len() != len || q.len() != len {
+	return Err(Raid6Error::SizeMismatch)
+    }
+    let table = mul_table(&field(), gexp(&field(), index));
+    for (((pb, qb), o), n) in p.iter_mut().zip(q.iter_mut()).zip(old).zip(new) {
+	let delta = *o ^ *n;
+	*pb ^= delta;
+	*qb ^= table[delta as usize];
+    }
+    Ok(())
+}
+
+// P and Q over the present data blocks, treating missing ones as zero
+fn partial_syndrome(stripe : &[Option<Vec<u8>>], n : usize, len : usize) -> (Vec<u8>, Vec<u8>) {
+    let zero = vec![0u8; len];
+    let data : Vec<&[u8]> = stripe[..n].iter()
+	.map(|b| b.as_ref().map_or(&zero[..], |v| &v[..])).collect();
+    let mut p = vec![0u8; len];
+    let mut q = vec![0u8; len];
+    gen_syndrome(&data, &mut p, &mut q).unwrap();
+    (p, q)
+}
+
+fn xor_into(dest : &mut [u8], src : &[u8]) {
+    for (d, s) in dest.iter_mut().zip(src) { *d ^= *s }
+}
+
+/// Rebuild up to two missing (`None`) blocks of a stripe
+///
+/// The stripe is laid out as data blocks, then P, then Q. All four
+/// kinds of double failure (data+data, data+P, data+Q and P+Q) are
+/// handled, as well as any single failure.
+pub fn recover(stripe : &mut [Option<Vec<u8>>]) -> Result<(), Raid6Error> {
+    if stripe.len() < 3 || stripe.len() > 257 { return Err(Raid6Error::BadStripe) }
+    let n = stripe.len() - 2;
+    let (pi, qi) = (n, n + 1);
+    let missing : Vec<usize> = (0..stripe.len()).filter(|i| stripe[*i].is_none()).collect();
+    if missing.len() > 2 { return Err(Raid6Error::TooManyFailures) }
+    if missing.is_empty() { return Ok(()) }
+    let len = stripe.iter().flatten().next().unwrap().len();
+    if stripe.iter().flatten().any(|b| b.len() != len) { return Err(Raid6Error::SizeMismatch) }
+
+    let f = field();
+    let lost_data : Vec<usize> = missing.iter().cloned().filter(|i| *i < n).collect();
+    let (mut pxy, mut qxy) = partial_syndrome(stripe, n, len);
+
+    match lost_data.len() {
+	0 => { },		// only P and/or Q: just recompute below
+	1 => {
+	    let x = lost_data[0];
+	    let dx = if stripe[pi].is_some() {
+		// D_x = P + (P over the rest)
+		xor_into(&mut pxy, stripe[pi].as_ref().unwrap());
+		pxy
+	    } else {
+		// D_x = g^-x (Q + (Q over the rest))
+		xor_into(&mut qxy, stripe[qi].as_ref().unwrap());
+		let table = mul_table(&f, f.inv(gexp(&f, x)));
+		qxy.iter().map(|b| table[*b as usize]).collect()
+	    };
+	    stripe[x] = Some(dx);
+	},
+	_ => {
+	    // the kernel's raid6_2data_recov: with Pxy, Qxy the
+	    // differences from the syndromes over the remaining blocks,
+	    // D_x = A·Pxy + B·Qxy and D_y = Pxy + D_x
+	    let (x, y) = (lost_data[0], lost_data[1]);
+	    xor_into(&mut pxy, stripe[pi].as_ref().unwrap());
+	    xor_into(&mut qxy, stripe[qi].as_ref().unwrap());
+	    let gyx = gexp(&f, y - x);
+	    let denom = f.inv(gyx ^ 1);
+	    let a = mul_table(&f, f.mul(gyx, denom));
+	    let b = mul_table(&f, f.mul(f.inv(gexp(&f, x)), denom));
+	    let dx : Vec<u8> = pxy.iter().zip(&qxy)
+		.map(|(p, q)| a[*p as usize] ^ b[*q as usize]).collect();
+	    xor_into(&mut pxy, &dx);
+	    stripe[x] = Some(dx);
+	    stripe[y] = Some(pxy);
+	}
+    }
+
+    if stripe[pi].is_none() || stripe[qi].is_none() {
+	let (p, q) = partial_syndrome(stripe, n, len);
+	if stripe[pi].is_none() { stripe[pi] = Some(p) }
+	if stripe[qi].is_none() { stripe[qi] = Some(q) }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn stripe(n : usize, len : usize) -> Vec<Vec<u8>> {
+	let mut blocks : Vec<Vec<u8>> = (0..n).map(|i| {
+	    (0..len).map(|j| ((i * 97 + j * 31 + i * j) % 256) as u8).collect()
+	}).collect();
+	let refs : Vec<&[u8]> = blocks.iter().map(|d| &d[..]).collect();
+	let mut p = vec![0u8; len];
+	let mut q = vec![0u8; len];
+	gen_syndrome(&refs, &mut p, &mut q).unwrap();
+	blocks.push(p);
+	blocks.push(q);
+	blocks
+    }
+
+    #[test]
+    fn matches_kernel_definition() {
+	// Q = Σ g^i D_i, with g^8 = 0x1d in the 0x11d field
+	let ones = vec![vec![1u8]; 9];
+	let refs : Vec<&[u8]> = ones.iter().map(|d| &d[..]).collect();
+	let (mut p, mut q) = ([0u8], [0u8]);
+	gen_syndrome(&refs, &mut p, &mut q).unwrap();
+	assert_eq!(p, [1]);
+	assert_eq!(q, [0xff ^ 0x1d]);
+
+	// and against a straightforward field calculation
+	let f = field();
+	let s = stripe(20, 64);
+	for (j, qb) in s[21].iter().enumerate() {
+	    let expect = (0..20).fold(0u8, |acc, i| acc ^ f.mul(f.pow(2, i as u16), s[i][j]));
+	    assert_eq!(*qb, expect);
+	}
+    }
+
+    #[test]
+    fn all_single_and_double_failures() {
+	let n = 7;
+	let good = stripe(n, 100);
+	for x in 0..n + 2 {
+	    for y in x..n + 2 {
+		let mut s : Vec<Option<Vec<u8>>> = good.iter().cloned().map(Some).collect();
+		s[x] = None;
+		s[y] = None;
+		recover(&mut s).unwrap();
+		assert!(s.iter().zip(&good).all(|(a, b)| a.as_ref() == Some(b)), "lost {} and {}", x, y);
+	    }
+	}
+    }
+
+    #[test]
+    fn update_fast_path() {
+	let mut s = stripe(10, 50);
+	let new : Vec<u8> = (0..50).map(|j| (j * 3) as u8).collect();
+	let old = std::mem::replace(&mut s[6], new.clone());
+	let (data, pq) = s.split_at_mut(10);
+	let (p, q) = pq.split_at_mut(1);
+	update_syndrome(6, &old, &new, &mut p[0], &mut q[0]).unwrap();
+	let refs : Vec<&[u8]> = data.iter().map(|d| &d[..]).collect();
+	let (mut p2, mut q2) = (vec![0u8; 50], vec![0u8; 50]);
+	gen_syndrome(&refs, &mut p2, &mut q2).unwrap();
+	assert_eq!(p[0], p2);
+	assert_eq!(q[0], q2);
+    }
+
+    #[test]
+    fn errors() {
+	let good = stripe(4, 8);
+	let mut s : Vec<Option<Vec<u8>>> = good.iter().cloned().map(Some).collect();
+	s[0] = None; s[1] = None; s[2] = None;
+	assert_eq!(recover(&mut s), Err(Raid6Error::TooManyFailures));
+	s[2] = Some(vec![0; 7]);
+	assert_eq!(recover(&mut s), Err(Raid6Error::SizeMismatch));
+	let (mut p, mut q) = ([0u8; 8], [0u8; 8]);
+	assert_eq!(gen_syndrome(&[], &mut p, &mut q), Err(Raid6Error::BadStripe));
+	assert_eq!(gen_syndrome(&[&[0u8; 7]], &mut p, &mut q), Err(Raid6Error::SizeMismatch));
+
+	// largest stripe still works
+	let big = stripe(255, 4);
+	let mut s : Vec<Option<Vec<u8>>> = big.iter().cloned().map(Some).collect();
+	s[0] = None; s[254] = None;
+	recover(&mut s).unwrap();
+	assert_eq!(s[254].as_ref(), Some(&big[254]));
+    }
+}
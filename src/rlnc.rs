@@ -0,0 +1,496 @@
+//! # Random linear network coding
+//!
+//! Data is split into *generations* of k equal-sized source packets.
+//! An [Encoder] sends out coded packets, each a random linear
+//! combination of the sources along with its coefficient vector. A
+//! [Recoder] in the middle of the network mixes whatever coded
+//! packets it has received into new ones, without decoding first. A
+//! [Decoder] collects packets with an [OnlineDecoder] until it has k
+//! linearly independent ones, and then has the sources.
+//!
+//! Coefficient vectors can be *dense* (every coefficient random) or
+//! *sparse* (only a few non-zero), picked with [Coefficients]. Sparse
+//! vectors make decoding cheaper, at the cost of a few more packets
+//! needed before the decoder reaches full rank.
+//!
+//! This works over any field whose elements can be read from bytes,
+//! but is meant for GF(2<sup>8</sup>) and GF(2<sup>16</sup>), which
+//! are the only ones the header format can describe. Randomness
+//! comes from a [RandomSource], as in [crate::shamir].
+//!
+//! ```rust
+//! use guff::good::new_gf8_0x11b;
+//! use guff::rlnc::{Encoder, Recoder, Decoder, Coefficients, CodedPacket};
+//!
+//! # let mut state = 1u64;
+//! # let mut rng = |buf : &mut [u8]| for b in buf.iter_mut() {
+//! #     state ^= state << 13; state ^= state >> 7; state ^= state << 17;
+//! #     *b = (state >> 24) as u8;
+//! # };
+//! let f = new_gf8_0x11b();
+//! let sources : Vec<Vec<u8>> = (0..8).map(|i| vec![i as u8; 100]).collect();
+//! let enc = Encoder::new(7, sources.clone(), Coefficients::Dense).unwrap();
+//!
+//! let mut relay = Recoder::new(&f, 7, 8, 100).unwrap();
+//! let mut dec = Decoder::new(&f, 7, 8, 100).unwrap();
+//! while !dec.is_complete() {
+//!     let pkt = enc.next_packet(&f, &mut rng);
+//!     relay.push(&f, &pkt).unwrap();
+//!     // packets go over the wire in the compact header format
+//!     let wire = relay.next_packet(&f, &mut rng).unwrap().to_bytes().unwrap();
+//!     dec.push(&f, &CodedPacket::from_bytes(&wire).unwrap()).unwrap();
+//! }
+//! assert_eq!(dec.into_sources().unwrap(), sources);
+//! ```
+//!
+//! ## Packet format
+//!
+//! All multi-byte fields are big-endian.
+//!
+//! | bytes | contents                                           |
+//! |-------|----------------------------------------------------|
+//! | 1     | flags: bit 0 set for sparse, bit 1 set for GF(2<sup>16</sup>) |
+//! | 2     | generation id                                      |
+//! | 2     | generation size k                                  |
+//! | ...   | dense: k coefficients                              |
+//! |       | sparse: 2-byte count c, then c (2-byte index, coefficient) pairs |
+//! | ...   | payload, up to the end of the packet               |
+//!
+//! [CodedPacket::to_bytes] uses whichever of the two coefficient
+//! encodings is shorter.
+
+use crate::GaloisField;
+use crate::online::OnlineDecoder;
+use crate::shamir::RandomSource;
+use crate::bytes::{ElementBytes, Endian, elements_from_bytes, elements_to_bytes};
+
+use num::Zero;
+use std::fmt;
+
+/// How coded packets choose their coefficients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coefficients {
+    /// Every coefficient uniformly random
+    Dense,
+    /// This many random positions get random non-zero coefficients,
+    /// and the rest are zero
+    Sparse(usize),
+}
+
+/// Errors from network coding operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RlncError {
+    /// Bad generation size, source sizes or sparsity
+    BadParameters,
+    /// Packet belongs to some other generation
+    WrongGeneration,
+    /// Packet has the wrong number of coefficients or payload size,
+    /// or is malformed
+    BadPacket,
+    /// Packet was coded over a different field
+    WrongField,
+}
+
+impl fmt::Display for RlncError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    RlncError::BadParameters   => write!(f, "bad network coding parameters"),
+	    RlncError::WrongGeneration => write!(f, "packet is from another generation"),
+	    RlncError::BadPacket       => write!(f, "malformed packet"),
+	    RlncError::WrongField      => write!(f, "packet is from another field"),
+	}
+    }
+}
+
+impl std::error::Error for RlncError {}
+
+/// A coded packet: coefficient vector plus payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodedPacket<E> {
+    /// Generation this packet belongs to
+    pub generation : u16,
+    /// One coefficient per source packet
+    pub coeffs     : Vec<E>,
+    /// The same combination of the source payloads
+    pub payload    : Vec<E>,
+}
+
+const FLAG_SPARSE : u8 = 1;
+const FLAG_WIDE   : u8 = 2;
+
+impl<E> CodedPacket<E> where E : ElementBytes {
+
+    /// Serialise in the compact header format
+    ///
+    /// The header can only describe GF(2<sup>8</sup>) and
+    /// GF(2<sup>16</sup>) elements and up to 65535 coefficients, so
+    /// wider elements give [RlncError::WrongField] and longer
+    /// coefficient vectors [RlncError::BadPacket].
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RlncError> {
+	let b = E::BYTES;
+	let k = self.coeffs.len();
+	if b > 2 { return Err(RlncError::WrongField) }
+	if k > 65535 { return Err(RlncError::BadPacket) }
+	let nonzero : Vec<(usize, E)> = self.coeffs.iter().cloned().enumerate()
+	    .filter(|(_, c)| *c != E::zero()).collect();
+	let sparse = 2 + nonzero.len() * (2 + b) < k * b;
+
+	let mut out = Vec::with_capacity(5 + k * b + self.payload.len() * b);
+	let mut flags = if b == 2 { FLAG_WIDE } else { 0 };
+	if sparse { flags |= FLAG_SPARSE }
+	out.push(flags);
+	out.extend_from_slice(&self.generation.to_be_bytes());
+	out.extend_from_slice(&(k as u16).to_be_bytes());
+	let mut elem = vec![0u8; b];
+	if sparse {
+	    out.extend_from_slice(&(nonzero.len() as u16).to_be_bytes());
+	    for (i, c) in nonzero {
+		out.extend_from_slice(&(i as u16).to_be_bytes());
+		c.write(&mut elem, Endian::Big);
+		out.extend_from_slice(&elem);
+	    }
+	} else {
+	    let start = out.len();
+	    out.resize(start + k * b, 0);
+	    elements_to_bytes(&self.coeffs, &mut out[start..], Endian::Big);
+	}
+	let start = out.len();
+	out.resize(start + self.payload.len() * b, 0);
+	elements_to_bytes(&self.payload, &mut out[start..], Endian::Big);
+	Ok(out)
+    }
+
+    /// Parse a packet in the compact header format
+    pub fn from_bytes(bytes : &[u8]) -> Result<CodedPacket<E>, RlncError> {
+	let b = E::BYTES;
+	if bytes.len() < 5 { return Err(RlncError::BadPacket) }
+	let flags = bytes[0];
+	if flags & !(FLAG_SPARSE | FLAG_WIDE) != 0 { return Err(RlncError::BadPacket) }
+	let wide = flags & FLAG_WIDE != 0;
+	if wide != (b == 2) || b > 2 { return Err(RlncError::WrongField) }
+	let generation = u16::from_be_bytes([bytes[1], bytes[2]]);
+	let k = u16::from_be_bytes([bytes[3], bytes[4]]) as usize;
+	let mut rest = &bytes[5..];
+
+	let coeffs = if flags & FLAG_SPARSE != 0 {
+	    if rest.len() < 2 { return Err(RlncError::BadPacket) }
+	    let c = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+	    rest = &rest[2..];
+	    if rest.len() < c * (2 + b) { return Err(RlncError::BadPacket) }
+	    let mut coeffs = vec![E::zero(); k];
+	    for entry in rest[..c * (2 + b)].chunks_exact(2 + b) {
+		let i = u16::from_be_bytes([entry[0], entry[1]]) as usize;
+		if i >= k { return Err(RlncError::BadPacket) }
+		coeffs[i] = E::read(&entry[2..], Endian::Big);
+	    }
+	    rest = &rest[c * (2 + b)..];
+	    coeffs
+	} else {
+	    if rest.len() < k * b { return Err(RlncError::BadPacket) }
+	    let coeffs = elements_from_bytes(&rest[..k * b], Endian::Big);
+	    rest = &rest[k * b..];
+	    coeffs
+	};
+	if rest.len() % b != 0 { return Err(RlncError::BadPacket) }
+	Ok(CodedPacket { generation, coeffs, payload : elements_from_bytes(rest, Endian::Big) })
+    }
+}
+
+// uniformly random element
+fn random_element<E, R>(rng : &mut R) -> E
+where E : ElementBytes, R : RandomSource + ?Sized
+{
+    let mut buf = [0u8; 4];
+    rng.fill_bytes(&mut buf[..E::BYTES]);
+    E::read(&buf, Endian::Big)
+}
+
+fn random_nonzero<E, R>(rng : &mut R) -> E
+where E : ElementBytes, R : RandomSource + ?Sized
+{
+    loop {
+	let e : E = random_element(rng);
+	if e != E::zero() { return e }
+    }
+}
+
+// random coefficient vector of length k
+fn random_coeffs<E, R>(k : usize, mode : Coefficients, rng : &mut R) -> Vec<E>
+where E : ElementBytes, R : RandomSource + ?Sized
+{
+    match mode {
+	Coefficients::Dense => {
+	    let mut buf = vec![0u8; k * E::BYTES];
+	    rng.fill_bytes(&mut buf);
+	    elements_from_bytes(&buf, Endian::Big)
+	},
+	Coefficients::Sparse(d) => {
+	    let mut coeffs = vec![E::zero(); k];
+	    let mut placed = 0;
+	    while placed < d {
+		let mut buf = [0u8; 4];
+		rng.fill_bytes(&mut buf);
+		let i = (u32::from_be_bytes(buf) as usize) % k;
+		if coeffs[i] == E::zero() {
+		    coeffs[i] = random_nonzero(rng);
+		    placed += 1;
+		}
+	    }
+	    coeffs
+	}
+    }
+}
+
+/// Source of coded packets for one generation
+pub struct Encoder<E> {
+    generation : u16,
+    sources    : Vec<Vec<E>>,
+    mode       : Coefficients,
+}
+
+impl<E> Encoder<E> where E : ElementBytes {
+
+    /// New encoder for a generation of equal-sized source packets
+    pub fn new(generation : u16, sources : Vec<Vec<E>>, mode : Coefficients)
+	       -> Result<Encoder<E>, RlncError> {
+	let k = sources.len();
+	if k == 0 || k > 65535 { return Err(RlncError::BadParameters) }
+	if sources.iter().any(|s| s.len() != sources[0].len()) {
+	    return Err(RlncError::BadParameters)
+	}
+	if let Coefficients::Sparse(d) = mode {
+	    if d == 0 || d > k { return Err(RlncError::BadParameters) }
+	}
+	Ok(Encoder { generation, sources, mode })
+    }
+
+    /// Generation size
+    pub fn k(&self) -> usize { self.sources.len() }
+
+    /// Source packet i, uncoded (its coefficients are a unit vector)
+    pub fn systematic_packet(&self, i : usize) -> CodedPacket<E> {
+	let mut coeffs = vec![E::zero(); self.k()];
+	coeffs[i] = E::one();
+	CodedPacket { generation : self.generation, coeffs, payload : self.sources[i].clone() }
+    }
+
+    /// A new random combination of the source packets
+    pub fn next_packet<G, R>(&self, f : &G, rng : &mut R) -> CodedPacket<E>
+    where G : GaloisField<E = E>, R : RandomSource + ?Sized
+    {
+	let coeffs = random_coeffs(self.k(), self.mode, rng);
+	let inputs : Vec<&[E]> = self.sources.iter().map(|s| &s[..]).collect();
+	let mut payload = vec![E::zero(); self.sources[0].len()];
+	f.matrix_apply(&coeffs, &inputs, &mut [&mut payload[..]]);
+	CodedPacket { generation : self.generation, coeffs, payload }
+    }
+}
+
+// check a packet matches the generation we're working on
+fn check_packet<E : ElementBytes>(p : &CodedPacket<E>, generation : u16, k : usize, len : usize)
+				  -> Result<(), RlncError> {
+    if p.generation != generation { return Err(RlncError::WrongGeneration) }
+    if p.coeffs.len() != k || p.payload.len() != len { return Err(RlncError::BadPacket) }
+    Ok(())
+}
+
+/// Mixes received coded packets into new ones without decoding
+///
+/// Only innovative packets are kept, so the recoder never stores
+/// more than k of them.
+pub struct Recoder<G> where G : GaloisField {
+    generation : u16,
+    k          : usize,
+    len        : usize,
+    held       : Vec<CodedPacket<G::E>>,
+    // just for telling whether a packet is innovative
+    tracker    : OnlineDecoder<G>,
+}
+
+impl<G> Recoder<G> where G : GaloisField, G::E : ElementBytes {
+
+    /// New recoder for generation size k and payload length `len`
+    ///
+    /// k must be between 1 and 65535, as for [Encoder::new].
+    pub fn new(f : &G, generation : u16, k : usize, len : usize)
+	       -> Result<Recoder<G>, RlncError> {
+	if k == 0 || k > 65535 { return Err(RlncError::BadParameters) }
+	Ok(Recoder { generation, k, len, held : Vec::new(),
+		     tracker : OnlineDecoder::new(f, k, 0) })
+    }
+
+    /// Number of linearly independent packets held
+    pub fn rank(&self) -> usize { self.held.len() }
+
+    /// Take in a coded packet; returns whether it was innovative
+    pub fn push(&mut self, f : &G, p : &CodedPacket<G::E>) -> Result<bool, RlncError> {
+	check_packet(p, self.generation, self.k, self.len)?;
+	if self.held.len() == self.k { return Ok(false) }
+	// OnlineDecoder only needs the coefficients to track rank
+	let innovative = self.tracker.push(f, &p.coeffs, &[]);
+	if innovative { self.held.push(p.clone()) }
+	Ok(innovative)
+    }
+
+    /// A new random combination of the packets held so far, or
+    /// `None` if nothing has been received yet
+    pub fn next_packet<R>(&self, f : &G, rng : &mut R) -> Option<CodedPacket<G::E>>
+    where R : RandomSource + ?Sized
+    {
+	if self.held.is_empty() { return None }
+	let mix : Vec<G::E> = random_coeffs(self.held.len(), Coefficients::Dense, rng);
+	let mut coeffs  = vec![G::E::zero(); self.k];
+	let mut payload = vec![G::E::zero(); self.len];
+	for (p, r) in self.held.iter().zip(mix) {
+	    f.vec_add_scaled_vec_in_place(&mut coeffs, &p.coeffs, r);
+	    f.vec_add_scaled_vec_in_place(&mut payload, &p.payload, r);
+	}
+	Some(CodedPacket { generation : self.generation, coeffs, payload })
+    }
+}
+
+/// Decoder for one generation
+pub struct Decoder<G> where G : GaloisField {
+    generation : u16,
+    len        : usize,
+    inner      : OnlineDecoder<G>,
+}
+
+impl<G> Decoder<G> where G : GaloisField, G::E : ElementBytes {
+
+    /// New decoder for generation size k and payload length `len`
+    ///
+    /// k must be between 1 and 65535, as for [Encoder::new].
+    pub fn new(f : &G, generation : u16, k : usize, len : usize)
+	       -> Result<Decoder<G>, RlncError> {
+	if k == 0 || k > 65535 { return Err(RlncError::BadParameters) }
+	Ok(Decoder { generation, len, inner : OnlineDecoder::new(f, k, len) })
+    }
+
+    /// Generation size
+    pub fn k(&self) -> usize { self.inner.k() }
+
+    /// Number of linearly independent packets received
+    pub fn rank(&self) -> usize { self.inner.rank() }
+
+    /// Whether all source packets are available
+    pub fn is_complete(&self) -> bool { self.inner.is_complete() }
+
+    /// Take in a coded packet; returns whether it was innovative
+    pub fn push(&mut self, f : &G, p : &CodedPacket<G::E>) -> Result<bool, RlncError> {
+	check_packet(p, self.generation, self.k(), self.len)?;
+	Ok(self.inner.push(f, &p.coeffs, &p.payload))
+    }
+
+    /// Decoded source packet i, once complete
+    pub fn source(&self, i : usize) -> Option<&[G::E]> { self.inner.source(i) }
+
+    /// All the source packets, once complete
+    pub fn into_sources(self) -> Option<Vec<Vec<G::E>>> { self.inner.into_sources() }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::good::{new_gf8_0x11b, new_gf16_0x1002b};
+
+    fn test_rng(seed : u64) -> impl FnMut(&mut [u8]) {
+	let mut state = seed | 1;
+	move |buf : &mut [u8]| for b in buf.iter_mut() {
+	    state ^= state << 13; state ^= state >> 7; state ^= state << 17;
+	    *b = (state >> 24) as u8;
+	}
+    }
+
+    #[test]
+    fn dense_end_to_end_gf16() {
+	let f = new_gf16_0x1002b();
+	let mut rng = test_rng(1);
+	let sources : Vec<Vec<u16>> = (0..20).map(|i| (0..64).map(|j| (i * 1000 + j) as u16).collect()).collect();
+	let enc = Encoder::new(3, sources.clone(), Coefficients::Dense).unwrap();
+	let mut dec = Decoder::new(&f, 3, 20, 64).unwrap();
+	let mut sent = 0;
+	while !dec.is_complete() {
+	    let wire = enc.next_packet(&f, &mut rng).to_bytes().unwrap();
+	    assert_eq!(wire[0], FLAG_WIDE);
+	    dec.push(&f, &CodedPacket::from_bytes(&wire).unwrap()).unwrap();
+	    sent += 1;
+	}
+	// over GF(2^16) a dense packet is almost never redundant
+	assert_eq!(sent, 20);
+	assert_eq!(dec.into_sources().unwrap(), sources);
+    }
+
+    #[test]
+    fn sparse_with_recoding() {
+	let f = new_gf8_0x11b();
+	let mut rng = test_rng(2);
+	let sources : Vec<Vec<u8>> = (0..32).map(|i| vec![(i * 7) as u8; 40]).collect();
+	let enc = Encoder::new(9, sources.clone(), Coefficients::Sparse(3)).unwrap();
+	let pkt = enc.next_packet(&f, &mut rng);
+	assert_eq!(pkt.coeffs.iter().filter(|c| **c != 0).count(), 3);
+	let wire = pkt.to_bytes().unwrap();
+	assert_eq!(wire[0], FLAG_SPARSE);
+	assert_eq!(wire.len(), 5 + 2 + 3 * 3 + 40);
+	assert_eq!(CodedPacket::from_bytes(&wire).unwrap(), pkt);
+
+	// two relays, each seeing part of the traffic
+	let mut a = Recoder::new(&f, 9, 32, 40).unwrap();
+	let mut b = Recoder::new(&f, 9, 32, 40).unwrap();
+	let mut dec = Decoder::new(&f, 9, 32, 40).unwrap();
+	let mut n = 0;
+	while !dec.is_complete() {
+	    let p = enc.next_packet(&f, &mut rng);
+	    if n % 2 == 0 { a.push(&f, &p).unwrap(); } else { b.push(&f, &p).unwrap(); }
+	    let relay = if n % 3 == 0 { &a } else { &b };
+	    if let Some(r) = relay.next_packet(&f, &mut rng) { dec.push(&f, &r).unwrap(); }
+	    n += 1;
+	    assert!(n < 500);
+	}
+	assert_eq!(dec.source(31), Some(&sources[31][..]));
+    }
+
+    #[test]
+    fn recoder_drops_non_innovative() {
+	let f = new_gf8_0x11b();
+	let sources = vec![vec![1u8, 2], vec![3, 4]];
+	let enc = Encoder::new(0, sources, Coefficients::Dense).unwrap();
+	let mut r = Recoder::new(&f, 0, 2, 2).unwrap();
+	assert!(r.next_packet(&f, &mut test_rng(3)).is_none());
+	assert!(r.push(&f, &enc.systematic_packet(0)).unwrap());
+	assert!(!r.push(&f, &enc.systematic_packet(0)).unwrap());
+	assert!(r.push(&f, &enc.systematic_packet(1)).unwrap());
+	assert_eq!(r.rank(), 2);
+    }
+
+    #[test]
+    fn errors() {
+	let f = new_gf8_0x11b();
+	assert_eq!(Encoder::<u8>::new(0, vec![], Coefficients::Dense).err(), Some(RlncError::BadParameters));
+	assert_eq!(Encoder::new(0, vec![vec![1u8], vec![]], Coefficients::Dense).err(),
+		   Some(RlncError::BadParameters));
+	assert_eq!(Encoder::new(0, vec![vec![1u8]], Coefficients::Sparse(2)).err(),
+		   Some(RlncError::BadParameters));
+
+	let enc = Encoder::new(5, vec![vec![1u8, 2]; 3], Coefficients::Dense).unwrap();
+	assert_eq!(Decoder::new(&f, 5, 0, 2).err(), Some(RlncError::BadParameters));
+	assert_eq!(Decoder::new(&f, 5, 65536, 2).err(), Some(RlncError::BadParameters));
+	assert_eq!(Recoder::new(&f, 5, 65536, 2).err(), Some(RlncError::BadParameters));
+	let mut dec = Decoder::new(&f, 6, 3, 2).unwrap();
+	assert_eq!(dec.push(&f, &enc.systematic_packet(0)), Err(RlncError::WrongGeneration));
+	let mut dec = Decoder::new(&f, 5, 3, 3).unwrap();
+	assert_eq!(dec.push(&f, &enc.systematic_packet(0)), Err(RlncError::BadPacket));
+
+	let wire = enc.systematic_packet(1).to_bytes().unwrap();
+	assert_eq!(CodedPacket::<u16>::from_bytes(&wire).err(), Some(RlncError::WrongField));
+	let wide = CodedPacket { generation : 0, coeffs : vec![1u32], payload : vec![2u32] };
+	assert_eq!(wide.to_bytes().err(), Some(RlncError::WrongField));
+	let long = CodedPacket { generation : 0, coeffs : vec![1u8; 65536], payload : vec![] };
+	assert_eq!(long.to_bytes().err(), Some(RlncError::BadPacket));
+	assert_eq!(CodedPacket::<u8>::from_bytes(&wire[..4]).err(), Some(RlncError::BadPacket));
+	let mut bad = wire.clone();
+	bad[0] = 0x80;
+	assert_eq!(CodedPacket::<u8>::from_bytes(&bad).err(), Some(RlncError::BadPacket));
+    }
+}
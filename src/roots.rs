@@ -0,0 +1,315 @@
+//! # Finding roots of polynomials over GF(2<sup>x</sup>)
+//!
+//! Two methods are provided:
+//!
+//! * [chien_search], which simply tries every non-zero element of
+//!   the field, but does so using only one multiplication per
+//!   coefficient per element. This is the method of choice for fields
+//!   up to GF(2<sup>16</sup>).
+//!
+//! * [trace_roots], the Berlekamp trace algorithm, which recursively
+//!   splits the polynomial using gcds with the trace function. Its
+//!   running time depends on the degree of the polynomial and the
+//!   order of the field, not on the number of field elements, so it
+//!   is the only practical choice for GF(2<sup>32</sup>) and beyond.
+//!
+//! [find_roots] picks whichever is appropriate for the field and
+//! also counts the multiplicity of each root:
+//!
+//! ```rust
+//! use guff::{new_gf8, new_gf32};
+//! use guff::poly;
+//! use guff::roots::find_roots;
+//!
+//! let f = new_gf8(0x11b, 0x1b);
+//! let p = poly::from_roots(&f, &[7, 9, 7, 0]);
+//! assert_eq!(find_roots(&f, &p), vec![(0, 1), (7, 2), (9, 1)]);
+//!
+//! let f = new_gf32(0x1_0000_008d, 0x8d);
+//! let p = poly::from_roots(&f, &[0xdead_beef, 3]);
+//! assert_eq!(find_roots(&f, &p), vec![(3, 1), (0xdead_beef, 1)]);
+//! ```
+//!
+//! Polynomials are in the format described in [crate::poly].
+
+use crate::GaloisField;
+use crate::poly::{self, Poly};
+
+use num::{One,Zero,FromPrimitive};
+
+/// Largest field order for which [find_roots] uses [chien_search]
+pub const CHIEN_MAX_ORDER : u16 = 16;
+
+// Distinct prime factors of 2**order - 1, by trial division. Even for
+// GF(2**32) this is quick, since the largest factor is 65537. (Also
+// used for testing binary polynomials for primitivity, where it can
+// be slow for some orders above 32.)
+pub(crate) fn group_order_factors(order : u16) -> Vec<u64> {
+    assert!(order > 0 && order <= 64);
+    let mut n : u64 = u64::MAX >> (64 - order);
+    let mut factors = Vec::new();
+    let mut p = 3u64;		// 2**order - 1 is odd
+    while (p as u128) * (p as u128) <= n as u128 {
+	if n % p == 0 {
+	    factors.push(p);
+	    while n % p == 0 { n /= p }
+	}
+	p += 2;
+    }
+    if n > 1 { factors.push(n) }
+    factors
+}
+
+/// Test whether g generates the multiplicative group of the field
+pub fn is_generator<G>(f : &G, g : G::E) -> bool
+where G : GaloisField
+{
+    let zero = G::E::zero();
+    let one  = G::E::one();
+    if g == zero { return false }
+    let group = (1u64 << G::ORDER) - 1;
+    if group == 1 { return g == one }
+    // g generates the group iff g**(group/p) != 1 for every prime
+    // factor p of the group order
+    group_order_factors(G::ORDER).iter().all(|p| {
+	let e = G::EE::from_u64(group / p).unwrap();
+	f.pow(g, e) != one
+    })
+}
+
+/// Find the smallest generator of the field's multiplicative group
+///
+/// For a primitive field polynomial this is always `2` (ie, x).
+pub fn find_generator<G>(f : &G) -> G::E
+where G : GaloisField
+{
+    let mut g = G::E::one() + G::E::one();
+    loop {
+	if is_generator(f, g) { return g }
+	// no need to guard against overflow: every field has
+	// generators, and about half of all elements are generators
+	g = g + G::E::one();
+    }
+}
+
+/// Find all distinct roots of a polynomial by Chien search
+///
+/// Rather than evaluating p(x) from scratch at every point, we step
+/// x through g<sup>0</sup>, g<sup>1</sup>, ... for a generator g, and
+/// keep each term c<sub>i</sub>x<sup>i</sup> updated by multiplying
+/// it by the constant g<sup>i</sup>. Roots are returned in ascending
+/// order of value. The zero polynomial has no roots (by convention).
+///
+/// Running time is proportional to the size of the field, so this
+/// is only sensible for fields up to GF(2<sup>16</sup>).
+pub fn chien_search<G>(f : &G, p : &[G::E]) -> Vec<G::E>
+where G : GaloisField
+{
+    let zero = G::E::zero();
+    let one  = G::E::one();
+    let mut roots = Vec::new();
+    let deg = match poly::degree(p) {
+	Some(d) if d > 0 => d,
+	_ => return roots
+    };
+    if p[0] == zero { roots.push(zero) }
+
+    let g = find_generator(f);
+    // step[i] = g**i
+    let mut step = Vec::with_capacity(deg + 1);
+    let mut s = one;
+    for _ in 0..=deg {
+	step.push(s);
+	s = f.mul(s, g);
+    }
+    let mut terms : Poly<G> = p[..=deg].to_vec();
+    let mut x = one;
+    let group = (1u64 << G::ORDER) - 1;
+    for _ in 0..group {
+	if f.vec_sum_elements(&terms) == zero { roots.push(x) }
+	for (t, s) in terms.iter_mut().zip(step.iter()) {
+	    *t = f.mul(*t, *s)
+	}
+	x = f.mul(x, g);
+    }
+    roots.sort();
+    roots
+}
+
+// Split a monic, square-free poly whose roots all lie in the field
+// (ie, which divides x**q - x) by taking gcds with Tr(βx) for each
+// basis element β = 2**k in turn.
+fn trace_split<G>(f : &G, p : Poly<G>, k : u16, roots : &mut Vec<G::E>)
+where G : GaloisField
+{
+    match poly::degree(&p) {
+	None | Some(0) => return,
+	// monic linear factor x + c has root c
+	Some(1) => { roots.push(p[0]); return },
+	_ => ()
+    }
+    // Distinct roots r, s always differ in Tr(βr) for some basis
+    // element β, so we can't run out of basis before the split is
+    // complete.
+    assert!(k < G::ORDER, "trace split failed (polynomial not square-free?)");
+
+    let beta = G::E::one() << k as usize;
+    let y0 = poly::rem(f, &[G::E::zero(), beta], &p);
+    let mut y = y0.clone();
+    let mut trace = y0;
+    for _ in 1..G::ORDER {
+	y = poly::mulmod(f, &y, &y, &p);
+	trace = poly::add(f, &trace, &y);
+    }
+    let h = poly::gcd(f, &p, &trace);
+    let dh = poly::degree(&h).unwrap_or(0);
+    if dh == 0 || dh == p.len() - 1 {
+	trace_split(f, p, k + 1, roots)
+    } else {
+	let (other, _) = poly::divrem(f, &p, &h);
+	trace_split(f, h, k + 1, roots);
+	trace_split(f, other, k + 1, roots);
+    }
+}
+
+/// Find all distinct roots of a polynomial with the Berlekamp trace
+/// algorithm
+///
+/// First, gcd(p, x<sup>q</sup> - x) extracts the product of all the
+/// distinct linear factors of p. This is then split recursively using
+/// the trace map Tr(z) = z + z<sup>2</sup> + z<sup>4</sup> + ... Roots
+/// are returned in ascending order of value.
+pub fn trace_roots<G>(f : &G, p : &[G::E]) -> Vec<G::E>
+where G : GaloisField
+{
+    let zero = G::E::zero();
+    let mut roots = Vec::new();
+    let mut p = poly::monic(f, p);
+    if p.len() < 2 { return roots }
+
+    // take out root zero
+    if p[0] == zero {
+	roots.push(zero);
+	let low = p.iter().position(|c| *c != zero).unwrap();
+	p.drain(..low);
+    }
+
+    // x**q mod p by repeated squaring
+    let x = vec![zero, G::E::one()];
+    let mut xq = poly::rem(f, &x, &p);
+    for _ in 0..G::ORDER {
+	xq = poly::mulmod(f, &xq, &xq, &p);
+    }
+    let linear = poly::gcd(f, &p, &poly::add(f, &xq, &x));
+
+    trace_split(f, linear, 0, &mut roots);
+    roots.sort();
+    roots
+}
+
+/// Number of times (x - r) divides p
+pub fn multiplicity<G>(f : &G, p : &[G::E], r : G::E) -> usize
+where G : GaloisField
+{
+    let mut p = p.to_vec();
+    poly::normalise(&mut p);
+    let mut count = 0;
+    while !p.is_empty() {
+	let (q, rem) = poly::divrem(f, &p, &[r, G::E::one()]);
+	if !rem.is_empty() { break }
+	count += 1;
+	p = q;
+    }
+    count
+}
+
+/// Find all roots of a polynomial, along with their multiplicities
+///
+/// Uses [chien_search] for fields up to [CHIEN_MAX_ORDER] bits, and
+/// [trace_roots] for larger ones. Results are (root, multiplicity)
+/// pairs in ascending order of root.
+pub fn find_roots<G>(f : &G, p : &[G::E]) -> Vec<(G::E, usize)>
+where G : GaloisField
+{
+    let roots = if G::ORDER <= CHIEN_MAX_ORDER {
+	chien_search(f, p)
+    } else {
+	trace_roots(f, p)
+    };
+    roots.into_iter().map(|r| (r, multiplicity(f, p, r))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{new_gf4, new_gf8, new_gf16, new_gf32};
+    use crate::good::new_gf16_0x1002b;
+
+    #[test]
+    fn generators_of_gf16_0x13() {
+	// same list as in lib.rs generator_should_loop()
+	let f = new_gf4(19, 3);
+	let gens : Vec<u8> = (0..16).filter(|g| is_generator(&f, *g)).collect();
+	assert_eq!(gens, vec![2u8, 3, 4, 5, 9, 11, 13, 14]);
+    }
+
+    #[test]
+    fn generator_of_aes_field_is_3() {
+	// 0x11b is not primitive, so 2 is not a generator
+	let f = new_gf8(0x11b, 0x1b);
+	assert!(!is_generator(&f, 2));
+	assert_eq!(find_generator(&f), 3);
+    }
+
+    #[test]
+    fn chien_finds_all_roots() {
+	let f = new_gf8(0x11b, 0x1b);
+	let p = poly::from_roots(&f, &[0x53, 1, 0xca, 0xff, 0]);
+	assert_eq!(chien_search(&f, &p), vec![0, 1, 0x53, 0xca, 0xff]);
+    }
+
+    #[test]
+    fn chien_ignores_irreducible_factors() {
+	let f = new_gf16_0x1002b();
+	// x² + x + c has no roots in the field when Tr(c) = 1
+	let trace = |c : u16| {
+	    let (mut t, mut y) = (c, c);
+	    for _ in 1..16 { y = f.mul(y, y); t ^= y }
+	    t
+	};
+	let c = (1u16..).find(|c| trace(*c) == 1).unwrap();
+	assert!(chien_search(&f, &[c, 1, 1]).is_empty());
+	let p = poly::mul(&f, &[c, 1, 1], &poly::from_roots(&f, &[4321]));
+	assert_eq!(chien_search(&f, &p), vec![4321]);
+    }
+
+    #[test]
+    fn trace_agrees_with_chien() {
+	let f = new_gf16(0x1002b, 0x2b);
+	let quad = [0x1234u16, 1, 1];
+	let p = poly::mul(&f, &quad,
+			  &poly::from_roots(&f, &[9, 0xfff0, 77, 0, 77]));
+	assert_eq!(trace_roots(&f, &p), chien_search(&f, &p));
+    }
+
+    #[test]
+    fn trace_roots_gf32() {
+	let f = new_gf32(0x1_0000_008d, 0x8d);
+	let want = vec![1u32, 0x8000_0000, 0x1234_5678, 0xffff_ffff];
+	let p = poly::from_roots(&f, &want);
+	assert_eq!(trace_roots(&f, &p), {
+	    let mut w = want.clone(); w.sort(); w
+	});
+    }
+
+    #[test]
+    fn find_roots_with_multiplicity() {
+	let f = new_gf32(0x1_0000_008d, 0x8d);
+	let p = poly::from_roots(&f, &[5, 6, 5, 5, 0, 0]);
+	assert_eq!(find_roots(&f, &p), vec![(0, 2), (5, 3), (6, 1)]);
+	let f = new_gf4(19, 3);
+	let p = poly::from_roots(&f, &[15, 15, 2]);
+	assert_eq!(find_roots(&f, &p), vec![(2, 1), (15, 2)]);
+    }
+}
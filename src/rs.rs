@@ -0,0 +1,574 @@
+//! # Systematic Reed-Solomon erasure coding
+//!
+//! A [ReedSolomon] codec with k data shards and m parity shards
+//! computes the parity so that the data can be rebuilt from *any* k
+//! of the n = k + m shards. It is systematic: the data shards are
+//! stored as they are, and only the parity shards are computed.
+//!
+//! The generator matrix is a Vandermonde matrix on the points 0, 1,
+//! ..., n-1, converted to systematic form with
+//! [Matrix::systematic_vandermonde]. Points have to be distinct, so
+//! GF(2<sup>8</sup>) allows up to 256 shards in total and
+//! GF(2<sup>16</sup>) up to 65536.
+//!
+//! Shards are slices of field elements, all the same length. For
+//! reconstruction, missing shards are passed in as `None`:
+//!
+//! ```rust
+//! use guff::good::new_gf8_0x11b;
+//! use guff::rs::ReedSolomon;
+//!
+//! let f = new_gf8_0x11b();
+//! let rs = ReedSolomon::new(&f, 4, 2).unwrap();
+//!
+//! let mut shards : Vec<Vec<u8>> = vec![
+//!     b"abcd".to_vec(), b"efgh".to_vec(), b"ijkl".to_vec(), b"mnop".to_vec(),
+//!     vec![0; 4], vec![0; 4],
+//! ];
+//! rs.encode_shards(&f, &mut shards).unwrap();
+//! assert!(rs.verify(&f, &shards).unwrap());
+//!
+//! // lose any two
+//! let mut damaged : Vec<Option<Vec<u8>>> = shards.iter().cloned().map(Some).collect();
+//! damaged[1] = None;
+//! damaged[4] = None;
+//! rs.reconstruct(&f, &mut damaged).unwrap();
+//! assert_eq!(damaged[1].as_ref().unwrap(), b"efgh");
+//! assert_eq!(damaged[4].as_ref().unwrap(), &shards[4]);
+//! ```
+//!
+//! ## Errors and erasures
+//!
+//! When corrupted symbols can turn up at unknown positions, use the
+//! classic BCH-view code [RsCode] instead. A codeword is a polynomial
+//! c(x) of length n that is a multiple of the generator polynomial
+//!
+//! g(x) = (x - α<sup>b</sup>)(x - α<sup>b+1</sup>) ...
+//! (x - α<sup>b+n-k-1</sup>)
+//!
+//! where α is the generator and b the first consecutive root. Both
+//! are parameters, since standards differ (CCSDS, DVB, QR codes ...).
+//! Element i of a codeword is the coefficient of x<sup>i</sup>: the
+//! n-k parity symbols come first, followed by the k message symbols.
+//!
+//! [RsCode::decode] corrects any combination of v errors and e
+//! erasures with 2v + e ≤ n - k, using syndromes, Berlekamp–Massey
+//! on the Forney syndromes, Chien search and Forney's formula:
+//!
+//! ```rust
+//! use guff::{GaloisField, new_gf8};
+//! use guff::rs::RsCode;
+//!
+//! let f = new_gf8(0x11d, 0x1d);
+//! let code = RsCode::new(&f, 255, 223, 1, 2).unwrap();
+//! let msg : Vec<u8> = (0..223).map(|i| i as u8).collect();
+//! let mut word = code.encode(&f, &msg).unwrap();
+//!
+//! // 10 errors and 12 erasures: 2·10 + 12 ≤ 32
+//! for i in 0..10 { word[i * 20] ^= 0x5a }
+//! let erasures : Vec<usize> = (0..12).map(|i| i * 20 + 7).collect();
+//! for e in erasures.iter() { word[*e] = 0 }
+//! code.decode(&f, &mut word, &erasures).unwrap();
+//! assert_eq!(&word[32..], &msg[..]);
+//! ```
+
+use crate::GaloisField;
+use crate::matrix::Matrix;
+use crate::poly::{self, Poly};
+use crate::bm::berlekamp_massey;
+
+use num::{Zero,One,FromPrimitive};
+use std::fmt;
+
+/// Errors from [ReedSolomon] operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RsError {
+    /// Need at least one data and one parity shard
+    BadParameters,
+    /// More shards than the field has distinct points
+    TooManyShards,
+    /// Number of shards passed in doesn't match the codec
+    WrongShardCount,
+    /// Shards are not all the same length
+    ShardSizeMismatch,
+    /// Fewer than k shards are present
+    TooFewShards,
+    /// Message or codeword has the wrong length
+    WrongLength,
+    /// Erasure position out of range or repeated
+    BadErasure,
+    /// Too many errors and erasures to correct
+    Uncorrectable,
+}
+
+impl fmt::Display for RsError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    RsError::BadParameters     => write!(f, "need at least one data and one parity shard"),
+	    RsError::TooManyShards     => write!(f, "too many shards for field size"),
+	    RsError::WrongShardCount   => write!(f, "wrong number of shards"),
+	    RsError::ShardSizeMismatch => write!(f, "shards differ in size"),
+	    RsError::TooFewShards      => write!(f, "too few shards present to reconstruct"),
+	    RsError::WrongLength       => write!(f, "wrong message or codeword length"),
+	    RsError::BadErasure        => write!(f, "bad erasure position"),
+	    RsError::Uncorrectable     => write!(f, "block is uncorrectable"),
+	}
+    }
+}
+
+impl std::error::Error for RsError {}
+
+/// Systematic Reed-Solomon erasure codec
+pub struct ReedSolomon<G> where G : GaloisField {
+    data_shards   : usize,
+    parity_shards : usize,
+    // full n×k generator; top k rows are the identity
+    generator     : Matrix<G>,
+}
+
+impl<G> ReedSolomon<G> where G : GaloisField {
+
+    /// New codec with the given numbers of data and parity shards
+    pub fn new(f : &G, data_shards : usize, parity_shards : usize)
+	       -> Result<ReedSolomon<G>, RsError> {
+	if data_shards == 0 || parity_shards == 0 { return Err(RsError::BadParameters) }
+	let n = data_shards + parity_shards;
+	if n as u64 > 1u64 << G::ORDER { return Err(RsError::TooManyShards) }
+	let points : Vec<G::E> = (0..n).map(|i| G::E::from_usize(i).unwrap()).collect();
+	let generator = Matrix::systematic_vandermonde(f, &points, data_shards)
+	    .map_err(|_| RsError::BadParameters)?;
+	Ok(ReedSolomon { data_shards, parity_shards, generator })
+    }
+
+    /// Number of data shards (k)
+    pub fn data_shards(&self) -> usize { self.data_shards }
+
+    /// Number of parity shards (m)
+    pub fn parity_shards(&self) -> usize { self.parity_shards }
+
+    /// Total number of shards (n = k + m)
+    pub fn total_shards(&self) -> usize { self.data_shards + self.parity_shards }
+
+    // coefficients for the parity rows only
+    fn parity_coeffs(&self) -> &[G::E] {
+	&self.generator.as_slice()[self.data_shards * self.data_shards ..]
+    }
+
+    /// Compute parity shards from data shards
+    pub fn encode(&self, f : &G, data : &[&[G::E]], parity : &mut [&mut [G::E]])
+		  -> Result<(), RsError> {
+	if data.len() != self.data_shards || parity.len() != self.parity_shards {
+	    return Err(RsError::WrongShardCount)
+	}
+	let len = data[0].len();
+	if data.iter().any(|s| s.len() != len) || parity.iter().any(|s| s.len() != len) {
+	    return Err(RsError::ShardSizeMismatch)
+	}
+	f.matrix_apply(self.parity_coeffs(), data, parity);
+	Ok(())
+    }
+
+    /// Compute parity in place: the first k shards are data and the
+    /// rest are overwritten with parity
+    pub fn encode_shards(&self, f : &G, shards : &mut [Vec<G::E>]) -> Result<(), RsError> {
+	if shards.len() != self.total_shards() { return Err(RsError::WrongShardCount) }
+	let (data, parity) = shards.split_at_mut(self.data_shards);
+	let data : Vec<&[G::E]> = data.iter().map(|s| &s[..]).collect();
+	let mut parity : Vec<&mut [G::E]> = parity.iter_mut().map(|s| &mut s[..]).collect();
+	self.encode(f, &data, &mut parity)
+    }
+
+    /// Check that the parity shards match the data shards
+    pub fn verify<S>(&self, f : &G, shards : &[S]) -> Result<bool, RsError>
+    where S : AsRef<[G::E]>
+    {
+	if shards.len() != self.total_shards() { return Err(RsError::WrongShardCount) }
+	let len = shards[0].as_ref().len();
+	if shards.iter().any(|s| s.as_ref().len() != len) {
+	    return Err(RsError::ShardSizeMismatch)
+	}
+	let data : Vec<&[G::E]> = shards[..self.data_shards].iter().map(|s| s.as_ref()).collect();
+	let mut fresh = vec![vec![G::E::zero(); len]; self.parity_shards];
+	let mut parity : Vec<&mut [G::E]> = fresh.iter_mut().map(|s| &mut s[..]).collect();
+	self.encode(f, &data, &mut parity)?;
+	Ok(fresh.iter().zip(&shards[self.data_shards..]).all(|(a, b)| &a[..] == b.as_ref()))
+    }
+
+    /// Rebuild all missing (`None`) shards
+    pub fn reconstruct(&self, f : &G, shards : &mut [Option<Vec<G::E>>]) -> Result<(), RsError> {
+	self.rebuild(f, shards, false)
+    }
+
+    /// Rebuild only the missing data shards, leaving missing parity
+    /// shards as `None`
+    pub fn reconstruct_data(&self, f : &G, shards : &mut [Option<Vec<G::E>>]) -> Result<(), RsError> {
+	self.rebuild(f, shards, true)
+    }
+
+    fn rebuild(&self, f : &G, shards : &mut [Option<Vec<G::E>>], data_only : bool)
+	       -> Result<(), RsError> {
+	let k = self.data_shards;
+	if shards.len() != self.total_shards() { return Err(RsError::WrongShardCount) }
+	let present : Vec<usize> = (0..shards.len()).filter(|i| shards[*i].is_some()).collect();
+	if present.len() < k { return Err(RsError::TooFewShards) }
+	let len = shards[present[0]].as_ref().unwrap().len();
+	if present.iter().any(|i| shards[*i].as_ref().unwrap().len() != len) {
+	    return Err(RsError::ShardSizeMismatch)
+	}
+	let missing_data : Vec<usize> = (0..k).filter(|i| shards[*i].is_none()).collect();
+
+	if !missing_data.is_empty() {
+	    // The generator is V·T⁻¹, where V is the Vandermonde matrix
+	    // on points 0..n and T its top k rows, so the inverse of the
+	    // rows we have is T·V_sub⁻¹. Only the rows of T for missing
+	    // data shards are needed, and V_sub⁻¹ has a closed form.
+	    let use_rows = &present[..k];
+	    let point = |i : usize| G::E::from_usize(i).unwrap();
+	    let sub : Vec<G::E> = use_rows.iter().map(|r| point(*r)).collect();
+	    let vinv = Matrix::vandermonde_inverse(f, &sub)
+		.expect("shard points are distinct");
+	    let want : Vec<G::E> = missing_data.iter().map(|d| point(*d)).collect();
+	    let coeffs = Matrix::vandermonde(f, &want, k).mul(f, &vinv);
+	    let coeffs = coeffs.as_slice();
+	    let mut out = vec![vec![G::E::zero(); len]; missing_data.len()];
+	    {
+		let inputs : Vec<&[G::E]> = use_rows.iter()
+		    .map(|r| &shards[*r].as_ref().unwrap()[..]).collect();
+		let mut outputs : Vec<&mut [G::E]> = out.iter_mut().map(|s| &mut s[..]).collect();
+		f.matrix_apply(coeffs, &inputs, &mut outputs);
+	    }
+	    for (d, s) in missing_data.iter().zip(out) { shards[*d] = Some(s) }
+	}
+	if data_only { return Ok(()) }
+
+	let missing_parity : Vec<usize> = (k..shards.len()).filter(|i| shards[*i].is_none()).collect();
+	if missing_parity.is_empty() { return Ok(()) }
+	let mut coeffs = Vec::with_capacity(missing_parity.len() * k);
+	for p in missing_parity.iter() { coeffs.extend_from_slice(self.generator.row(*p)) }
+	let mut out = vec![vec![G::E::zero(); len]; missing_parity.len()];
+	{
+	    let inputs : Vec<&[G::E]> = shards[..k].iter().map(|s| &s.as_ref().unwrap()[..]).collect();
+	    let mut outputs : Vec<&mut [G::E]> = out.iter_mut().map(|s| &mut s[..]).collect();
+	    f.matrix_apply(&coeffs, &inputs, &mut outputs);
+	}
+	for (p, s) in missing_parity.iter().zip(out) { shards[*p] = Some(s) }
+	Ok(())
+    }
+}
+
+/// Reed-Solomon code correcting both errors and erasures
+pub struct RsCode<G> where G : GaloisField {
+    n       : usize,
+    k       : usize,
+    fcr     : usize,
+    alpha   : G::E,
+    genpoly : Poly<G>,
+}
+
+impl<G> RsCode<G> where G : GaloisField {
+
+    /// New (n, k) code with first consecutive root α<sup>fcr</sup>
+    ///
+    /// The generator α must have multiplicative order at least n, so
+    /// that every codeword position has a distinct locator. Since
+    /// α<sup>2<sup>ORDER</sup>-1</sup> = 1, any fcr is accepted and
+    /// taken modulo 2<sup>ORDER</sup>-1.
+    pub fn new(f : &G, n : usize, k : usize, fcr : usize, alpha : G::E)
+	       -> Result<RsCode<G>, RsError> {
+	if k == 0 || k >= n { return Err(RsError::BadParameters) }
+	if (n as u64) >= 1u64 << G::ORDER { return Err(RsError::TooManyShards) }
+	// check order of alpha is at least n
+	let one = G::E::one();
+	let mut x = alpha;
+	for _ in 1..n {
+	    if x == one || x == G::E::zero() { return Err(RsError::BadParameters) }
+	    x = f.mul(x, alpha);
+	}
+	// α's order divides the group order, so exponents can be reduced
+	let fcr = (fcr as u64 % Self::group()) as usize;
+	let roots : Vec<G::E> = (0..n - k).map(|j| f.pow(alpha, Self::ee(fcr + j))).collect();
+	let genpoly = poly::from_roots(f, &roots);
+	Ok(RsCode { n, k, fcr, alpha, genpoly })
+    }
+
+    // size of the multiplicative group
+    fn group() -> u64 { (1u64 << G::ORDER) - 1 }
+
+    fn ee(x : usize) -> G::EE { G::EE::from_u64(x as u64 % Self::group()).unwrap() }
+
+    /// Codeword length
+    pub fn n(&self) -> usize { self.n }
+
+    /// Message length
+    pub fn k(&self) -> usize { self.k }
+
+    /// Generator polynomial, of degree n - k
+    pub fn generator_poly(&self) -> &[G::E] { &self.genpoly }
+
+    /// Systematic encoding: parity symbols followed by the message
+    pub fn encode(&self, f : &G, msg : &[G::E]) -> Result<Vec<G::E>, RsError> {
+	if msg.len() != self.k { return Err(RsError::WrongLength) }
+	let nsym = self.n - self.k;
+	let mut word = vec![G::E::zero(); self.n];
+	word[nsym..].copy_from_slice(msg);
+	let r = poly::rem(f, &word, &self.genpoly);
+	word[..r.len()].copy_from_slice(&r);
+	Ok(word)
+    }
+
+    /// Syndromes S<sub>j</sub> = r(α<sup>fcr+j</sup>) for j = 0, ..., n-k-1
+    pub fn syndromes(&self, f : &G, word : &[G::E]) -> Vec<G::E> {
+	(0..self.n - self.k)
+	    .map(|j| poly::eval(f, word, f.pow(self.alpha, Self::ee(self.fcr + j))))
+	    .collect()
+    }
+
+    /// Correct errors and erasures in place
+    ///
+    /// Returns the positions that were changed. On failure, `word`
+    /// is left untouched.
+    pub fn decode(&self, f : &G, word : &mut [G::E], erasures : &[usize])
+		  -> Result<Vec<usize>, RsError> {
+	let nsym = self.n - self.k;
+	let zero = G::E::zero();
+	let one  = G::E::one();
+	if word.len() != self.n { return Err(RsError::WrongLength) }
+	for (i, e) in erasures.iter().enumerate() {
+	    if *e >= self.n || erasures[..i].contains(e) { return Err(RsError::BadErasure) }
+	}
+	if erasures.len() > nsym { return Err(RsError::Uncorrectable) }
+
+	let synd = self.syndromes(f, word);
+	if synd.iter().all(|s| *s == zero) { return Ok(Vec::new()) }
+
+	// erasure locator Γ(x) = ∏ (1 - X_i x)
+	let locator = |pos : usize| f.pow(self.alpha, Self::ee(pos));
+	let mut gamma : Poly<G> = vec![one];
+	for e in erasures.iter() {
+	    gamma = poly::mul(f, &gamma, &[one, locator(*e)]);
+	}
+
+	// Forney syndromes Γ(x)S(x) mod x^nsym; past the first e terms
+	// they follow the recurrence given by the error locator
+	let mut t = poly::mul(f, &gamma, &synd);
+	t.resize(nsym.max(t.len()), zero);
+	let ne = erasures.len();
+	let (sigma, l) = berlekamp_massey(f, &t[ne..nsym]);
+	if 2 * l + ne > nsym { return Err(RsError::Uncorrectable) }
+
+	// errata locator and evaluator
+	let lambda = poly::mul(f, &sigma, &gamma);
+	let mut omega = poly::mul(f, &synd, &lambda);
+	omega.truncate(nsym);
+	let dlambda = poly::derivative(f, &lambda);
+	let nroots = poly::degree(&lambda).unwrap_or(0);
+
+	// Chien search over codeword positions only: x = α^-i. This
+	// costs O(n) rather than a scan of the whole field, which
+	// matters for shortened codes and is the only option for
+	// GF(2^32).
+	let alpha_inv = f.inv(self.alpha);
+	let mut x = one;
+	let mut fixes = Vec::with_capacity(nroots);
+	for i in 0..self.n {
+	    if poly::eval(f, &lambda, x) == zero {
+		// Forney: Y = X^(1-fcr) Ω(X^-1) / Λ'(X^-1)
+		let d = poly::eval(f, &dlambda, x);
+		if d == zero { return Err(RsError::Uncorrectable) }
+		let xi = f.inv(x);
+		let scale = if self.fcr == 0 { xi } else {
+		    f.inv(f.pow(xi, Self::ee(self.fcr - 1)))
+		};
+		let y = f.mul(scale, f.div(poly::eval(f, &omega, x), d));
+		fixes.push((i, y));
+	    }
+	    x = f.mul(x, alpha_inv);
+	}
+	if fixes.len() != nroots { return Err(RsError::Uncorrectable) }
+
+	let mut fixed = word.to_vec();
+	for (i, y) in fixes.iter() { fixed[*i] = fixed[*i] ^ *y }
+	if self.syndromes(f, &fixed).iter().any(|s| *s != zero) {
+	    return Err(RsError::Uncorrectable)
+	}
+	word.copy_from_slice(&fixed);
+	Ok(fixes.into_iter().filter(|(_, y)| *y != zero).map(|(i, _)| i).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::good::{new_gf8_0x11b, new_gf16_0x1002b};
+    use crate::{new_gf8, new_gf32};
+
+    fn make_shards<G : GaloisField>(f : &G, rs : &ReedSolomon<G>, len : usize) -> Vec<Vec<G::E>> {
+	let mut shards : Vec<Vec<G::E>> = (0..rs.total_shards()).map(|i| {
+	    (0..len).map(|j| G::E::from_usize((i * 131 + j * 7) % 251).unwrap()).collect()
+	}).collect();
+	rs.encode_shards(f, &mut shards).unwrap();
+	shards
+    }
+
+    #[test]
+    fn any_k_of_n() {
+	let f = new_gf8_0x11b();
+	let rs = ReedSolomon::new(&f, 4, 3).unwrap();
+	let shards = make_shards(&f, &rs, 100);
+	assert!(rs.verify(&f, &shards).unwrap());
+	for mask in 0u32..128 {
+	    if mask.count_ones() < 4 { continue }
+	    let mut damaged : Vec<Option<Vec<u8>>> = shards.iter().enumerate()
+		.map(|(i, s)| if mask & (1 << i) != 0 { Some(s.clone()) } else { None })
+		.collect();
+	    rs.reconstruct(&f, &mut damaged).unwrap();
+	    let fixed : Vec<Vec<u8>> = damaged.into_iter().map(|s| s.unwrap()).collect();
+	    assert_eq!(fixed, shards);
+	}
+    }
+
+    #[test]
+    fn data_only_reconstruction() {
+	let f = new_gf16_0x1002b();
+	let rs = ReedSolomon::new(&f, 5, 3).unwrap();
+	let shards = make_shards(&f, &rs, 50);
+	let mut damaged : Vec<Option<Vec<u16>>> = shards.iter().cloned().map(Some).collect();
+	damaged[0] = None;
+	damaged[3] = None;
+	damaged[6] = None;
+	rs.reconstruct_data(&f, &mut damaged).unwrap();
+	assert_eq!(damaged[0].as_ref(), Some(&shards[0]));
+	assert_eq!(damaged[3].as_ref(), Some(&shards[3]));
+	assert!(damaged[6].is_none());
+    }
+
+    #[test]
+    fn many_shards_gf16() {
+	let f = new_gf16_0x1002b();
+	let rs = ReedSolomon::new(&f, 200, 100).unwrap();
+	let shards = make_shards(&f, &rs, 8);
+	let mut damaged : Vec<Option<Vec<u16>>> = shards.iter().cloned().map(Some).collect();
+	for i in (0..300).step_by(3) { damaged[i] = None }
+	rs.reconstruct(&f, &mut damaged).unwrap();
+	assert!(damaged.iter().zip(&shards).all(|(a, b)| a.as_ref() == Some(b)));
+    }
+
+    #[test]
+    fn detects_corruption_and_errors() {
+	let f = new_gf8_0x11b();
+	assert_eq!(ReedSolomon::new(&f, 200, 57).err(), Some(RsError::TooManyShards));
+	assert!(ReedSolomon::new(&f, 200, 56).is_ok());
+	assert_eq!(ReedSolomon::new(&f, 0, 2).err(), Some(RsError::BadParameters));
+
+	let rs = ReedSolomon::new(&f, 3, 2).unwrap();
+	let mut shards = make_shards(&f, &rs, 10);
+	shards[1][5] ^= 1;
+	assert!(!rs.verify(&f, &shards).unwrap());
+	assert_eq!(rs.verify(&f, &shards[..4]).err(), Some(RsError::WrongShardCount));
+
+	let mut damaged : Vec<Option<Vec<u8>>> = shards.iter().cloned().map(Some).collect();
+	damaged[0] = None;
+	damaged[1] = None;
+	damaged[2] = None;
+	assert_eq!(rs.reconstruct(&f, &mut damaged).err(), Some(RsError::TooFewShards));
+	damaged[2] = Some(vec![0; 9]);
+	assert_eq!(rs.reconstruct(&f, &mut damaged).err(), Some(RsError::ShardSizeMismatch));
+    }
+
+    #[test]
+    fn rs_code_errors_and_erasures() {
+	// CCSDS-like parameters, and a non-standard generator/fcr
+	let f = new_gf8(0x11d, 0x1d);
+	for (fcr, alpha) in [(1usize, 2u8), (0, 2), (112, 0x4c)].iter() {
+	    let code = RsCode::new(&f, 60, 44, *fcr, *alpha).unwrap();
+	    let msg : Vec<u8> = (0..44).map(|i| (i * 37 + 11) as u8).collect();
+	    let clean = code.encode(&f, &msg).unwrap();
+	    assert!(code.syndromes(&f, &clean).iter().all(|s| *s == 0));
+	    assert_eq!(poly::rem(&f, &clean, code.generator_poly()), vec![]);
+	    for (nerr, ners) in [(8usize, 0usize), (0, 16), (5, 6), (3, 10), (1, 0)].iter() {
+		let mut word = clean.clone();
+		let errs : Vec<usize> = (0..*nerr).map(|i| i * 7 + 2).collect();
+		let ers : Vec<usize> = (0..*ners).map(|i| i * 3 + 1).filter(|p| !errs.contains(p)).collect();
+		for e in errs.iter() { word[*e] ^= 0x81 }
+		for e in ers.iter() { word[*e] ^= 0x33 }
+		let mut changed = code.decode(&f, &mut word, &ers).unwrap();
+		assert_eq!(word, clean);
+		changed.sort();
+		let mut expect : Vec<usize> = errs.iter().chain(ers.iter()).cloned().collect();
+		expect.sort();
+		assert_eq!(changed, expect);
+	    }
+	}
+    }
+
+    #[test]
+    fn rs_code_reports_uncorrectable() {
+	let f = new_gf8(0x11d, 0x1d);
+	let code = RsCode::new(&f, 255, 239, 0, 2).unwrap();
+	let msg = vec![0x42u8; 239];
+	let clean = code.encode(&f, &msg).unwrap();
+	let mut failed = 0;
+	for trial in 0..20 {
+	    let mut word = clean.clone();
+	    // 9 errors exceeds t = 8
+	    for i in 0..9 { word[(i * 29 + trial * 3) % 255] ^= (trial + i + 1) as u8 }
+	    let before = word.clone();
+	    match code.decode(&f, &mut word, &[]) {
+		Err(e) => { assert_eq!(e, RsError::Uncorrectable); assert_eq!(word, before); failed += 1 }
+		Ok(_)  => assert_ne!(word, clean), // miscorrected to another codeword
+	    }
+	}
+	assert!(failed > 15);
+	let mut word = clean.clone();
+	assert_eq!(code.decode(&f, &mut word, &[3, 3]).err(), Some(RsError::BadErasure));
+	assert_eq!(code.decode(&f, &mut word, &(0..17).collect::<Vec<_>>()).err(), Some(RsError::Uncorrectable));
+	assert_eq!(RsCode::new(&f, 255, 239, 0, 1).err(), Some(RsError::BadParameters));
+    }
+
+    #[test]
+    fn rs_code_large_fcr_wraps() {
+	// α^255 = 1, so fcr = 256 is the same code as fcr = 1
+	let f = new_gf8(0x11d, 0x1d);
+	let a = RsCode::new(&f, 100, 80, 1, 2).unwrap();
+	let b = RsCode::new(&f, 100, 80, 256, 2).unwrap();
+	assert_eq!(a.generator_poly(), b.generator_poly());
+	let msg : Vec<u8> = (0..80).collect();
+	let clean = b.encode(&f, &msg).unwrap();
+	let mut word = clean.clone();
+	word[7] ^= 0x55;
+	word[90] ^= 1;
+	assert_eq!(b.decode(&f, &mut word, &[40]), Ok(vec![7, 90]));
+	assert_eq!(word, clean);
+	assert!(RsCode::new(&f, 100, 80, usize::MAX, 2).is_ok());
+    }
+
+    #[test]
+    fn rs_code_gf16() {
+	let f = new_gf16_0x1002b();
+	let code = RsCode::new(&f, 1000, 980, 1, 2).unwrap();
+	let msg : Vec<u16> = (0..980).map(|i| (i * 4097) as u16).collect();
+	let mut word = code.encode(&f, &msg).unwrap();
+	for i in 0..6 { word[i * 150 + 10] ^= 0xbeef }
+	word[999] = 0;
+	word[500] = 0;
+	code.decode(&f, &mut word, &[999, 500]).unwrap();
+	assert_eq!(&word[20..], &msg[..]);
+    }
+
+    #[test]
+    fn rs_code_gf32_shortened() {
+	// a full scan of GF(2^32) would never finish
+	let f = new_gf32(0x1_0000_008d, 0x8d);
+	let code = RsCode::new(&f, 40, 30, 1, 2).unwrap();
+	let msg : Vec<u32> = (0..30).map(|i| (i as u32).wrapping_mul(0x9e37_79b9)).collect();
+	let clean = code.encode(&f, &msg).unwrap();
+	let mut word = clean.clone();
+	word[3] ^= 0xdead_beef;
+	word[33] ^= 1;
+	word[20] = 0;
+	word[21] = 0;
+	assert_eq!(code.decode(&f, &mut word, &[20, 21]), Ok(vec![3, 20, 21, 33]));
+	assert_eq!(word, clean);
+    }
+}
@@ -0,0 +1,553 @@
+//! # Shamir secret sharing
+//!
+//! Shamir's scheme splits a secret into n shares such that any t of
+//! them (the *threshold*) can recover it, while t-1 or fewer reveal
+//! nothing at all about it. Each byte (or pair of bytes) of the
+//! secret is the constant term of a random polynomial of degree t-1,
+//! and share i holds the value of every such polynomial at x = i.
+//! Recovery is Lagrange interpolation at x = 0.
+//!
+//! Unlike [crate::ida], shares are each as big as the secret, and
+//! the field is chosen automatically: GF(2<sup>8</sup>) (the AES
+//! field, [crate::good::new_gf8_0x11b]) for up to 255 shares, and
+//! GF(2<sup>16</sup>) ([crate::good::new_gf16_0x1002b]) for up to
+//! 65535. Each [Share] records its x-coordinate and field.
+//!
+//! Randomness comes from a [RandomSource], which is implemented for
+//! any `FnMut(&mut [u8])`. This crate has no random number generator
+//! of its own, and the security of the scheme rests entirely on the
+//! one passed in: use the operating system's CSPRNG (eg, by wrapping
+//! `rand::rngs::OsRng`) rather than anything like the toy generator
+//! below.
+//!
+//! ```rust
+//! use guff::shamir::{split, combine};
+//!
+//! // NOT cryptographically secure: for illustration only
+//! let mut state = 0x2545_f491_4f6c_dd1du64;
+//! let mut rng = |buf : &mut [u8]| for b in buf.iter_mut() {
+//!     state ^= state << 13; state ^= state >> 7; state ^= state << 17;
+//!     *b = state as u8;
+//! };
+//!
+//! let secret = b"correct horse battery staple";
+//! let shares = split(secret, 3, 5, &mut rng).unwrap();
+//!
+//! let some = vec![shares[3].clone(), shares[0].clone(), shares[4].clone()];
+//! assert_eq!(combine(&some).unwrap(), secret.to_vec());
+//! assert!(combine(&shares[..2]).is_err());
+//! ```
+//!
+//! ## Refreshing and resharing
+//!
+//! Existing shares can be changed without ever putting the secret
+//! back together:
+//!
+//! * [refresh] adds a random polynomial with a zero constant term to
+//!   every share, so that old shares can't be mixed with new ones
+//!   (proactive security);
+//! * [reshare] turns a (t, n) sharing into a (t', n') sharing: each
+//!   of t holders shares out their own share, and the new share j is
+//!   the Lagrange-weighted sum of the sub-shares for j;
+//! * [recover_share] rebuilds a lost share at a given x-coordinate
+//!   from any t others.
+//!
+//! These are written as single functions for convenience, but each
+//! step only uses data that one shareholder would have, so they map
+//! directly onto the usual distributed protocols.
+
+use crate::GaloisField;
+use crate::matrix::Matrix;
+use crate::good::{new_gf8_0x11b, new_gf16_0x1002b};
+use crate::bytes::{ElementBytes, Endian, elements_from_bytes, elements_to_bytes};
+
+use num::{Zero,One,FromPrimitive};
+use std::fmt;
+
+/// A source of (secure) random bytes
+pub trait RandomSource {
+    /// Fill `dest` with random bytes
+    fn fill_bytes(&mut self, dest : &mut [u8]);
+}
+
+impl<F> RandomSource for F where F : FnMut(&mut [u8]) {
+    fn fill_bytes(&mut self, dest : &mut [u8]) { self(dest) }
+}
+
+/// Which field a sharing was done in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareField {
+    /// GF(2<sup>8</sup>) with polynomial 0x11b, one byte per element
+    F8,
+    /// GF(2<sup>16</sup>) with polynomial 0x1002b, two bytes
+    /// (big-endian) per element
+    F16,
+}
+
+impl ShareField {
+    /// Smallest field that allows this many shares
+    pub fn for_shares(n : usize) -> Option<ShareField> {
+	match n {
+	    0..=255     => Some(ShareField::F8),
+	    256..=65535 => Some(ShareField::F16),
+	    _ => None
+	}
+    }
+
+    /// Largest number of shares (and largest x-coordinate)
+    pub fn max_shares(&self) -> usize {
+	match self {
+	    ShareField::F8  => 255,
+	    ShareField::F16 => 65535,
+	}
+    }
+}
+
+/// Errors from [split] and [combine]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShamirError {
+    /// Threshold or share count out of range (need 1 ≤ t ≤ n ≤ 65535)
+    BadParameters,
+    /// Fewer than threshold shares supplied
+    NotEnoughShares,
+    /// Shares come from different sharings, are malformed, or don't
+    /// agree on the secret
+    InconsistentShares,
+    /// The same x-coordinate was supplied twice
+    DuplicateShare,
+}
+
+impl fmt::Display for ShamirError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    ShamirError::BadParameters      => write!(f, "bad threshold or share count"),
+	    ShamirError::NotEnoughShares    => write!(f, "not enough shares to combine"),
+	    ShamirError::InconsistentShares => write!(f, "shares are inconsistent"),
+	    ShamirError::DuplicateShare     => write!(f, "duplicate share"),
+	}
+    }
+}
+
+impl std::error::Error for ShamirError {}
+
+/// One share of a secret
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    /// Field the sharing was done in
+    pub field     : ShareField,
+    /// x-coordinate of this share (never zero)
+    pub x         : usize,
+    /// Number of shares needed to recover the secret
+    pub threshold : usize,
+    /// Length of the secret in bytes
+    pub len       : usize,
+    /// The share's y-values, as bytes
+    pub data      : Vec<u8>,
+}
+
+/// Lagrange basis coefficients for interpolating at `at`
+///
+/// If p has degree less than `xs.len()`, then p(at) is the sum of
+/// w<sub>i</sub>·p(x<sub>i</sub>). The x<sub>i</sub> must be distinct.
+pub fn lagrange_coefficients<G>(f : &G, xs : &[G::E], at : G::E) -> Vec<G::E>
+where G : GaloisField
+{
+    xs.iter().enumerate().map(|(i, xi)| {
+	let mut num = G::E::one();
+	let mut den = G::E::one();
+	for (j, xj) in xs.iter().enumerate() {
+	    if i == j { continue }
+	    num = f.mul(num, at ^ *xj);
+	    den = f.mul(den, *xi ^ *xj);
+	}
+	f.div(num, den)
+    }).collect()
+}
+
+// Evaluate, at each of xs, the polynomials with the given constant
+// terms and t-1 random higher coefficients. Each polynomial is one
+// column; result i holds the values at xs[i].
+fn deal<G, R>(f : &G, constant : &[G::E], t : usize, xs : &[G::E], rng : &mut R)
+	      -> Vec<Vec<G::E>>
+where G : GaloisField, G::E : ElementBytes, R : RandomSource + ?Sized
+{
+    let len = constant.len();
+    let mut coeffs = vec![constant.to_vec()];
+    let mut buf = vec![0u8; len * G::E::BYTES];
+    for _ in 1..t {
+	rng.fill_bytes(&mut buf);
+	coeffs.push(elements_from_bytes(&buf, Endian::Big));
+    }
+    let v = Matrix::vandermonde(f, xs, t);
+    let inputs : Vec<&[G::E]> = coeffs.iter().map(|c| &c[..]).collect();
+    let mut out = vec![vec![G::E::zero(); len]; xs.len()];
+    let mut outputs : Vec<&mut [G::E]> = out.iter_mut().map(|r| &mut r[..]).collect();
+    f.matrix_apply(v.as_slice(), &inputs, &mut outputs);
+    out
+}
+
+// Interpolate the values at each of `at` from the points (xs, ys)
+fn interpolate<G>(f : &G, xs : &[G::E], ys : &[&[G::E]], at : &[G::E]) -> Vec<Vec<G::E>>
+where G : GaloisField
+{
+    if at.is_empty() { return Vec::new() }
+    let mut coeffs = Vec::with_capacity(at.len() * xs.len());
+    for a in at { coeffs.extend(lagrange_coefficients(f, xs, *a)) }
+    let len = ys.first().map_or(0, |y| y.len());
+    let mut out = vec![vec![G::E::zero(); len]; at.len()];
+    let mut outputs : Vec<&mut [G::E]> = out.iter_mut().map(|r| &mut r[..]).collect();
+    f.matrix_apply(&coeffs, ys, &mut outputs);
+    out
+}
+
+fn to_elements<E : ElementBytes>(data : &[u8]) -> Vec<E> {
+    let mut padded = data.to_vec();
+    padded.resize((data.len() + E::BYTES - 1) / E::BYTES * E::BYTES, 0);
+    elements_from_bytes(&padded, Endian::Big)
+}
+
+fn to_bytes<E : ElementBytes>(elements : &[E]) -> Vec<u8> {
+    let mut bytes = vec![0u8; elements.len() * E::BYTES];
+    elements_to_bytes(elements, &mut bytes, Endian::Big);
+    bytes
+}
+
+fn split_in<G, R>(f : &G, field : ShareField, secret : &[u8], t : usize, n : usize, rng : &mut R)
+		  -> Vec<Share>
+where G : GaloisField, G::E : ElementBytes, R : RandomSource + ?Sized
+{
+    let xs : Vec<G::E> = (1..=n).map(|x| G::E::from_usize(x).unwrap()).collect();
+    let ys = deal(f, &to_elements::<G::E>(secret), t, &xs, rng);
+    ys.into_iter().enumerate().map(|(i, y)| Share {
+	field, x : i + 1, threshold : t, len : secret.len(), data : to_bytes(&y)
+    }).collect()
+}
+
+/// Split `secret` into `shares` shares, any `threshold` of which
+/// recover it
+pub fn split<R>(secret : &[u8], threshold : usize, shares : usize, rng : &mut R)
+		-> Result<Vec<Share>, ShamirError>
+where R : RandomSource + ?Sized
+{
+    if threshold == 0 || threshold > shares { return Err(ShamirError::BadParameters) }
+    let field = ShareField::for_shares(shares).ok_or(ShamirError::BadParameters)?;
+    Ok(match field {
+	ShareField::F8  => split_in(&new_gf8_0x11b(), field, secret, threshold, shares, rng),
+	ShareField::F16 => split_in(&new_gf16_0x1002b(), field, secret, threshold, shares, rng),
+    })
+}
+
+// Check a set of shares for consistency. Returns the common
+// (field, threshold, len). Doesn't check there are enough of them.
+fn check_shares(shares : &[Share]) -> Result<(ShareField, usize, usize), ShamirError> {
+    let first = shares.first().ok_or(ShamirError::NotEnoughShares)?;
+    let (field, t, len) = (first.field, first.threshold, first.len);
+    let bytes = match field { ShareField::F8 => 1, ShareField::F16 => 2 };
+    let data_len = (len + bytes - 1) / bytes * bytes;
+    if t == 0 || t > field.max_shares() { return Err(ShamirError::InconsistentShares) }
+    for (i, s) in shares.iter().enumerate() {
+	if s.field != field || s.threshold != t || s.len != len || s.data.len() != data_len
+	    || s.x == 0 || s.x > field.max_shares() {
+		return Err(ShamirError::InconsistentShares)
+	    }
+	if shares[..i].iter().any(|o| o.x == s.x) { return Err(ShamirError::DuplicateShare) }
+    }
+    Ok((field, t, len))
+}
+
+fn x_of<G>(s : &Share) -> G::E where G : GaloisField {
+    G::E::from_usize(s.x).unwrap()
+}
+
+// Use the first t shares to find the values at each of `at`, after
+// checking that any extra shares lie on the same polynomials
+fn interpolate_shares<G>(f : &G, shares : &[Share], t : usize, at : &[G::E])
+			 -> Result<Vec<Vec<G::E>>, ShamirError>
+where G : GaloisField, G::E : ElementBytes
+{
+    if shares.len() < t { return Err(ShamirError::NotEnoughShares) }
+    let xs : Vec<G::E> = shares[..t].iter().map(x_of::<G>).collect();
+    let ys : Vec<Vec<G::E>> = shares.iter().map(|s| elements_from_bytes(&s.data, Endian::Big)).collect();
+    let y_refs : Vec<&[G::E]> = ys[..t].iter().map(|y| &y[..]).collect();
+
+    let mut all_at = at.to_vec();
+    all_at.extend(shares[t..].iter().map(x_of::<G>));
+    let mut values = interpolate(f, &xs, &y_refs, &all_at);
+    if values[at.len()..].iter().zip(&ys[t..]).any(|(v, y)| v != y) {
+	return Err(ShamirError::InconsistentShares)
+    }
+    values.truncate(at.len());
+    Ok(values)
+}
+
+fn combine_in<G>(f : &G, shares : &[Share], t : usize, len : usize) -> Result<Vec<u8>, ShamirError>
+where G : GaloisField, G::E : ElementBytes
+{
+    let values = interpolate_shares(f, shares, t, &[G::E::zero()])?;
+    let mut secret = to_bytes(&values[0]);
+    secret.truncate(len);
+    Ok(secret)
+}
+
+/// Recover the secret from at least `threshold` shares
+///
+/// If more shares than needed are given, they are all checked
+/// against each other, and any disagreement is reported as
+/// [ShamirError::InconsistentShares].
+pub fn combine(shares : &[Share]) -> Result<Vec<u8>, ShamirError> {
+    let (field, t, len) = check_shares(shares)?;
+    match field {
+	ShareField::F8  => combine_in(&new_gf8_0x11b(), shares, t, len),
+	ShareField::F16 => combine_in(&new_gf16_0x1002b(), shares, t, len),
+    }
+}
+
+fn refresh_in<G, R>(f : &G, shares : &[Share], t : usize, rng : &mut R) -> Vec<Share>
+where G : GaloisField, G::E : ElementBytes, R : RandomSource + ?Sized
+{
+    let xs : Vec<G::E> = shares.iter().map(x_of::<G>).collect();
+    let zero = vec![G::E::zero(); shares[0].data.len() / G::E::BYTES];
+    let deltas = deal(f, &zero, t, &xs, rng);
+    shares.iter().zip(deltas).map(|(s, d)| {
+	let mut y : Vec<G::E> = elements_from_bytes(&s.data, Endian::Big);
+	f.vec_add_vec_in_place(&mut y, &d);
+	Share { data : to_bytes(&y), ..s.clone() }
+    }).collect()
+}
+
+/// Re-randomise all shares without changing the secret
+///
+/// Every share of the sharing should be passed in (the returned
+/// shares replace them one for one), since an old share will not
+/// combine with new ones. Fewer than the threshold is fine.
+pub fn refresh<R>(shares : &[Share], rng : &mut R) -> Result<Vec<Share>, ShamirError>
+where R : RandomSource + ?Sized
+{
+    let (field, t, _) = check_shares(shares)?;
+    Ok(match field {
+	ShareField::F8  => refresh_in(&new_gf8_0x11b(), shares, t, rng),
+	ShareField::F16 => refresh_in(&new_gf16_0x1002b(), shares, t, rng),
+    })
+}
+
+fn reshare_in<G, R>(f : &G, shares : &[Share], t : usize, new_t : usize, new_n : usize, rng : &mut R)
+		    -> Result<Vec<Share>, ShamirError>
+where G : GaloisField, G::E : ElementBytes, R : RandomSource + ?Sized
+{
+    // only used for checking the extra shares
+    interpolate_shares(f, shares, t, &[])?;
+    let old = &shares[..t];
+    let xs : Vec<G::E> = old.iter().map(x_of::<G>).collect();
+    let weights = lagrange_coefficients(f, &xs, G::E::zero());
+    let new_xs : Vec<G::E> = (1..=new_n).map(|x| G::E::from_usize(x).unwrap()).collect();
+
+    // holder i deals out their y-values; new holder j weights and
+    // sums what they receive
+    let elems = old[0].data.len() / G::E::BYTES;
+    let mut new_ys = vec![vec![G::E::zero(); elems]; new_n];
+    for (s, w) in old.iter().zip(weights) {
+	let y : Vec<G::E> = elements_from_bytes(&s.data, Endian::Big);
+	let sub = deal(f, &y, new_t, &new_xs, rng);
+	for (acc, part) in new_ys.iter_mut().zip(sub) {
+	    f.vec_add_scaled_vec_in_place(acc, &part, w);
+	}
+    }
+    Ok(new_ys.into_iter().enumerate().map(|(j, y)| Share {
+	field : old[0].field, x : j + 1, threshold : new_t, len : old[0].len, data : to_bytes(&y)
+    }).collect())
+}
+
+/// Redistribute a sharing as a (`threshold`, `shares`) sharing
+///
+/// Needs at least the old threshold's worth of shares. The new
+/// sharing stays in the old field, so going from GF(2<sup>8</sup>)
+/// to more than 255 shares isn't possible this way (combine and
+/// split again instead).
+pub fn reshare<R>(shares : &[Share], threshold : usize, new_shares : usize, rng : &mut R)
+		  -> Result<Vec<Share>, ShamirError>
+where R : RandomSource + ?Sized
+{
+    let (field, t, _) = check_shares(shares)?;
+    if threshold == 0 || threshold > new_shares || new_shares > field.max_shares() {
+	return Err(ShamirError::BadParameters)
+    }
+    match field {
+	ShareField::F8  => reshare_in(&new_gf8_0x11b(), shares, t, threshold, new_shares, rng),
+	ShareField::F16 => reshare_in(&new_gf16_0x1002b(), shares, t, threshold, new_shares, rng),
+    }
+}
+
+fn recover_in<G>(f : &G, shares : &[Share], t : usize, x : usize) -> Result<Share, ShamirError>
+where G : GaloisField, G::E : ElementBytes
+{
+    let values = interpolate_shares(f, shares, t, &[G::E::from_usize(x).unwrap()])?;
+    Ok(Share { x, data : to_bytes(&values[0]), ..shares[0].clone() })
+}
+
+/// Rebuild the share with x-coordinate `x` from at least threshold
+/// others
+pub fn recover_share(shares : &[Share], x : usize) -> Result<Share, ShamirError> {
+    let (field, t, _) = check_shares(shares)?;
+    if x == 0 || x > field.max_shares() { return Err(ShamirError::BadParameters) }
+    match field {
+	ShareField::F8  => recover_in(&new_gf8_0x11b(), shares, t, x),
+	ShareField::F16 => recover_in(&new_gf16_0x1002b(), shares, t, x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn test_rng(seed : u64) -> impl FnMut(&mut [u8]) {
+	let mut state = seed | 1;
+	move |buf : &mut [u8]| for b in buf.iter_mut() {
+	    state ^= state << 13; state ^= state >> 7; state ^= state << 17;
+	    *b = (state >> 24) as u8;
+	}
+    }
+
+    #[test]
+    fn any_threshold_subset() {
+	let mut rng = test_rng(1);
+	let secret = b"attack at dawn!";
+	let shares = split(secret, 3, 6, &mut rng).unwrap();
+	assert!(shares.iter().all(|s| s.field == ShareField::F8 && s.data.len() == 15));
+	for a in 0..6 { for b in a + 1..6 { for c in b + 1..6 {
+	    let sub = vec![shares[c].clone(), shares[a].clone(), shares[b].clone()];
+	    assert_eq!(combine(&sub).unwrap(), secret.to_vec());
+	}}}
+	assert_eq!(combine(&shares).unwrap(), secret.to_vec());
+	// threshold 1 means every share is the secret
+	let ones = split(secret, 1, 3, &mut rng).unwrap();
+	assert_eq!(ones[2].data, secret.to_vec());
+    }
+
+    #[test]
+    fn many_shares_use_gf16() {
+	let mut rng = test_rng(2);
+	let secret = b"odd length";
+	let shares = split(&secret[..9], 10, 300, &mut rng).unwrap();
+	assert_eq!(shares[0].field, ShareField::F16);
+	assert_eq!(shares[299].x, 300);
+	assert_eq!(shares[0].data.len(), 10);
+	let sub : Vec<Share> = shares.iter().skip(250).take(10).cloned().collect();
+	assert_eq!(combine(&sub).unwrap(), secret[..9].to_vec());
+    }
+
+    #[test]
+    fn one_share_fits_every_secret() {
+	// t = 2: for any secret s there is a line through (0, s) and
+	// the share we hold, so that share says nothing about s
+	let f = new_gf8_0x11b();
+	let mut rng = test_rng(3);
+	let shares = split(&[0x5a], 2, 3, &mut rng).unwrap();
+	let held = &shares[0];
+	let (x, y) = (held.x as u8, held.data[0]);
+	let mut forged_values = Vec::new();
+	for secret in 0..=255u8 {
+	    // slope a with secret + a·x = y, then a second point on it
+	    let a = f.div(y ^ secret, x);
+	    let other = Share { x : 3, data : vec![secret ^ f.mul(a, 3)], ..held.clone() };
+	    assert_eq!(combine(&[held.clone(), other.clone()]).unwrap(), vec![secret]);
+	    forged_values.push(other.data[0]);
+	}
+	// and each secret needs a different second share
+	forged_values.sort();
+	forged_values.dedup();
+	assert_eq!(forged_values.len(), 256);
+    }
+
+    #[test]
+    fn clear_errors() {
+	let mut rng = test_rng(4);
+	assert_eq!(split(b"x", 0, 3, &mut rng).err(), Some(ShamirError::BadParameters));
+	assert_eq!(split(b"x", 4, 3, &mut rng).err(), Some(ShamirError::BadParameters));
+	assert_eq!(split(b"x", 2, 65536, &mut rng).err(), Some(ShamirError::BadParameters));
+
+	let shares = split(b"secret", 3, 5, &mut rng).unwrap();
+	assert_eq!(combine(&[]).err(), Some(ShamirError::NotEnoughShares));
+	assert_eq!(combine(&shares[..2]).err(), Some(ShamirError::NotEnoughShares));
+	let dup = vec![shares[0].clone(), shares[1].clone(), shares[0].clone()];
+	assert_eq!(combine(&dup).err(), Some(ShamirError::DuplicateShare));
+
+	// a share from a different sharing of the same secret
+	let other = split(b"secret", 3, 5, &mut rng).unwrap();
+	let mixed = vec![shares[0].clone(), shares[1].clone(), shares[2].clone(), other[3].clone()];
+	assert_eq!(combine(&mixed).err(), Some(ShamirError::InconsistentShares));
+
+	let mut bad = shares.clone();
+	bad[1].data.pop();
+	assert_eq!(combine(&bad).err(), Some(ShamirError::InconsistentShares));
+	let mut bad = shares.clone();
+	bad[2].x = 0;
+	assert_eq!(combine(&bad).err(), Some(ShamirError::InconsistentShares));
+	let mut bad = shares.clone();
+	bad[2].threshold = 2;
+	assert_eq!(combine(&bad).err(), Some(ShamirError::InconsistentShares));
+    }
+
+    #[test]
+    fn lagrange_interpolates() {
+	let f = new_gf8_0x11b();
+	let p = [7u8, 0x53, 0xca];
+	let xs = [1u8, 2, 3];
+	let ys : Vec<u8> = xs.iter().map(|x| crate::poly::eval(&f, &p, *x)).collect();
+	for at in [0u8, 9, 200].iter() {
+	    let w = lagrange_coefficients(&f, &xs, *at);
+	    assert_eq!(f.vec_dot_product(&w, &ys), crate::poly::eval(&f, &p, *at));
+	}
+    }
+
+    #[test]
+    fn refresh_keeps_secret() {
+	let mut rng = test_rng(5);
+	let secret = b"refresh me";
+	let old = split(secret, 3, 5, &mut rng).unwrap();
+	let new = refresh(&old, &mut rng).unwrap();
+	assert!(old.iter().zip(&new).all(|(a, b)| a.x == b.x && a.data != b.data));
+	assert_eq!(combine(&new[2..]).unwrap(), secret.to_vec());
+	// old and new don't mix
+	let mixed = vec![old[0].clone(), new[1].clone(), new[2].clone()];
+	assert_ne!(combine(&mixed).unwrap(), secret.to_vec());
+	let mixed = vec![old[0].clone(), old[1].clone(), old[2].clone(), new[3].clone()];
+	assert_eq!(combine(&mixed).err(), Some(ShamirError::InconsistentShares));
+
+	let wide = split(secret, 4, 400, &mut rng).unwrap();
+	let wide = refresh(&wide, &mut rng).unwrap();
+	assert_eq!(combine(&wide[100..104]).unwrap(), secret.to_vec());
+    }
+
+    #[test]
+    fn reshare_changes_threshold() {
+	let mut rng = test_rng(6);
+	let secret = b"the eagle has landed";
+	let old = split(secret, 3, 5, &mut rng).unwrap();
+	let new = reshare(&old[1..4], 5, 8, &mut rng).unwrap();
+	assert_eq!(new.len(), 8);
+	assert!(new.iter().all(|s| s.threshold == 5));
+	assert_eq!(combine(&new[3..]).unwrap(), secret.to_vec());
+	assert_eq!(combine(&new[..4]).err(), Some(ShamirError::NotEnoughShares));
+	// and back down again
+	let down = reshare(&new[..5], 2, 3, &mut rng).unwrap();
+	assert_eq!(combine(&down[1..]).unwrap(), secret.to_vec());
+
+	assert_eq!(reshare(&old[..2], 2, 3, &mut rng).err(), Some(ShamirError::NotEnoughShares));
+	assert_eq!(reshare(&old, 2, 300, &mut rng).err(), Some(ShamirError::BadParameters));
+    }
+
+    #[test]
+    fn recover_lost_share() {
+	let mut rng = test_rng(7);
+	let secret = b"lost and found";
+	let shares = split(secret, 4, 7, &mut rng).unwrap();
+	let others : Vec<Share> = shares.iter().filter(|s| s.x != 3).take(4).cloned().collect();
+	assert_eq!(recover_share(&others, 3).unwrap(), shares[2]);
+	assert_eq!(recover_share(&others, 0).err(), Some(ShamirError::BadParameters));
+	assert_eq!(recover_share(&others[..3], 3).err(), Some(ShamirError::NotEnoughShares));
+
+	let wide = split(secret, 3, 1000, &mut rng).unwrap();
+	assert_eq!(recover_share(&wide[500..503], 1).unwrap(), wide[0]);
+    }
+}
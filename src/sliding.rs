@@ -0,0 +1,507 @@
+//! # Sliding-window RLC forward error correction
+//!
+//! Block codes like [crate::rs] have to wait for a whole block of
+//! source symbols before sending any repair, which is too much
+//! latency for real-time streams. In the sliding-window scheme of RFC
+//! 8681, each repair symbol is instead a random linear combination of
+//! the most recent source symbols (the *encoding window*), which
+//! moves forward by one as each source symbol is sent.
+//!
+//! The decoder keeps a linear system in the source symbols it is
+//! missing. Each source symbol that arrives is substituted into it,
+//! each repair symbol adds an equation, and a lost symbol is handed
+//! back as soon as the equations pin it down. Symbols that fall out
+//! of the decoder's window are dropped, along with any equations
+//! that still depend on lost symbols out there.
+//!
+//! Everything follows RFC 8681 for GF(2<sup>8</sup>) (m = 8):
+//! polynomial 0x11d, coefficients from [coding_coefficients] (which
+//! uses the TinyMT32 generator of RFC 8682, seeded with the repair
+//! key) and the 8-byte repair FEC Payload ID in [RepairPacket]. The
+//! only simplification is that ESIs don't wrap around.
+//!
+//! ```rust
+//! use guff::sliding::{SwEncoder, SwDecoder};
+//!
+//! let mut enc = SwEncoder::new(4, 8, 15).unwrap();
+//! let mut dec = SwDecoder::new(4, 16).unwrap();
+//!
+//! let mut recovered = Vec::new();
+//! for i in 0..20u8 {
+//!     let esi = enc.push_source(&[i; 4]).unwrap();
+//!     // lose source symbols 5 and 6
+//!     if esi != 5 && esi != 6 {
+//!         recovered.extend(dec.push_source(esi, &[i; 4]).unwrap());
+//!     }
+//!     // one repair symbol after every third source symbol
+//!     if i % 3 == 2 {
+//!         let repair = enc.repair().unwrap();
+//!         recovered.extend(dec.push_repair(&repair).unwrap());
+//!     }
+//! }
+//! assert_eq!(recovered, vec![(5, vec![5; 4]), (6, vec![6; 4])]);
+//! ```
+
+use crate::{GaloisField, F8, new_gf8};
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+/// Errors from the sliding-window encoder and decoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwError {
+    /// Symbol size, window size or density out of range
+    BadParameters,
+    /// Symbol isn't the agreed size
+    WrongSize,
+    /// Repair packet is malformed
+    BadPacket,
+}
+
+impl fmt::Display for SwError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    SwError::BadParameters => write!(f, "bad sliding window parameters"),
+	    SwError::WrongSize     => write!(f, "symbol has the wrong size"),
+	    SwError::BadPacket     => write!(f, "malformed repair packet"),
+	}
+    }
+}
+
+impl std::error::Error for SwError {}
+
+/// The TinyMT32 generator, as specified in RFC 8682
+///
+/// This uses the parameter set from the RFC (mat1 = 0x8f7011ee, mat2
+/// = 0xfc78ff1f, tmat = 0x3793fdff). It is not cryptographically
+/// secure; it is only here so that both ends can generate the same
+/// coefficients from a repair key.
+#[derive(Debug, Clone)]
+pub struct TinyMt32 {
+    status : [u32; 4],
+}
+
+const MAT1 : u32 = 0x8f70_11ee;
+const MAT2 : u32 = 0xfc78_ff1f;
+const TMAT : u32 = 0x3793_fdff;
+
+impl TinyMt32 {
+    /// Seed a new generator
+    pub fn new(seed : u32) -> TinyMt32 {
+	let mut s = [seed, MAT1, MAT2, TMAT];
+	for i in 1..8u32 {
+	    let prev = s[((i - 1) & 3) as usize];
+	    s[(i & 3) as usize] ^= i.wrapping_add(1_812_433_253u32.wrapping_mul(prev ^ (prev >> 30)));
+	}
+	// period certification
+	if s[0] & 0x7fff_ffff == 0 && s[1] == 0 && s[2] == 0 && s[3] == 0 {
+	    s = [b'T' as u32, b'I' as u32, b'N' as u32, b'Y' as u32];
+	}
+	let mut t = TinyMt32 { status : s };
+	for _ in 0..8 { t.next_state() }
+	t
+    }
+
+    fn next_state(&mut self) {
+	let s = &mut self.status;
+	let mut x = (s[0] & 0x7fff_ffff) ^ s[1] ^ s[2];
+	let mut y = s[3];
+	x ^= x << 1;
+	y ^= (y >> 1) ^ x;
+	s[0] = s[1];
+	s[1] = s[2];
+	s[2] = x ^ (y << 10);
+	s[3] = y;
+	let mask = 0u32.wrapping_sub(y & 1);
+	s[1] ^= mask & MAT1;
+	s[2] ^= mask & MAT2;
+    }
+
+    /// Next 32-bit output
+    pub fn next_u32(&mut self) -> u32 {
+	self.next_state();
+	let s = &self.status;
+	let t1 = s[0].wrapping_add(s[2] >> 8);
+	let t0 = s[3] ^ t1;
+	t0 ^ (0u32.wrapping_sub(t1 & 1) & TMAT)
+    }
+}
+
+/// The RFC 8681 coding coefficients for a repair key (m = 8)
+///
+/// `dt` is the density threshold, 0 to 15: with 15, every
+/// coefficient is non-zero; otherwise each is non-zero with
+/// probability (dt + 1)/16.
+pub fn coding_coefficients(repair_key : u16, nss : usize, dt : u8) -> Vec<u8> {
+    assert!(dt <= 15, "density threshold must be at most 15");
+    let mut prng = TinyMt32::new(repair_key as u32);
+    let nonzero = |p : &mut TinyMt32| loop {
+	let c = p.next_u32() as u8;
+	if c != 0 { return c }
+    };
+    (0..nss).map(|_| {
+	if dt == 15 || prng.next_u32() & 0xf <= dt as u32 { nonzero(&mut prng) } else { 0 }
+    }).collect()
+}
+
+/// A repair symbol with its RFC 8681 FEC Payload ID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairPacket {
+    /// Seed for the coding coefficients
+    pub repair_key : u16,
+    /// Density threshold (0 to 15)
+    pub dt         : u8,
+    /// ESI of the first source symbol in the encoding window
+    pub first_esi  : u32,
+    /// Number of source symbols in the encoding window (up to 4095)
+    pub nss        : u16,
+    /// The repair symbol itself
+    pub data       : Vec<u8>,
+}
+
+impl RepairPacket {
+    /// Serialise as the 8-byte repair FEC Payload ID followed by
+    /// the repair symbol
+    pub fn to_bytes(&self) -> Vec<u8> {
+	let mut out = Vec::with_capacity(8 + self.data.len());
+	out.extend_from_slice(&self.repair_key.to_be_bytes());
+	out.extend_from_slice(&(((self.dt as u16) << 12) | self.nss).to_be_bytes());
+	out.extend_from_slice(&self.first_esi.to_be_bytes());
+	out.extend_from_slice(&self.data);
+	out
+    }
+
+    /// Parse the format written by [RepairPacket::to_bytes]
+    pub fn from_bytes(bytes : &[u8]) -> Result<RepairPacket, SwError> {
+	if bytes.len() < 8 { return Err(SwError::BadPacket) }
+	let dt_nss = u16::from_be_bytes([bytes[2], bytes[3]]);
+	Ok(RepairPacket {
+	    repair_key : u16::from_be_bytes([bytes[0], bytes[1]]),
+	    dt         : (dt_nss >> 12) as u8,
+	    nss        : dt_nss & 0xfff,
+	    first_esi  : u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+	    data       : bytes[8..].to_vec(),
+	})
+    }
+}
+
+/// The RFC 8681 field: GF(2<sup>8</sup>) with polynomial 0x11d
+fn field() -> F8 { new_gf8(0x11d, 0x1d) }
+
+/// Sliding-window encoder
+pub struct SwEncoder {
+    f           : F8,
+    symbol_size : usize,
+    window_size : usize,
+    dt          : u8,
+    window      : VecDeque<Vec<u8>>,
+    next_esi    : u32,
+    repair_key  : u16,
+}
+
+impl SwEncoder {
+    /// New encoder with the given symbol size, maximum encoding
+    /// window (1 to 4095 symbols) and density threshold (0 to 15)
+    pub fn new(symbol_size : usize, window_size : usize, dt : u8) -> Result<SwEncoder, SwError> {
+	if symbol_size == 0 || window_size == 0 || window_size > 4095 || dt > 15 {
+	    return Err(SwError::BadParameters)
+	}
+	Ok(SwEncoder { f : field(), symbol_size, window_size, dt,
+		       window : VecDeque::new(), next_esi : 0, repair_key : 0 })
+    }
+
+    /// Add the next source symbol to the window, returning its ESI
+    pub fn push_source(&mut self, symbol : &[u8]) -> Result<u32, SwError> {
+	if symbol.len() != self.symbol_size { return Err(SwError::WrongSize) }
+	if self.window.len() == self.window_size { self.window.pop_front(); }
+	self.window.push_back(symbol.to_vec());
+	self.next_esi += 1;
+	Ok(self.next_esi - 1)
+    }
+
+    /// A repair symbol over the current window, or `None` before any
+    /// source symbols have been added
+    pub fn repair(&mut self) -> Option<RepairPacket> {
+	if self.window.is_empty() { return None }
+	let nss = self.window.len();
+	let repair_key = self.repair_key;
+	self.repair_key = self.repair_key.wrapping_add(1);
+	let coeffs = coding_coefficients(repair_key, nss, self.dt);
+	let inputs : Vec<&[u8]> = self.window.iter().map(|s| &s[..]).collect();
+	let mut data = vec![0u8; self.symbol_size];
+	self.f.matrix_apply(&coeffs, &inputs, &mut [&mut data[..]]);
+	Some(RepairPacket { repair_key, dt : self.dt, first_esi : self.next_esi - nss as u32,
+			    nss : nss as u16, data })
+    }
+}
+
+// one equation: Σ coeffs[esi]·symbol[esi] = rhs, over missing symbols
+struct Equation {
+    coeffs : BTreeMap<u32, u8>,
+    rhs    : Vec<u8>,
+}
+
+/// Sliding-window decoder
+pub struct SwDecoder {
+    f           : F8,
+    symbol_size : usize,
+    window_size : usize,
+    // ESIs below this have left the window
+    start       : u32,
+    known       : BTreeMap<u32, Vec<u8>>,
+    equations   : Vec<Equation>,
+}
+
+impl SwDecoder {
+    /// New decoder with the given symbol size and window
+    ///
+    /// The window should be at least as big as the encoder's, and
+    /// bigger if repair symbols can arrive late.
+    pub fn new(symbol_size : usize, window_size : usize) -> Result<SwDecoder, SwError> {
+	if symbol_size == 0 || window_size == 0 { return Err(SwError::BadParameters) }
+	Ok(SwDecoder { f : field(), symbol_size, window_size, start : 0,
+		       known : BTreeMap::new(), equations : Vec::new() })
+    }
+
+    /// Oldest ESI still in the decoding window
+    pub fn window_start(&self) -> u32 { self.start }
+
+    /// Number of equations waiting for more information
+    pub fn pending(&self) -> usize { self.equations.len() }
+
+    /// A source symbol, if received or recovered and still in the window
+    pub fn symbol(&self, esi : u32) -> Option<&[u8]> {
+	self.known.get(&esi).map(|s| &s[..])
+    }
+
+    // move the window so that it ends at `newest`
+    fn advance(&mut self, newest : u32) {
+	let end = newest as u64 + 1;
+	if end <= self.start as u64 + self.window_size as u64 { return }
+	self.start = (end - self.window_size as u64) as u32;
+	let start = self.start;
+	self.known = self.known.split_off(&start);
+	self.equations.retain(|e| e.coeffs.keys().all(|esi| *esi >= start));
+    }
+
+    // substitute a newly known symbol into the equations
+    fn learn(&mut self, esi : u32, symbol : Vec<u8>) {
+	for e in self.equations.iter_mut() {
+	    if let Some(c) = e.coeffs.remove(&esi) {
+		self.f.vec_add_scaled_vec_in_place(&mut e.rhs, &symbol, c);
+	    }
+	}
+	self.equations.retain(|e| !e.coeffs.is_empty());
+	self.known.insert(esi, symbol);
+    }
+
+    /// Take in a received source symbol
+    ///
+    /// Returns any lost symbols that can now be recovered, as (ESI,
+    /// symbol) pairs in ESI order.
+    pub fn push_source(&mut self, esi : u32, symbol : &[u8]) -> Result<Vec<(u32, Vec<u8>)>, SwError> {
+	if symbol.len() != self.symbol_size { return Err(SwError::WrongSize) }
+	self.advance(esi);
+	if esi < self.start || self.known.contains_key(&esi) { return Ok(Vec::new()) }
+	self.learn(esi, symbol.to_vec());
+	Ok(self.solve())
+    }
+
+    /// Take in a repair symbol
+    ///
+    /// Returns any lost symbols that can now be recovered, as (ESI,
+    /// symbol) pairs in ESI order.
+    pub fn push_repair(&mut self, p : &RepairPacket) -> Result<Vec<(u32, Vec<u8>)>, SwError> {
+	if p.data.len() != self.symbol_size { return Err(SwError::WrongSize) }
+	if p.dt > 15 || p.nss == 0 || p.nss > 4095 { return Err(SwError::BadPacket) }
+	let last = p.first_esi.checked_add(p.nss as u32 - 1).ok_or(SwError::BadPacket)?;
+	self.advance(last);
+	let coeffs = coding_coefficients(p.repair_key, p.nss as usize, p.dt);
+	let mut e = Equation { coeffs : BTreeMap::new(), rhs : p.data.clone() };
+	for (esi, c) in (p.first_esi..=last).zip(coeffs) {
+	    if c == 0 { continue }
+	    match self.known.get(&esi) {
+		Some(s) => self.f.vec_add_scaled_vec_in_place(&mut e.rhs, s, c),
+		// can't use it if an unknown symbol has left the window
+		None if esi < self.start => return Ok(Vec::new()),
+		None => { e.coeffs.insert(esi, c); },
+	    }
+	}
+	if e.coeffs.is_empty() { return Ok(Vec::new()) }
+	self.equations.push(e);
+	Ok(self.solve())
+    }
+
+    // Gauss-Jordan on the pending equations; any row left with a
+    // single unknown gives that symbol
+    fn solve(&mut self) -> Vec<(u32, Vec<u8>)> {
+	let f = &self.f;
+	let mut rows = std::mem::take(&mut self.equations);
+	let vars : Vec<u32> = {
+	    let mut v : Vec<u32> = rows.iter().flat_map(|e| e.coeffs.keys().cloned()).collect();
+	    v.sort_unstable();
+	    v.dedup();
+	    v
+	};
+	let mut rank = 0;
+	for var in vars {
+	    let pivot = match (rank..rows.len()).find(|r| rows[*r].coeffs.contains_key(&var)) {
+		Some(p) => p,
+		None => continue
+	    };
+	    rows.swap(rank, pivot);
+	    let inv = f.inv(rows[rank].coeffs[&var]);
+	    for c in rows[rank].coeffs.values_mut() { *c = f.mul(*c, inv) }
+	    f.vec_constant_scale_in_place(&mut rows[rank].rhs, inv);
+	    let (pcoeffs, prhs) = (rows[rank].coeffs.clone(), rows[rank].rhs.clone());
+	    for (r, row) in rows.iter_mut().enumerate() {
+		if r == rank { continue }
+		if let Some(c) = row.coeffs.get(&var).cloned() {
+		    for (v, pc) in pcoeffs.iter() {
+			let x = row.coeffs.get(v).cloned().unwrap_or(0) ^ f.mul(c, *pc);
+			if x == 0 { row.coeffs.remove(v); } else { row.coeffs.insert(*v, x); }
+		    }
+		    f.vec_add_scaled_vec_in_place(&mut row.rhs, &prhs, c);
+		}
+	    }
+	    rank += 1;
+	}
+	rows.retain(|e| !e.coeffs.is_empty());
+
+	let mut out = Vec::new();
+	let mut pending = Vec::new();
+	for e in rows {
+	    if e.coeffs.len() == 1 {
+		let esi = *e.coeffs.keys().next().unwrap();
+		out.push((esi, e.rhs));
+	    } else {
+		pending.push(e);
+	    }
+	}
+	self.equations = pending;
+	out.sort_by_key(|(esi, _)| *esi);
+	for (esi, s) in out.iter() { self.known.insert(*esi, s.clone()); }
+	out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn tinymt32_reference_output() {
+	// from the TinyMT32 reference check output, seed 1
+	let mut t = TinyMt32::new(1);
+	let first : Vec<u32> = (0..5).map(|_| t.next_u32()).collect();
+	assert_eq!(first, vec![2545341989, 981918433, 3715302833, 2387538352, 3591001365]);
+    }
+
+    #[test]
+    fn coefficient_density() {
+	let all = coding_coefficients(7, 1000, 15);
+	assert!(all.iter().all(|c| *c != 0));
+	assert_eq!(all, coding_coefficients(7, 1000, 15));
+	assert_ne!(all, coding_coefficients(8, 1000, 15));
+	let sparse = coding_coefficients(7, 1600, 3);
+	let nz = sparse.iter().filter(|c| **c != 0).count();
+	// about a quarter non-zero
+	assert!(nz > 300 && nz < 500, "{}", nz);
+    }
+
+    #[test]
+    fn repair_packet_format() {
+	let p = RepairPacket { repair_key : 0x1234, dt : 9, first_esi : 0xdeadbeef, nss : 0x321,
+			       data : vec![1, 2, 3] };
+	let b = p.to_bytes();
+	assert_eq!(&b[..8], &[0x12, 0x34, 0x93, 0x21, 0xde, 0xad, 0xbe, 0xef]);
+	assert_eq!(RepairPacket::from_bytes(&b).unwrap(), p);
+	assert_eq!(RepairPacket::from_bytes(&b[..7]), Err(SwError::BadPacket));
+    }
+
+    #[test]
+    fn burst_recovered_as_soon_as_possible() {
+	let mut enc = SwEncoder::new(16, 10, 15).unwrap();
+	let mut dec = SwDecoder::new(16, 10).unwrap();
+	let sym = |i : u32| -> Vec<u8> { (0..16).map(|j| (i * 31 + j * 7) as u8).collect() };
+	for i in 0..6 { enc.push_source(&sym(i)).unwrap(); }
+	// lose 2, 3, 4: three repairs needed, nothing until the third
+	for i in [0u32, 1, 5].iter() { assert!(dec.push_source(*i, &sym(*i)).unwrap().is_empty()); }
+	assert!(dec.push_repair(&enc.repair().unwrap()).unwrap().is_empty());
+	assert!(dec.push_repair(&enc.repair().unwrap()).unwrap().is_empty());
+	assert_eq!(dec.pending(), 2);
+	let got = dec.push_repair(&enc.repair().unwrap()).unwrap();
+	assert_eq!(got, vec![(2, sym(2)), (3, sym(3)), (4, sym(4))]);
+	assert_eq!(dec.pending(), 0);
+    }
+
+    #[test]
+    fn old_symbols_leave_the_window() {
+	let mut enc = SwEncoder::new(4, 5, 15).unwrap();
+	let mut dec = SwDecoder::new(4, 5).unwrap();
+	// symbol 0 lost, and not repaired before the window moves on
+	for i in 0..3u8 {
+	    let esi = enc.push_source(&[i; 4]).unwrap();
+	    if esi != 0 { dec.push_source(esi, &[i; 4]).unwrap(); }
+	}
+	// by the time this arrives, symbol 0 has left the window
+	let stale = enc.repair().unwrap();
+	for i in 3..12u8 {
+	    let esi = enc.push_source(&[i; 4]).unwrap();
+	    dec.push_source(esi, &[i; 4]).unwrap();
+	}
+	assert_eq!(dec.window_start(), 7);
+	assert!(dec.symbol(0).is_none() && dec.symbol(6).is_none());
+	assert!(dec.push_repair(&stale).unwrap().is_empty());
+	assert_eq!(dec.pending(), 0);
+    }
+
+    #[test]
+    fn sparse_random_loss() {
+	let mut enc = SwEncoder::new(8, 20, 7).unwrap();
+	let mut dec = SwDecoder::new(8, 40).unwrap();
+	let mut lost = Vec::new();
+	let mut got = Vec::new();
+	let mut repairs = Vec::new();
+	for i in 0..200u32 {
+	    let s = vec![(i % 251) as u8; 8];
+	    let esi = enc.push_source(&s).unwrap();
+	    // loses every tenth symbol, starting with 7
+	    if (i * 7919) % 10 == 3 { lost.push(esi) } else { got.extend(dec.push_source(esi, &s).unwrap()) }
+	    if i % 4 == 3 {
+		let p = enc.repair().unwrap();
+		got.extend(dec.push_repair(&p).unwrap());
+		repairs.push(p);
+	    }
+	}
+	assert!(got.iter().all(|(e, s)| s == &vec![(e % 251) as u8; 8]));
+	let got_esis : Vec<u32> = got.iter().map(|(e, _)| *e).collect();
+
+	// a lost symbol must come back if some repair involves it and
+	// no other lost symbol; and no repair involves 167 or 197, so
+	// those two can't
+	let mut must = Vec::new();
+	let mut involved = Vec::new();
+	for p in repairs.iter() {
+	    let coeffs = coding_coefficients(p.repair_key, p.nss as usize, p.dt);
+	    let hit : Vec<u32> = (p.first_esi..).zip(coeffs)
+		.filter(|(esi, c)| *c != 0 && lost.contains(esi))
+		.map(|(esi, _)| esi).collect();
+	    if hit.len() == 1 { must.push(hit[0]) }
+	    involved.extend(hit);
+	}
+	must.sort_unstable();
+	must.dedup();
+	involved.sort_unstable();
+	involved.dedup();
+	assert!(must.len() > lost.len() / 2, "{:?}", must);
+	assert!(must.iter().all(|e| got_esis.contains(e)), "{:?} {:?}", must, got_esis);
+	let unreachable : Vec<u32> = lost.iter().filter(|e| !involved.contains(e)).cloned().collect();
+	assert_eq!(unreachable, vec![167, 197]);
+
+	// here the rest are all solved jointly
+	assert_eq!(got_esis, involved);
+	assert_eq!(dec.pending(), 0);
+    }
+}
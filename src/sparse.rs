@@ -0,0 +1,368 @@
+//! # Sparse matrices and inactivation decoding
+//!
+//! Fountain codes (LT, Raptor) and LDPC-style codes give rise to
+//! large systems of equations where each row only mentions a handful
+//! of unknowns. [SparseMatrix] stores each row as a list of
+//! (column, coefficient) pairs, sorted by column.
+//!
+//! [SparseMatrix::solve] uses *inactivation decoding*:
+//!
+//! * **peeling**: while some row has only one unknown left, that row
+//!   solves for it, and the unknown is struck from all other rows
+//!
+//! * **inactivation**: when no such row exists, the unknown used in
+//!   the most remaining rows is set aside ("inactivated") and treated
+//!   as if it were known, which usually lets peeling carry on
+//!
+//! * **dense phase**: eliminating the peeled unknowns leaves a small
+//!   system in just the inactivated unknowns. This is dense, so it is
+//!   handed over to [Matrix::row_reduce], which uses the field's
+//!   `vec_*` methods
+//!
+//! * **back-substitution** recovers the peeled unknowns from the
+//!   inactivated ones
+//!
+//! Each unknown (and right-hand side) is a whole packet of field
+//! elements rather than a single one:
+//!
+//! ```rust
+//! use guff::good::new_gf8_0x11b;
+//! use guff::sparse::SparseMatrix;
+//!
+//! let f = new_gf8_0x11b();
+//! let mut m = SparseMatrix::new(3);
+//! m.push_row(&[(0, 1), (1, 1)]);
+//! m.push_row(&[(1, 1), (2, 1)]);
+//! m.push_row(&[(0, 1), (2, 3)]);
+//!
+//! let x = vec![vec![1u8, 2], vec![3, 4], vec![5, 6]];
+//! let b = m.mul_packets(&f, &x);
+//! assert_eq!(m.solve(&f, &b), Ok(x));
+//! ```
+
+use crate::GaloisField;
+use crate::matrix::{Matrix, MatrixError};
+
+use num::{One,Zero};
+
+/// Sparse matrix stored as lists of (column, coefficient) per row
+pub struct SparseMatrix<G> where G : GaloisField {
+    cols : usize,
+    rows : Vec<Vec<(usize, G::E)>>,
+}
+
+impl<G> Clone for SparseMatrix<G> where G : GaloisField {
+    fn clone(&self) -> Self {
+	SparseMatrix { cols : self.cols, rows : self.rows.clone() }
+    }
+}
+
+// Result of the peeling/inactivation phase: pivots in the order
+// found, as (row, column), plus the inactivated columns
+struct Plan {
+    pivots   : Vec<(usize, usize)>,
+    inactive : Vec<usize>,
+}
+
+impl<G> SparseMatrix<G> where G : GaloisField {
+
+    /// Empty matrix (no rows) with the given number of columns
+    pub fn new(cols : usize) -> SparseMatrix<G> {
+	SparseMatrix { cols, rows : Vec::new() }
+    }
+
+    /// Add a row given as (column, coefficient) pairs
+    ///
+    /// Zero coefficients are dropped and repeated columns are summed.
+    pub fn push_row(&mut self, entries : &[(usize, G::E)]) {
+	let mut row = entries.to_vec();
+	row.sort_by_key(|(c, _)| *c);
+	let mut merged : Vec<(usize, G::E)> = Vec::with_capacity(row.len());
+	for (c, v) in row {
+	    assert!(c < self.cols, "column index out of range");
+	    match merged.last_mut() {
+		Some((lc, lv)) if *lc == c => *lv = *lv ^ v,
+		_ => merged.push((c, v)),
+	    }
+	}
+	merged.retain(|(_, v)| *v != G::E::zero());
+	self.rows.push(merged);
+    }
+
+    /// Convert from a dense matrix
+    pub fn from_dense(m : &Matrix<G>) -> SparseMatrix<G> {
+	let mut s = SparseMatrix::new(m.cols());
+	for r in 0..m.rows() {
+	    let entries : Vec<(usize, G::E)> = m.row(r).iter().cloned().enumerate().collect();
+	    s.push_row(&entries);
+	}
+	s
+    }
+
+    /// Convert to a dense matrix
+    pub fn to_dense(&self, f : &G) -> Matrix<G> {
+	let mut m = Matrix::zero(f, self.rows.len(), self.cols);
+	for (r, row) in self.rows.iter().enumerate() {
+	    for (c, v) in row { m[(r, *c)] = *v }
+	}
+	m
+    }
+
+    /// Number of rows
+    pub fn rows(&self) -> usize { self.rows.len() }
+
+    /// Number of columns
+    pub fn cols(&self) -> usize { self.cols }
+
+    /// Number of non-zero entries
+    pub fn nnz(&self) -> usize { self.rows.iter().map(|r| r.len()).sum() }
+
+    /// Non-zero entries of row r
+    pub fn row(&self, r : usize) -> &[(usize, G::E)] { &self.rows[r] }
+
+    /// Multiply by a column of packets (one per column of the matrix)
+    pub fn mul_packets(&self, f : &G, x : &[Vec<G::E>]) -> Vec<Vec<G::E>> {
+	assert_eq!(x.len(), self.cols, "need one packet per column");
+	let len = x.first().map_or(0, |p| p.len());
+	self.rows.iter().map(|row| {
+	    let mut out = vec![G::E::zero(); len];
+	    for (c, v) in row { f.vec_add_scaled_vec_in_place(&mut out, &x[*c], *v) }
+	    out
+	}).collect()
+    }
+
+    // Symbolic phase: decide pivot order and which columns to
+    // inactivate, looking only at the sparsity pattern
+    fn plan(&self) -> Plan {
+	let ncols = self.cols;
+	let mut col_rows : Vec<Vec<usize>> = vec![Vec::new(); ncols];
+	for (r, row) in self.rows.iter().enumerate() {
+	    for (c, _) in row { col_rows[*c].push(r) }
+	}
+	// active degree of each unused row and each unresolved column
+	let mut row_deg : Vec<usize> = self.rows.iter().map(|r| r.len()).collect();
+	let mut col_deg : Vec<usize> = col_rows.iter().map(|r| r.len()).collect();
+	let mut row_used = vec![false; self.rows.len()];
+	let mut col_done = vec![false; ncols];
+	let mut ripple : Vec<usize> = (0..self.rows.len()).filter(|r| row_deg[*r] == 1).collect();
+	let mut plan = Plan { pivots : Vec::new(), inactive : Vec::new() };
+	let mut remaining = ncols;
+
+	// strike a column from all rows, updating degrees
+	let remove_col = |c : usize, row_deg : &mut Vec<usize>, col_deg : &mut Vec<usize>,
+			  row_used : &Vec<bool>, ripple : &mut Vec<usize>| {
+	    for r in col_rows[c].iter() {
+		if row_used[*r] { continue }
+		row_deg[*r] -= 1;
+		if row_deg[*r] == 1 { ripple.push(*r) }
+	    }
+	    col_deg[c] = 0;
+	};
+
+	while remaining > 0 {
+	    if let Some(r) = ripple.pop() {
+		if row_used[r] || row_deg[r] != 1 { continue }
+		let c = self.rows[r].iter().map(|(c, _)| *c).find(|c| !col_done[*c]).unwrap();
+		row_used[r] = true;
+		for (c2, _) in self.rows[r].iter() {
+		    if !col_done[*c2] { col_deg[*c2] -= 1 }
+		}
+		col_done[c] = true;
+		remaining -= 1;
+		plan.pivots.push((r, c));
+		remove_col(c, &mut row_deg, &mut col_deg, &row_used, &mut ripple);
+	    } else {
+		// inactivate the unresolved column in most unused rows
+		let c = (0..ncols).filter(|c| !col_done[*c])
+		    .max_by_key(|c| (col_deg[*c], std::cmp::Reverse(*c))).unwrap();
+		col_done[c] = true;
+		remaining -= 1;
+		plan.inactive.push(c);
+		remove_col(c, &mut row_deg, &mut col_deg, &row_used, &mut ripple);
+	    }
+	}
+	plan
+    }
+
+    /// Solve self · x = b, where b has one packet per row
+    ///
+    /// There must be at least as many rows as columns. Extra rows are
+    /// assumed to be consistent with the rest and aren't checked.
+    /// Returns [MatrixError::Singular] if the solution isn't unique,
+    /// or [MatrixError::DimensionMismatch] if the packets are wrong.
+    pub fn solve(&self, f : &G, b : &[Vec<G::E>]) -> Result<Vec<Vec<G::E>>, MatrixError> {
+	if b.len() != self.rows.len() { return Err(MatrixError::DimensionMismatch) }
+	let len = b.first().map_or(0, |p| p.len());
+	if b.iter().any(|p| p.len() != len) { return Err(MatrixError::DimensionMismatch) }
+	if self.rows.len() < self.cols { return Err(MatrixError::Singular) }
+
+	let zero = G::E::zero();
+	let plan = self.plan();
+	let m = plan.inactive.len();
+	let mut inactive_idx = vec![usize::MAX; self.cols];
+	for (i, c) in plan.inactive.iter().enumerate() { inactive_idx[*c] = i }
+
+	// Split each row into its active sparse part and a dense tail of
+	// inactive coefficients followed by the packet
+	let mut active : Vec<Vec<(usize, G::E)>> = Vec::with_capacity(self.rows.len());
+	let mut dense  : Vec<Vec<G::E>> = Vec::with_capacity(self.rows.len());
+	for (row, packet) in self.rows.iter().zip(b) {
+	    let mut tail = vec![zero; m + len];
+	    tail[m..].copy_from_slice(packet);
+	    let mut act = Vec::new();
+	    for (c, v) in row {
+		if inactive_idx[*c] != usize::MAX { tail[inactive_idx[*c]] = *v }
+		else { act.push((*c, *v)) }
+	    }
+	    active.push(act);
+	    dense.push(tail);
+	}
+	let mut col_rows : Vec<Vec<usize>> = vec![Vec::new(); self.cols];
+	for (r, row) in active.iter().enumerate() {
+	    for (c, _) in row { col_rows[*c].push(r) }
+	}
+
+	// Eliminate each pivot column from every other row using it.
+	// By construction, the pivot row has no other active entries
+	// left by the time we get to it.
+	let mut is_pivot_row = vec![false; self.rows.len()];
+	for (r, c) in plan.pivots.iter() {
+	    is_pivot_row[*r] = true;
+	    let coef = active[*r].iter().find(|(c2, _)| c2 == c).unwrap().1;
+	    let pivot_tail = {
+		let t = &mut dense[*r];
+		let inv = f.inv(coef);
+		if inv != G::E::one() { f.vec_constant_scale_in_place(t, inv) }
+		t.clone()
+	    };
+	    active[*r] = vec![(*c, G::E::one())];
+	    for x in col_rows[*c].iter() {
+		if *x == *r || is_pivot_row[*x] { continue }
+		let pos = match active[*x].iter().position(|(c2, _)| c2 == c) {
+		    Some(p) => p,
+		    None => continue
+		};
+		let a = active[*x].swap_remove(pos).1;
+		f.vec_add_scaled_vec_in_place(&mut dense[*x], &pivot_tail, a);
+	    }
+	}
+
+	// Dense phase on the leftover rows, which now only involve
+	// inactive columns
+	let mut inactive_values : Vec<Vec<G::E>> = Vec::new();
+	if m > 0 {
+	    let rest : Vec<&Vec<G::E>> = dense.iter().enumerate()
+		.filter(|(r, _)| !is_pivot_row[*r]).map(|(_, t)| t).collect();
+	    if rest.len() < m { return Err(MatrixError::Singular) }
+	    let mut sys = Matrix::from_rows(f, &rest);
+	    sys.row_reduce(f);
+	    if sys[(m - 1, m - 1)] != G::E::one() { return Err(MatrixError::Singular) }
+	    inactive_values = (0..m).map(|i| sys.row(i)[m..].to_vec()).collect();
+	}
+
+	// Back-substitute
+	let mut x = vec![Vec::new(); self.cols];
+	for (i, c) in plan.inactive.iter().enumerate() {
+	    x[*c] = inactive_values[i].clone();
+	}
+	for (r, c) in plan.pivots.iter() {
+	    let tail = &dense[*r];
+	    let mut value = tail[m..].to_vec();
+	    for (i, v) in tail[..m].iter().enumerate() {
+		f.vec_add_scaled_vec_in_place(&mut value, &inactive_values[i], *v);
+	    }
+	    x[*c] = value;
+	}
+	Ok(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::new_gf16;
+    use crate::good::new_gf8_0x11b;
+
+    fn xorshift(seed : &mut u32) -> u32 {
+	*seed ^= *seed << 13; *seed ^= *seed >> 17; *seed ^= *seed << 5;
+	*seed
+    }
+
+    fn packets(n : usize, len : usize) -> Vec<Vec<u8>> {
+	(0..n).map(|i| (0..len).map(|j| (i * 17 + j * 5 + 1) as u8).collect()).collect()
+    }
+
+    #[test]
+    fn triangular_system_needs_no_inactivation() {
+	let f = new_gf8_0x11b();
+	let mut m = SparseMatrix::new(5);
+	m.push_row(&[(0, 3)]);
+	for c in 1..5 { m.push_row(&[(c - 1, 1), (c, 7)]) }
+	let plan = m.plan();
+	assert!(plan.inactive.is_empty());
+	assert_eq!(plan.pivots.len(), 5);
+	let x = packets(5, 8);
+	assert_eq!(m.solve(&f, &m.mul_packets(&f, &x)), Ok(x));
+    }
+
+    #[test]
+    fn random_sparse_system() {
+	let f = new_gf8_0x11b();
+	let k = 200;
+	let mut seed = 2021;
+	let mut m = SparseMatrix::new(k);
+	for _ in 0..k + 20 {
+	    let entries : Vec<(usize, u8)> = (0..3)
+		.map(|_| (xorshift(&mut seed) as usize % k, xorshift(&mut seed) as u8 | 1))
+		.collect();
+	    m.push_row(&entries);
+	}
+	// make sure every column appears somewhere
+	for c in 0..k { m.push_row(&[(c, 1), ((c + 1) % k, 2)]) }
+	let plan = m.plan();
+	assert!(plan.inactive.len() < k / 4);
+	let x = packets(k, 16);
+	assert_eq!(m.solve(&f, &m.mul_packets(&f, &x)), Ok(x));
+    }
+
+    #[test]
+    fn binary_coefficients_in_larger_field() {
+	// GF(2) system (LT-style) solved over GF(2^16)
+	let f = new_gf16(0x1002b, 0x2b);
+	let k = 50;
+	let mut seed = 7;
+	let mut m = SparseMatrix::new(k);
+	for _ in 0..2 * k {
+	    let deg = 1 + xorshift(&mut seed) as usize % 4;
+	    let entries : Vec<(usize, u16)> = (0..deg)
+		.map(|_| (xorshift(&mut seed) as usize % k, 1)).collect();
+	    m.push_row(&entries);
+	}
+	// a binary staircase guarantees full rank
+	m.push_row(&[(0, 1)]);
+	for c in 1..k { m.push_row(&[(c - 1, 1), (c, 1)]) }
+	let x : Vec<Vec<u16>> = (0..k).map(|i| vec![i as u16 * 0x0101, 0xffff]).collect();
+	let b = m.mul_packets(&f, &x);
+	let dense = m.to_dense(&f);
+	assert_eq!(dense.rank(&f), k);
+	assert_eq!(m.solve(&f, &b), Ok(x));
+	assert_eq!(SparseMatrix::from_dense(&dense).nnz(), dense.as_slice().iter().filter(|e| **e != 0).count());
+    }
+
+    #[test]
+    fn detects_singular() {
+	let f = new_gf8_0x11b();
+	let mut m = SparseMatrix::new(3);
+	m.push_row(&[(0, 1), (1, 1)]);
+	m.push_row(&[(1, 1), (2, 1)]);
+	m.push_row(&[(0, 1), (2, 1)]);
+	let x = packets(3, 4);
+	assert_eq!(m.solve(&f, &m.mul_packets(&f, &x)), Err(MatrixError::Singular));
+	// repeated columns cancel
+	let mut m = SparseMatrix::new(2);
+	m.push_row(&[(0, 5), (1, 1), (0, 5)]);
+	assert_eq!(m.row(0), &[(1, 1)]);
+	assert_eq!(m.solve(&f, &[vec![1]]), Err(MatrixError::Singular));
+    }
+}